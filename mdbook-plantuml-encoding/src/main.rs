@@ -0,0 +1,167 @@
+//! mdBook preprocessor that replaces fenced `plantuml` / `puml` code blocks and
+//! `{{#plantuml file.puml}}` directives with encoded plantuml server image links.
+//!
+//! `book.toml`:
+//!
+//! ```toml
+//! [preprocessor.plantuml-encoding]
+//! # plantuml server of the image links
+//! server = "https://www.plantuml.com/plantuml"
+//! # format of the image links: `png`, `svg` or `txt`
+//! format = "svg"
+//! # keep the source in collapsible block under the image
+//! fallback = false
+//! # only encode diagrams without sending them to the server; `false` asks
+//! # the server to render every diagram and reports syntax errors as warnings
+//! offline = true
+//! ```
+
+mod preprocess;
+
+use std::io::{self, Read, Write};
+use std::process;
+use std::time::Duration;
+
+use plantuml_encoding::FromPlantumlError;
+
+use crate::preprocess::{preprocess_book, Rendered, NAME};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const USAGE: &str = "mdBook preprocessor replacing plantuml code blocks with image links
+
+Usage:
+    mdbook-plantuml-encoding               read [context, book] JSON from stdin
+    mdbook-plantuml-encoding supports <renderer>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("supports") => process::exit(0),
+        Some("-h") | Some("--help") => println!("{}", USAGE),
+        Some("-V") | Some("--version") => println!("{} {}", NAME, env!("CARGO_PKG_VERSION")),
+        Some(arg) => {
+            eprintln!("unknown argument `{}`\n\n{}", arg, USAGE);
+            process::exit(2);
+        }
+        None => {
+            if let Err(FromPlantumlError(err)) = run() {
+                eprintln!("[{}] {}", NAME, err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn run() -> Result<(), FromPlantumlError> {
+    let mut input = String::new();
+
+    io::stdin().read_to_string(&mut input).map_err(|err| {
+        FromPlantumlError(format!(
+            "there is a problem during reading stdin: `{}`",
+            err
+        ))
+    })?;
+
+    let preprocessed = preprocess_book(&input)?;
+
+    if !preprocessed.config.offline {
+        for warning in check_rendered(&preprocessed.rendered) {
+            eprintln!("[{}] warning: {}", NAME, warning);
+        }
+    }
+
+    io::stdout()
+        .write_all(preprocessed.book.to_string().as_bytes())
+        .map_err(|err| {
+            FromPlantumlError(format!(
+                "there is a problem during writing stdout: `{}`",
+                err
+            ))
+        })
+}
+
+/// Ask the server to render every diagram and return warnings
+/// about diagrams with syntax errors or unreachable server
+fn check_rendered(rendered: &[Rendered]) -> Vec<String> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let mut warnings = vec![];
+    let mut checked: Vec<&str> = vec![];
+
+    for diagram in rendered.iter() {
+        if checked.contains(&diagram.url.as_str()) {
+            continue;
+        }
+
+        checked.push(&diagram.url);
+
+        if let Some(warning) = check_url(&agent, &diagram.url) {
+            warnings.push(format!("{} (chapter `{}`)", warning, diagram.chapter));
+        }
+    }
+
+    warnings
+}
+
+fn check_url(agent: &ureq::Agent, url: &str) -> Option<String> {
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => return Some(format!("can not render `{}`: {}", url, err)),
+    };
+
+    let error = response.header("X-PlantUML-Diagram-Error")?;
+    let line = response
+        .header("X-PlantUML-Diagram-Error-Line")
+        .map(|line| format!(" at line {}", line))
+        .unwrap_or_default();
+
+    Some(format!("diagram error{}: {} ({})", line, error, url))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::{check_rendered, Rendered};
+
+    fn serve(responses: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for (stream, response) in listener.incoming().zip(responses) {
+                let mut stream = stream.unwrap();
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer);
+
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn it_check_rendered() {
+        let server = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 400 Bad Request\r\nX-PlantUML-Diagram-Error: Syntax Error?\r\nX-PlantUML-Diagram-Error-Line: 2\r\nContent-Length: 0\r\n\r\n",
+        ]);
+        let rendered = |encoded: &str| Rendered {
+            chapter: "intro.md".to_string(),
+            url: format!("{}/svg/{}", server, encoded),
+        };
+
+        assert_eq!(
+            check_rendered(&[rendered("ok"), rendered("ok"), rendered("broken")]),
+            vec![format!(
+                "diagram error at line 2: Syntax Error? ({}/svg/broken) (chapter `intro.md`)",
+                server
+            )]
+        );
+    }
+}
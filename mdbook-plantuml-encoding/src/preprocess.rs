@@ -0,0 +1,441 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use plantuml_encoding::{
+    encode_plantuml_deflate_wrapped, markdown_fenced_blocks, plantuml_url, FromPlantumlError,
+    OutputFormat, PLANTUML_SERVER,
+};
+use serde_json::Value;
+
+/// Name of the preprocessor in `book.toml` (`[preprocessor.plantuml-encoding]`)
+pub const NAME: &str = "plantuml-encoding";
+
+const DIRECTIVE: &str = "{{#plantuml ";
+
+/// `[preprocessor.plantuml-encoding]` table of `book.toml`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Plantuml server of the image links
+    pub server: String,
+    /// Format of the image links
+    pub format: OutputFormat,
+    /// Keep the source in collapsible `<details>` block under the image
+    pub fallback: bool,
+    /// Only encode diagrams without sending them to the server to render
+    /// (`false` reports diagrams with syntax errors)
+    pub offline: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            server: PLANTUML_SERVER.to_string(),
+            format: OutputFormat::Svg,
+            fallback: false,
+            offline: true,
+        }
+    }
+}
+
+impl Config {
+    /// Config from the table of the preprocessor in `book.toml`
+    pub fn from_table(table: Option<&Value>) -> Result<Config, FromPlantumlError> {
+        let mut config = Config::default();
+
+        let table = match table {
+            Some(table) => table,
+            None => return Ok(config),
+        };
+
+        if let Some(server) = table.get("server") {
+            config.server = server
+                .as_str()
+                .ok_or_else(|| invalid_option("server", "a string"))?
+                .to_string();
+        }
+
+        if let Some(format) = table.get("format") {
+            config.format = format
+                .as_str()
+                .and_then(OutputFormat::from_name)
+                .ok_or_else(|| invalid_option("format", "`uml`, `png`, `svg` or `txt`"))?;
+        }
+
+        if let Some(fallback) = table.get("fallback") {
+            config.fallback = fallback
+                .as_bool()
+                .ok_or_else(|| invalid_option("fallback", "a boolean"))?;
+        }
+
+        if let Some(offline) = table.get("offline") {
+            config.offline = offline
+                .as_bool()
+                .ok_or_else(|| invalid_option("offline", "a boolean"))?;
+        }
+
+        Ok(config)
+    }
+}
+
+fn invalid_option(option: &str, expected: &str) -> FromPlantumlError {
+    FromPlantumlError(format!(
+        "`{}` option of `preprocessor.{}` must be {}",
+        option, NAME, expected
+    ))
+}
+
+/// Diagram found in a chapter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    /// Chapter (source path) where the diagram is
+    pub chapter: String,
+    /// Image link of the diagram
+    pub url: String,
+}
+
+/// Result of [`preprocess_book`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preprocessed {
+    /// Config of the preprocessor
+    pub config: Config,
+    /// Book JSON with diagrams replaced by image links
+    pub book: Value,
+    /// Diagrams of all chapters
+    pub rendered: Vec<Rendered>,
+}
+
+/// Process `[context, book]` JSON given by mdBook and replace diagrams
+/// of all chapters with image links
+pub fn preprocess_book(input: &str) -> Result<Preprocessed, FromPlantumlError> {
+    let input: Value = serde_json::from_str(input).map_err(|err| {
+        FromPlantumlError(format!(
+            "there is a problem during parsing mdbook input: `{}`",
+            err
+        ))
+    })?;
+
+    let (context, mut book) = match input {
+        Value::Array(mut items) if items.len() == 2 => {
+            let book = items.pop().unwrap_or_default();
+
+            (items.pop().unwrap_or_default(), book)
+        }
+        _ => {
+            return Err(FromPlantumlError(
+                "mdbook input must be `[context, book]` array".to_string(),
+            ))
+        }
+    };
+
+    let config = Config::from_table(context.pointer(&format!("/config/preprocessor/{}", NAME)))?;
+    let src = PathBuf::from(context["root"].as_str().unwrap_or(".")).join(
+        context
+            .pointer("/config/book/src")
+            .and_then(Value::as_str)
+            .unwrap_or("src"),
+    );
+
+    let mut rendered = vec![];
+
+    for key in ["sections", "items"] {
+        if let Some(Value::Array(items)) = book.get_mut(key) {
+            process_items(items, &config, &src, &mut rendered)?;
+        }
+    }
+
+    Ok(Preprocessed {
+        config,
+        book,
+        rendered,
+    })
+}
+
+fn process_items(
+    items: &mut [Value],
+    config: &Config,
+    src: &Path,
+    rendered: &mut Vec<Rendered>,
+) -> Result<(), FromPlantumlError> {
+    for item in items.iter_mut() {
+        let chapter = match item.get_mut("Chapter") {
+            Some(chapter) => chapter,
+            None => continue,
+        };
+
+        let source_path = chapter["source_path"]
+            .as_str()
+            .or_else(|| chapter["path"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        let dir = src.join(
+            Path::new(&source_path)
+                .parent()
+                .unwrap_or_else(|| Path::new("")),
+        );
+
+        if let Some(content) = chapter["content"].as_str() {
+            let mut urls = vec![];
+            let content = process_chapter(content, config, &dir, &mut urls).map_err(|err| {
+                FromPlantumlError(format!("{} (chapter `{}`)", err.0, source_path))
+            })?;
+
+            rendered.extend(urls.into_iter().map(|url| Rendered {
+                chapter: source_path.clone(),
+                url,
+            }));
+            chapter["content"] = Value::String(content);
+        }
+
+        if let Some(Value::Array(sub_items)) = chapter.get_mut("sub_items") {
+            process_items(sub_items, config, src, rendered)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace fenced `plantuml` / `puml` blocks and `{{#plantuml file.puml}}` directives
+/// (paths relative to `dir`) of the markdown with image links, everything else
+/// is kept as is
+pub fn process_chapter(
+    content: &str,
+    config: &Config,
+    dir: &Path,
+    urls: &mut Vec<String>,
+) -> Result<String, FromPlantumlError> {
+    let mut output = String::with_capacity(content.len());
+    let mut position = 0;
+
+    for block in markdown_fenced_blocks(content) {
+        process_directives(
+            &content[position..block.range.start],
+            config,
+            dir,
+            urls,
+            &mut output,
+        )?;
+
+        if block.is_plantuml() {
+            let url = diagram_url(&block.body, config)?;
+
+            output.push_str(&image(&url, &block.body, config));
+            urls.push(url);
+        } else {
+            output.push_str(&content[block.range.clone()]);
+        }
+
+        position = block.range.end;
+    }
+
+    process_directives(&content[position..], config, dir, urls, &mut output)?;
+
+    Ok(output)
+}
+
+fn process_directives(
+    text: &str,
+    config: &Config,
+    dir: &Path,
+    urls: &mut Vec<String>,
+    output: &mut String,
+) -> Result<(), FromPlantumlError> {
+    let mut rest = text;
+
+    while let Some(start) = rest.find(DIRECTIVE) {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        let file = rest[start + DIRECTIVE.len()..end].trim();
+        let path = dir.join(file);
+        let plantuml = fs::read_to_string(&path).map_err(|err| {
+            FromPlantumlError(format!(
+                "there is a problem during reading `{}`: `{}`",
+                path.display(),
+                err
+            ))
+        })?;
+        let url = diagram_url(&plantuml, config)?;
+
+        output.push_str(&rest[..start]);
+        output.push_str(&image(&url, &plantuml, config));
+        urls.push(url);
+        rest = &rest[end + 2..];
+    }
+
+    output.push_str(rest);
+
+    Ok(())
+}
+
+fn diagram_url(plantuml: &str, config: &Config) -> Result<String, FromPlantumlError> {
+    Ok(plantuml_url(
+        &config.server,
+        config.format,
+        encode_plantuml_deflate_wrapped(plantuml.trim_end_matches(['\r', '\n']))?,
+    ))
+}
+
+fn image(url: &str, plantuml: &str, config: &Config) -> String {
+    let mut image = format!("![plantuml]({})", url);
+
+    if config.fallback {
+        image += &format!(
+            "\n\n<details>\n<summary>PlantUML source</summary>\n\n```plantuml\n{}\n```\n\n</details>",
+            plantuml.trim_end_matches('\n')
+        );
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use plantuml_encoding::{FromPlantumlError, OutputFormat};
+    use serde_json::json;
+
+    use super::{preprocess_book, process_chapter, Config};
+
+    const URL: &str =
+        "https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000";
+
+    #[test]
+    fn it_process_chapter_fences() {
+        let mut urls = vec![];
+
+        assert_eq!(
+            process_chapter(
+                "# Title\n\n```plantuml\nPUML -> RUST\n```\n\n~~~rust\n{{#plantuml missing.puml}}\n~~~\n* item\n\n  ```puml\n  PUML -> RUST\n  ```\n",
+                &Config::default(),
+                Path::new("."),
+                &mut urls
+            ),
+            Ok(format!(
+                "# Title\n\n![plantuml]({})\n\n~~~rust\n{{{{#plantuml missing.puml}}}}\n~~~\n* item\n\n  ![plantuml]({})\n",
+                URL, URL
+            ))
+        );
+        assert_eq!(urls, vec![URL.to_string(), URL.to_string()]);
+    }
+
+    #[test]
+    fn it_process_chapter_fallback() {
+        let config = Config {
+            format: OutputFormat::Png,
+            fallback: true,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            process_chapter(
+                "````puml\n@startuml\nPUML -> RUST\n@enduml\n````",
+                &config,
+                Path::new("."),
+                &mut vec![]
+            ),
+            Ok(format!(
+                "![plantuml]({})\n\n<details>\n<summary>PlantUML source</summary>\n\n```plantuml\n@startuml\nPUML -> RUST\n@enduml\n```\n\n</details>",
+                URL.replace("/svg/", "/png/")
+            ))
+        );
+    }
+
+    #[test]
+    fn it_process_chapter_directive() {
+        let dir =
+            std::env::temp_dir().join(format!("mdbook_plantuml_encoding_{}", std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("small.puml"), "@startuml\nPUML -> RUST\n@enduml\n").unwrap();
+
+        assert_eq!(
+            process_chapter(
+                "See {{#plantuml small.puml}} here.\n",
+                &Config::default(),
+                &dir,
+                &mut vec![]
+            ),
+            Ok(format!("See ![plantuml]({}) here.\n", URL))
+        );
+        assert!(process_chapter(
+            "{{#plantuml missing.puml}}",
+            &Config::default(),
+            &dir,
+            &mut vec![]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_preprocess_book() {
+        let input = json!([
+            {
+                "root": ".",
+                "renderer": "html",
+                "config": {
+                    "book": { "src": "src" },
+                    "preprocessor": { "plantuml-encoding": { "server": "http://localhost:8080", "offline": true } }
+                }
+            },
+            {
+                "sections": [
+                    {
+                        "Chapter": {
+                            "name": "Intro",
+                            "content": "```plantuml\nPUML -> RUST\n```\n",
+                            "source_path": "intro.md",
+                            "sub_items": [
+                                { "Chapter": { "name": "Nested", "content": "```puml\nPUML -> RUST\n```", "source_path": "nested/index.md", "sub_items": [] } }
+                            ]
+                        }
+                    },
+                    "Separator"
+                ],
+                "__non_exhaustive": null
+            }
+        ]);
+
+        let preprocessed = preprocess_book(&input.to_string()).unwrap();
+        let book = &preprocessed.book;
+
+        assert_eq!(
+            book["sections"][0]["Chapter"]["content"],
+            "![plantuml](http://localhost:8080/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)\n"
+        );
+        assert_eq!(
+            book["sections"][0]["Chapter"]["sub_items"][0]["Chapter"]["content"],
+            "![plantuml](http://localhost:8080/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)"
+        );
+        assert_eq!(
+            preprocessed
+                .rendered
+                .iter()
+                .map(|r| r.chapter.as_str())
+                .collect::<Vec<_>>(),
+            vec!["intro.md", "nested/index.md"]
+        );
+    }
+
+    #[test]
+    fn it_config_offline_by_default() {
+        assert!(Config::default().offline);
+        assert_eq!(
+            Config::from_table(Some(&json!({ "offline": false }))).map(|c| c.offline),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn it_config_errors() {
+        assert_eq!(
+            Config::from_table(Some(&json!({ "format": "pdf" }))),
+            Err(FromPlantumlError(
+                "`format` option of `preprocessor.plantuml-encoding` must be `uml`, `png`, `svg` or `txt`"
+                    .to_string()
+            ))
+        );
+        assert!(preprocess_book("{}").is_err());
+    }
+}
@@ -0,0 +1,487 @@
+//! Procedural macros for [plantuml_encoding](https://docs.rs/plantuml_encoding/).
+//!
+//! ## Installation
+//!
+//! ```toml
+//! [dependencies]
+//! plantuml_encoding = "2.0.3"
+//! plantuml_encoding_macros = "2.0.3"
+//! ```
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use plantuml_encoding::{ClassRegistry, FromPlantumlError};
+//! use plantuml_encoding_macros::PlantUmlClass;
+//!
+//! #[derive(PlantUmlClass)]
+//! pub struct Order {
+//!     pub id: u64,
+//!     lines: Vec<Line>,
+//! }
+//!
+//! #[derive(PlantUmlClass)]
+//! pub struct Line {
+//!     pub quantity: u32,
+//! }
+//!
+//! fn main() -> Result<(), FromPlantumlError> {
+//!     let registry = ClassRegistry::new().register::<Order>().register::<Line>();
+//!
+//!     assert_eq!(
+//!         registry.diagram().plantuml()?,
+//!         "@startuml\nclass Line {\n  +quantity : u32\n}\nclass Order {\n  +id : u64\n  -lines : Vec<Line>\n}\nOrder --> \"*\" Line : lines\n@enduml"
+//!     );
+//!
+//!     let encoded_deflate = registry.encode_deflate()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use std::path::PathBuf;
+
+use plantuml_encoding::{encode_plantuml_deflate, OutputFormat, PLANTUML_SERVER};
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Fields, Ident, LitStr, Token, Type, Visibility,
+};
+
+const COLLECTIONS: [&str; 8] = [
+    "Vec",
+    "VecDeque",
+    "LinkedList",
+    "HashSet",
+    "BTreeSet",
+    "HashMap",
+    "BTreeMap",
+    "BinaryHeap",
+];
+
+const WRAPPERS: [&str; 8] = [
+    "Box", "Rc", "Arc", "Cell", "RefCell", "Mutex", "RwLock", "Cow",
+];
+
+/// Derive `plantuml_encoding::PlantUmlClass` for a struct or an enum.
+///
+/// Struct fields are rendered as members with visibility (`+` for `pub`,
+/// `~` for restricted `pub(...)`, `-` for private) and type, enum variants
+/// are rendered as members of `enum`. Types used in fields (through `Option`,
+/// collections, `Box` and other smart pointers) become references, so
+/// `plantuml_encoding::ClassRegistry` can draw relations between registered types.
+///
+/// Attributes:
+///
+/// * `#[plantuml(stereotype = "entity")]` on the type adds stereotype
+/// * `#[plantuml(package = "shop")]` on the type puts it into the package
+/// * `#[plantuml(skip)]` on the field or the variant hides it
+#[proc_macro_derive(PlantUmlClass, attributes(plantuml))]
+pub fn derive_plantuml_class(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_plantuml_class(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Encode the plantuml at compile time and expand to `&'static str` server URL
+/// (the same encoding as `plantuml_encoding::encode_plantuml_deflate`).
+///
+/// * `plantuml_url!("docs/arch.puml")` reads the file relative to the `CARGO_MANIFEST_DIR`
+///   of the crate being compiled (unreadable file is a compile error)
+/// * `plantuml_url!(inline "@startuml ... @enduml")` encodes the literal
+///
+/// Optional `format = "png"` (`svg` by default) and `server = "..."`
+/// (the public plantuml server by default) can follow the source.
+///
+/// The URL is a string literal, so it can be used in `concat!` for doc attributes.
+/// Cargo does not track the files read by procedural macros, so to rebuild the crate
+/// when a file changes, print `cargo:rerun-if-changed` for it (or its directory)
+/// from `build.rs`, e.g. `println!("cargo:rerun-if-changed=docs");`.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding_macros::plantuml_url;
+///
+/// #[doc = concat!("![puml](", plantuml_url!(inline "@startuml\nPUML -> RUST\n@enduml"), ")")]
+/// pub struct Documented;
+///
+/// assert_eq!(
+///     plantuml_url!(inline "@startuml\nPUML -> RUST\n@enduml", format = "png", server = "http://localhost:8080/"),
+///     "http://localhost:8080/png/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000"
+/// );
+/// ```
+#[proc_macro]
+pub fn plantuml_url(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as UrlInput);
+
+    match expand_plantuml_url(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct UrlInput {
+    inline: bool,
+    source: LitStr,
+    format: Option<LitStr>,
+    server: Option<LitStr>,
+}
+
+impl Parse for UrlInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let inline = if input.peek(Ident) {
+            let ident: Ident = input.parse()?;
+
+            if ident != "inline" {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "expected file path or `inline \"...\"`",
+                ));
+            }
+
+            true
+        } else {
+            false
+        };
+
+        let mut url_input = UrlInput {
+            inline,
+            source: input.parse()?,
+            format: None,
+            server: None,
+        };
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+
+            if input.is_empty() {
+                break;
+            }
+
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            if key == "format" {
+                url_input.format = Some(input.parse()?);
+            } else if key == "server" {
+                url_input.server = Some(input.parse()?);
+            } else {
+                return Err(syn::Error::new_spanned(
+                    key,
+                    "unsupported argument, expected `format` or `server`",
+                ));
+            }
+        }
+
+        Ok(url_input)
+    }
+}
+
+fn expand_plantuml_url(input: &UrlInput) -> syn::Result<TokenStream2> {
+    let plantuml = if input.inline {
+        input.source.value()
+    } else {
+        let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default())
+            .join(input.source.value());
+
+        std::fs::read_to_string(&path).map_err(|err| {
+            syn::Error::new_spanned(
+                &input.source,
+                format!("can not read `{}`: {}", path.display(), err),
+            )
+        })?
+    };
+
+    let format = match &input.format {
+        Some(format) => OutputFormat::from_name(format.value()).ok_or_else(|| {
+            syn::Error::new_spanned(format, "expected `uml`, `png`, `svg` or `txt` format")
+        })?,
+        None => OutputFormat::Svg,
+    };
+
+    let server = input
+        .server
+        .as_ref()
+        .map(LitStr::value)
+        .unwrap_or_else(|| PLANTUML_SERVER.to_string());
+
+    let encoded = encode_plantuml_deflate(plantuml)
+        .map_err(|err| syn::Error::new_spanned(&input.source, err.0))?;
+    let url = plantuml_encoding::plantuml_url(server, format, encoded);
+
+    Ok(quote!(#url))
+}
+
+#[derive(Default)]
+struct Options {
+    stereotype: Option<String>,
+    package: Option<String>,
+    skip: bool,
+}
+
+fn options(attrs: &[Attribute]) -> syn::Result<Options> {
+    let mut options = Options::default();
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("plantuml")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("stereotype") {
+                options.stereotype = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("package") {
+                options.package = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else {
+                return Err(meta.error("unsupported plantuml attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(options)
+}
+
+fn expand_plantuml_class(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let type_options = options(&input.attrs)?;
+
+    let mut members: Vec<String> = vec![];
+    let mut references: Vec<(String, String, &'static str)> = vec![];
+
+    let constructor = match &input.data {
+        Data::Struct(data) => {
+            for (index, field) in data.fields.iter().enumerate() {
+                if options(&field.attrs)?.skip {
+                    continue;
+                }
+
+                let field_name = field
+                    .ident
+                    .as_ref()
+                    .map(|i| i.to_string())
+                    .unwrap_or_else(|| index.to_string());
+
+                members.push(format!(
+                    "{}{} : {}",
+                    visibility(&field.vis),
+                    field_name,
+                    type_name(&field.ty)
+                ));
+                push_references(&mut references, &field_name, &field.ty);
+            }
+
+            quote!(class)
+        }
+        Data::Enum(data) => {
+            for variant in data.variants.iter() {
+                if options(&variant.attrs)?.skip {
+                    continue;
+                }
+
+                let variant_name = variant.ident.to_string();
+
+                let fields = match &variant.fields {
+                    Fields::Named(fields) => fields
+                        .named
+                        .iter()
+                        .map(|f| {
+                            format!(
+                                "{} : {}",
+                                f.ident.as_ref().map(|i| i.to_string()).unwrap_or_default(),
+                                type_name(&f.ty)
+                            )
+                        })
+                        .collect::<Vec<String>>(),
+                    Fields::Unnamed(fields) => {
+                        fields.unnamed.iter().map(|f| type_name(&f.ty)).collect()
+                    }
+                    Fields::Unit => vec![],
+                };
+
+                if fields.is_empty() {
+                    members.push(variant_name.clone());
+                } else {
+                    members.push(format!("{}({})", variant_name, fields.join(", ")));
+                }
+
+                for field in variant.fields.iter() {
+                    push_references(&mut references, &variant_name, &field.ty);
+                }
+            }
+
+            quote!(enumeration)
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "PlantUmlClass can be derived only for structs and enums",
+            ))
+        }
+    };
+
+    let stereotype = type_options
+        .stereotype
+        .map(|s| quote!(.stereotype(#s)))
+        .unwrap_or_default();
+    let package = type_options
+        .package
+        .map(|p| quote!(.package(#p)))
+        .unwrap_or_default();
+    let references = references.iter().map(|(field, target, multiplicity)| {
+        quote! {
+            ::plantuml_encoding::ClassReference {
+                field: #field,
+                target: #target,
+                multiplicity: #multiplicity,
+            }
+        }
+    });
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::plantuml_encoding::PlantUmlClass for #ident #type_generics #where_clause {
+            fn plantuml_element() -> ::plantuml_encoding::Element {
+                ::plantuml_encoding::Element::#constructor(#name)
+                    #stereotype
+                    #package
+                    #(.member(#members))*
+            }
+
+            fn plantuml_references() -> ::std::vec::Vec<::plantuml_encoding::ClassReference> {
+                ::std::vec![#(#references),*]
+            }
+        }
+    })
+}
+
+fn visibility(vis: &Visibility) -> &'static str {
+    match vis {
+        Visibility::Public(_) => "+",
+        Visibility::Restricted(_) => "~",
+        Visibility::Inherited => "-",
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    let mut name = ty.to_token_stream().to_string();
+
+    for (from, to) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        ("& ", "&"),
+        (" ;", ";"),
+        ("[ ", "["),
+        (" ]", "]"),
+        ("( ", "("),
+        (" )", ")"),
+    ] {
+        name = name.replace(from, to);
+    }
+
+    name
+}
+
+fn push_references(references: &mut Vec<(String, String, &'static str)>, field: &str, ty: &Type) {
+    let mut targets = vec![];
+
+    collect_targets(ty, "1", &mut targets);
+
+    for (target, multiplicity) in targets {
+        let reference = (field.to_string(), target, multiplicity);
+
+        if !references.contains(&reference) {
+            references.push(reference);
+        }
+    }
+}
+
+fn collect_targets(
+    ty: &Type,
+    multiplicity: &'static str,
+    targets: &mut Vec<(String, &'static str)>,
+) {
+    match ty {
+        Type::Path(path) => {
+            let segment = match path.path.segments.last() {
+                Some(segment) => segment,
+                None => return,
+            };
+            let name = segment.ident.to_string();
+
+            let arguments_multiplicity = if COLLECTIONS.contains(&name.as_str()) {
+                "*"
+            } else if name == "Option" {
+                if multiplicity == "1" {
+                    "0..1"
+                } else {
+                    multiplicity
+                }
+            } else {
+                if !WRAPPERS.contains(&name.as_str()) {
+                    targets.push((name, multiplicity));
+                }
+
+                multiplicity
+            };
+
+            if let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                for argument in arguments.args.iter() {
+                    if let syn::GenericArgument::Type(ty) = argument {
+                        collect_targets(ty, arguments_multiplicity, targets);
+                    }
+                }
+            }
+        }
+        Type::Reference(reference) => collect_targets(&reference.elem, multiplicity, targets),
+        Type::Ptr(pointer) => collect_targets(&pointer.elem, multiplicity, targets),
+        Type::Paren(paren) => collect_targets(&paren.elem, multiplicity, targets),
+        Type::Group(group) => collect_targets(&group.elem, multiplicity, targets),
+        Type::Array(array) => collect_targets(&array.elem, "*", targets),
+        Type::Slice(slice) => collect_targets(&slice.elem, "*", targets),
+        Type::Tuple(tuple) => {
+            for elem in tuple.elems.iter() {
+                collect_targets(elem, multiplicity, targets);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_plantuml_url, UrlInput};
+
+    #[test]
+    fn it_plantuml_url_unreadable_file() {
+        let input: UrlInput = syn::parse_str("\"tests/diagrams/missing.puml\"").unwrap();
+
+        assert!(expand_plantuml_url(&input)
+            .unwrap_err()
+            .to_string()
+            .starts_with("can not read `"));
+    }
+
+    #[test]
+    fn it_plantuml_url_arguments_error() {
+        assert!(syn::parse_str::<UrlInput>("file \"a.puml\"").is_err());
+        assert!(syn::parse_str::<UrlInput>("inline \"a\", size = \"1\"").is_err());
+
+        let input: UrlInput = syn::parse_str("inline \"a\", format = \"pdf\"").unwrap();
+
+        assert_eq!(
+            expand_plantuml_url(&input).unwrap_err().to_string(),
+            "expected `uml`, `png`, `svg` or `txt` format"
+        );
+    }
+}
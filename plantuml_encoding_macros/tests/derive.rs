@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use plantuml_encoding::{
+    decode_plantuml_deflate, ClassReference, ClassRegistry, PlantUmlClass, PLANTUML_SERVER,
+};
+use plantuml_encoding_macros::PlantUmlClass;
+
+#[derive(PlantUmlClass)]
+#[plantuml(stereotype = "entity", package = "shop")]
+pub struct Customer {
+    pub name: String,
+    pub(crate) orders: Vec<Order>,
+    address: Option<Box<Address>>,
+    #[plantuml(skip)]
+    secret: String,
+}
+
+#[derive(PlantUmlClass)]
+#[plantuml(package = "shop")]
+pub struct Order {
+    pub id: u64,
+    pub status: Status,
+    pub items: HashMap<String, &'static [u8]>,
+}
+
+#[derive(PlantUmlClass)]
+pub struct Address(pub String, u16);
+
+#[derive(PlantUmlClass)]
+pub enum Status {
+    New,
+    Paid { amount: u64 },
+    Shipped(Address, String),
+}
+
+#[derive(PlantUmlClass)]
+pub struct Wrapper<T> {
+    pub value: T,
+}
+
+#[test]
+fn it_derive_struct() {
+    assert_eq!(
+        ClassRegistry::new()
+            .register::<Customer>()
+            .diagram()
+            .plantuml()
+            .unwrap(),
+        "@startuml\npackage \"shop\" {\n  class Customer <<entity>> {\n    +name : String\n    ~orders : Vec<Order>\n    -address : Option<Box<Address>>\n  }\n}\n@enduml"
+    );
+}
+
+#[test]
+fn it_derive_references() {
+    assert_eq!(
+        Customer::plantuml_references(),
+        vec![
+            ClassReference {
+                field: "name",
+                target: "String",
+                multiplicity: "1",
+            },
+            ClassReference {
+                field: "orders",
+                target: "Order",
+                multiplicity: "*",
+            },
+            ClassReference {
+                field: "address",
+                target: "Address",
+                multiplicity: "0..1",
+            },
+        ]
+    );
+}
+
+#[test]
+fn it_derive_enum_and_tuple_struct() {
+    assert_eq!(
+        ClassRegistry::new()
+            .register::<Status>()
+            .register::<Address>()
+            .diagram()
+            .plantuml()
+            .unwrap(),
+        "@startuml\nclass Address {\n  +0 : String\n  -1 : u16\n}\nenum Status {\n  New\n  Paid(amount : u64)\n  Shipped(Address, String)\n}\nStatus --> \"1\" Address : Shipped\n@enduml"
+    );
+}
+
+#[test]
+fn it_derive_generic() {
+    assert_eq!(
+        ClassRegistry::new()
+            .register::<Wrapper<u8>>()
+            .diagram()
+            .plantuml()
+            .unwrap(),
+        "@startuml\nclass Wrapper {\n  +value : T\n}\n@enduml"
+    );
+}
+
+#[test]
+fn it_derive_registry_url() {
+    let registry = ClassRegistry::new()
+        .register::<Customer>()
+        .register::<Order>()
+        .register::<Address>()
+        .register::<Status>();
+
+    let encoded = registry.encode_deflate().unwrap();
+
+    assert_eq!(
+        decode_plantuml_deflate(&encoded),
+        Ok("@startuml\nclass Address {\n  +0 : String\n  -1 : u16\n}\nenum Status {\n  New\n  Paid(amount : u64)\n  Shipped(Address, String)\n}\npackage \"shop\" {\n  class Customer <<entity>> {\n    +name : String\n    ~orders : Vec<Order>\n    -address : Option<Box<Address>>\n  }\n  class Order {\n    +id : u64\n    +status : Status\n    +items : HashMap<String, &'static [u8]>\n  }\n}\nCustomer --> \"0..1\" Address : address\nCustomer --> \"*\" Order : orders\nOrder --> \"1\" Status : status\nStatus --> \"1\" Address : Shipped\n@enduml".to_string())
+    );
+    assert_eq!(
+        registry.url(PLANTUML_SERVER, plantuml_encoding::OutputFormat::Svg),
+        Ok(format!("{}/svg/{}", PLANTUML_SERVER, encoded))
+    );
+}
@@ -0,0 +1,33 @@
+use plantuml_encoding::{
+    encode_plantuml_deflate, plantuml_url as url, OutputFormat, PLANTUML_SERVER,
+};
+use plantuml_encoding_macros::plantuml_url;
+
+const FILE_URL: &str = plantuml_url!("tests/diagrams/small.puml");
+
+#[doc = concat!("![small](", plantuml_url!("tests/diagrams/small.puml", format = "png"), ")")]
+pub struct Documented;
+
+#[test]
+fn it_plantuml_url_file() {
+    assert_eq!(
+        FILE_URL,
+        url(
+            PLANTUML_SERVER,
+            OutputFormat::Svg,
+            encode_plantuml_deflate(include_str!("diagrams/small.puml")).unwrap()
+        )
+    );
+}
+
+#[test]
+fn it_plantuml_url_inline() {
+    assert_eq!(
+        plantuml_url!(inline "@startuml\nPUML -> RUST\n@enduml", format = "uml"),
+        "https://www.plantuml.com/plantuml/uml/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000"
+    );
+    assert_eq!(
+        plantuml_url!(inline "@startuml\nPUML -> RUST\n@enduml", server = "http://localhost:8080/",),
+        "http://localhost:8080/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000"
+    );
+}
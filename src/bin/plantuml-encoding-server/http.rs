@@ -0,0 +1,325 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use plantuml_encoding::FromPlantumlError;
+
+/// Limit of request bodies (plantuml sources are far smaller)
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Limit of the request line and headers together
+const MAX_HEAD: usize = 16 * 1024;
+
+/// Limit of the number of headers
+const MAX_HEADERS: usize = 100;
+
+/// HTTP/1.1 request
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// Path with the query
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Read the request (line, headers and `Content-Length` body),
+    /// answering `Expect: 100-continue` to the interim writer
+    /// (chunked bodies are rejected)
+    pub fn read<R: Read, W: Write>(
+        reader: R,
+        interim: &mut W,
+    ) -> Result<Request, FromPlantumlError> {
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        let mut head = MAX_HEAD;
+
+        read_line(&mut reader, &mut line, &mut head)?;
+
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+            _ => {
+                return Err(FromPlantumlError(format!(
+                    "malformed request line `{}`",
+                    line.trim_end()
+                )))
+            }
+        };
+
+        let mut headers = vec![];
+
+        loop {
+            line.clear();
+            read_line(&mut reader, &mut line, &mut head)?;
+
+            let header = line.trim_end();
+
+            if header.is_empty() {
+                break;
+            }
+
+            if headers.len() == MAX_HEADERS {
+                return Err(FromPlantumlError(format!(
+                    "request has more than {} headers",
+                    MAX_HEADERS
+                )));
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        if headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("transfer-encoding"))
+        {
+            return Err(FromPlantumlError(
+                "`Transfer-Encoding` is not supported, send the body with `Content-Length`"
+                    .to_string(),
+            ));
+        }
+
+        let length = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .map_or(Ok(0), |(_, value)| value.parse::<usize>())
+            .map_err(|_| FromPlantumlError("malformed `Content-Length` header".to_string()))?;
+
+        if length > MAX_BODY {
+            return Err(FromPlantumlError(format!(
+                "request body is larger than {} bytes",
+                MAX_BODY
+            )));
+        }
+
+        let expects_continue = headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("expect") && value.eq_ignore_ascii_case("100-continue")
+        });
+
+        if expects_continue && length > 0 {
+            interim
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .and_then(|_| interim.flush())
+                .map_err(|err| {
+                    FromPlantumlError(format!(
+                        "there is a problem during writing response: `{}`",
+                        err
+                    ))
+                })?;
+        }
+
+        let mut body = vec![0; length];
+
+        reader.read_exact(&mut body).map_err(|err| {
+            FromPlantumlError(format!(
+                "there is a problem during reading request: `{}`",
+                err
+            ))
+        })?;
+
+        Ok(Request {
+            method,
+            target,
+            headers,
+            body,
+        })
+    }
+
+    /// Path without the query
+    pub fn path(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map_or(self.target.as_str(), |(path, _)| path)
+    }
+
+    /// Query without `?`
+    pub fn query(&self) -> &str {
+        self.target.split_once('?').map_or("", |(_, query)| query)
+    }
+}
+
+/// Read the line taking its length from the remaining limit of the head
+fn read_line<R: BufRead>(
+    reader: &mut R,
+    line: &mut String,
+    remaining: &mut usize,
+) -> Result<(), FromPlantumlError> {
+    let read = reader
+        .take(*remaining as u64)
+        .read_line(line)
+        .map_err(|err| {
+            FromPlantumlError(format!(
+                "there is a problem during reading request: `{}`",
+                err
+            ))
+        })?;
+
+    *remaining -= read;
+
+    if *remaining == 0 {
+        return Err(FromPlantumlError(format!(
+            "request headers are larger than {} bytes",
+            MAX_HEAD
+        )));
+    }
+
+    Ok(())
+}
+
+/// HTTP/1.1 response (connections are closed after it)
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    /// Headers except `Content-Type`, `Content-Length` and `Connection`
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        Response {
+            status,
+            content_type,
+            headers: vec![],
+            body,
+        }
+    }
+
+    pub fn text(status: u16, text: &str) -> Self {
+        Response::new(
+            status,
+            "text/plain; charset=utf-8",
+            text.as_bytes().to_vec(),
+        )
+    }
+
+    pub fn redirect(location: &str) -> Self {
+        Response::text(302, location).header("Location", location)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        // header values can not contain line breaks
+        self.headers
+            .push((name.to_string(), value.replace(['\r', '\n'], " ")));
+        self
+    }
+
+    /// Write the status line, headers and body
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+
+        for (name, value) in self.headers.iter() {
+            write!(writer, "{}: {}\r\n", name, value)?;
+        }
+
+        writer.write_all(b"\r\n")?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        302 => "Found",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plantuml_encoding::FromPlantumlError;
+
+    use super::{Request, Response, MAX_HEAD, MAX_HEADERS};
+
+    #[test]
+    fn it_reads_request() {
+        let raw = "POST /plantuml/coder?x=1 HTTP/1.1\r\nHost: localhost\r\ncontent-length: 5\r\n\r\nA -> Bextra";
+        let mut interim = vec![];
+        let request = Request::read(raw.as_bytes(), &mut interim).unwrap();
+
+        assert_eq!(request.method, "POST");
+        assert_eq!(
+            (request.path(), request.query()),
+            ("/plantuml/coder", "x=1")
+        );
+        assert_eq!(request.body, b"A -> ");
+        assert!(interim.is_empty());
+        assert!(Request::read("\r\n".as_bytes(), &mut vec![]).is_err());
+        assert!(Request::read(
+            "GET / HTTP/1.1\r\nContent-Length: x\r\n\r\n".as_bytes(),
+            &mut vec![]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_rejects_chunked_and_large_requests() {
+        let chunked =
+            "POST /coder HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nA -> \r\n0\r\n\r\n";
+        let long = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD));
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: a\r\n".repeat(MAX_HEADERS + 1)
+        );
+
+        assert_eq!(
+            Request::read(chunked.as_bytes(), &mut vec![]),
+            Err(FromPlantumlError(
+                "`Transfer-Encoding` is not supported, send the body with `Content-Length`"
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            Request::read(long.as_bytes(), &mut vec![]),
+            Err(FromPlantumlError(
+                "request headers are larger than 16384 bytes".to_string()
+            ))
+        );
+        assert_eq!(
+            Request::read(many.as_bytes(), &mut vec![]),
+            Err(FromPlantumlError(
+                "request has more than 100 headers".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_answers_expect_continue() {
+        let raw = "POST /coder HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 1\r\n\r\nA";
+        let mut interim = vec![];
+
+        assert_eq!(
+            Request::read(raw.as_bytes(), &mut interim).map(|r| r.body),
+            Ok(b"A".to_vec())
+        );
+        assert_eq!(interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+
+    #[test]
+    fn it_writes_response() {
+        let mut written = vec![];
+
+        Response::redirect("/uml/SoWk\r\n")
+            .write(&mut written)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "HTTP/1.1 302 Found\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 11\r\nConnection: close\r\nLocation: /uml/SoWk  \r\n\r\n/uml/SoWk\r\n"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Local HTTP server emulating encoding routes of the plantuml server (requires `server` feature).
+//!
+//! It answers `/coder`, `/form`, `/uml/{payload}` and placeholder `/txt`, `/svg` routes
+//! without the real Java server, e.g. for integration tests and offline development.
+
+mod http;
+mod routes;
+
+use std::io::{self, Read};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use plantuml_encoding::FromPlantumlError;
+
+use crate::http::{Request, Response};
+
+const USAGE: &str = "Usage: plantuml-encoding-server [--bind ADDRESS] [--port PORT] [--prefix PATH]
+
+Options:
+    --bind ADDRESS    address to listen on (default 127.0.0.1)
+    --port PORT       port to listen on (default 8080, 0 for any free port)
+    --prefix PATH     path of the routes, e.g. /plantuml (default is the root)";
+
+/// Limit of reading the whole request of a connection
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Limit of connections answered at once, others are answered with 503
+const MAX_CONNECTIONS: usize = 64;
+
+/// Options of the server
+#[derive(Debug, PartialEq)]
+struct Options {
+    bind: String,
+    port: u16,
+    prefix: String,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if let Err(FromPlantumlError(err)) = parse_options(&args).and_then(|options| serve(&options)) {
+        eprintln!("error: {}", err);
+        process::exit(2);
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, FromPlantumlError> {
+    let mut options = Options {
+        bind: "127.0.0.1".to_string(),
+        port: 8080,
+        prefix: String::new(),
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Err(FromPlantumlError(USAGE.to_string()));
+        }
+
+        let value = match arg.as_str() {
+            "--bind" | "--port" | "--prefix" => args.next().ok_or_else(|| {
+                FromPlantumlError(format!("`{}` expects a value\n\n{}", arg, USAGE))
+            })?,
+            _ => {
+                return Err(FromPlantumlError(format!(
+                    "unknown option `{}`\n\n{}",
+                    arg, USAGE
+                )))
+            }
+        };
+
+        match arg.as_str() {
+            "--bind" => options.bind = value.to_string(),
+            "--port" => {
+                options.port = value
+                    .parse()
+                    .map_err(|_| FromPlantumlError(format!("`{}` is not a port", value)))?
+            }
+            _ => options.prefix = format!("/{}", value.trim_matches('/')).replace("//", "/"),
+        }
+    }
+
+    if options.prefix == "/" {
+        options.prefix.clear();
+    }
+
+    Ok(options)
+}
+
+fn serve(options: &Options) -> Result<(), FromPlantumlError> {
+    let listener = TcpListener::bind((options.bind.as_str(), options.port)).map_err(|err| {
+        FromPlantumlError(format!(
+            "there is a problem during binding `{}:{}`: `{}`",
+            options.bind, options.port, err
+        ))
+    })?;
+
+    if let Ok(address) = listener.local_addr() {
+        println!("listening on http://{}{}", address, options.prefix);
+    }
+
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for mut stream in listener.incoming().flatten() {
+        let slot = match Slot::take(&connections) {
+            Some(slot) => slot,
+            None => {
+                let _ = Response::text(503, "too many connections").write(&mut stream);
+                continue;
+            }
+        };
+        let prefix = options.prefix.clone();
+
+        thread::spawn(move || {
+            answer(stream, &prefix, REQUEST_TIMEOUT);
+            drop(slot);
+        });
+    }
+
+    Ok(())
+}
+
+/// Taken place among [`MAX_CONNECTIONS`], given back on drop
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn take(connections: &Arc<AtomicUsize>) -> Option<Slot> {
+        connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < MAX_CONNECTIONS).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| Slot(Arc::clone(connections)))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Reader of the stream failing once the deadline of the whole request passes,
+/// so that a client trickling bytes can not keep its thread
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    until: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.until.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request is not received in time",
+            ));
+        }
+
+        self.stream.set_read_timeout(Some(remaining))?;
+
+        let mut stream = self.stream;
+
+        stream.read(buf)
+    }
+}
+
+fn answer(mut stream: TcpStream, prefix: &str, timeout: Duration) {
+    let deadline = Deadline {
+        stream: &stream,
+        until: Instant::now() + timeout,
+    };
+
+    let response = match Request::read(deadline, &mut &stream) {
+        Ok(request) => routes::handle(&request, prefix),
+        Err(FromPlantumlError(err)) => Response::text(400, &err),
+    };
+
+    if let Err(err) = response.write(&mut stream) {
+        eprintln!(
+            "error: there is a problem during writing response: `{}`",
+            err
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{answer, parse_options, Options, Slot, MAX_CONNECTIONS, REQUEST_TIMEOUT};
+
+    #[test]
+    fn it_parses_options() {
+        let args: Vec<String> = ["--port", "0", "--prefix", "plantuml/"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        assert_eq!(
+            parse_options(&args),
+            Ok(Options {
+                bind: "127.0.0.1".to_string(),
+                port: 0,
+                prefix: "/plantuml".to_string(),
+            })
+        );
+        assert!(parse_options(&["--port".to_string()]).is_err());
+        assert!(parse_options(&["--port".to_string(), "x".to_string()]).is_err());
+        assert_eq!(
+            parse_options(&["--prefix".to_string(), "/".to_string()]).map(|o| o.prefix),
+            Ok(String::new())
+        );
+    }
+
+    #[test]
+    fn it_answers_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+
+            answer(stream, "/plantuml", REQUEST_TIMEOUT);
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        let source = "@startuml\nPUML -> RUST\n@enduml";
+
+        write!(
+            client,
+            "POST /plantuml/coder HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            source.len(),
+            source
+        )
+        .unwrap();
+
+        let mut response = String::new();
+
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nSoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000"));
+    }
+
+    #[test]
+    fn it_limits_the_whole_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let started = Instant::now();
+
+            answer(stream, "", Duration::from_millis(300));
+
+            started.elapsed()
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+
+        client.write_all(b"GET /coder HTTP/1.1\r\n").unwrap();
+
+        for _ in 0..20 {
+            thread::sleep(Duration::from_millis(50));
+
+            if client.write_all(b"X").is_err() {
+                break;
+            }
+        }
+
+        let mut response = String::new();
+        let _ = client.read_to_string(&mut response);
+
+        assert!(server.join().unwrap() < Duration::from_millis(900));
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[test]
+    fn it_limits_connections() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let slots: Vec<Slot> = (0..MAX_CONNECTIONS)
+            .map(|_| Slot::take(&connections).unwrap())
+            .collect();
+
+        assert!(Slot::take(&connections).is_none());
+
+        drop(slots);
+
+        assert!(Slot::take(&connections).is_some());
+    }
+}
@@ -0,0 +1,296 @@
+use plantuml_encoding::{decode_plantuml, encode_plantuml_deflate, FromPlantumlError, PlantumlUrl};
+
+use crate::http::{Request, Response};
+
+const INDEX: &str = "plantuml-encoding-server emulates encoding routes of the plantuml server
+
+POST /coder          plantuml source -> deflate payload
+POST /form           form with `text` (source) or `url` field -> redirect to /uml/{payload}
+GET  /uml/{payload}  decoded source
+GET  /txt/{payload}  placeholder with the decoded source (POST with source too)
+GET  /svg/{payload}  placeholder svg with the decoded source (POST with source too)
+
+Payloads are deflate (with optional ~1 prefix) or hex (with ~h prefix).";
+
+/// Handle the request to the routes under `prefix` (e.g. `/plantuml`, empty for the root)
+pub fn handle(request: &Request, prefix: &str) -> Response {
+    let path = request.path();
+    let path = match path.strip_prefix(prefix) {
+        Some(path) if path.is_empty() || path.starts_with('/') => path,
+        _ => return Response::text(404, "not found"),
+    };
+
+    let (route, payload) = match path.trim_start_matches('/').split_once('/') {
+        Some((route, payload)) => (route, Some(payload)),
+        None => (path.trim_start_matches('/'), None),
+    };
+
+    match (request.method.as_str(), route, payload) {
+        ("GET", "", None) => Response::text(200, INDEX),
+        ("POST", "coder", None) => match source(request) {
+            Ok(source) => {
+                result(encode_plantuml_deflate(source).map(|encoded| Response::text(200, &encoded)))
+            }
+            Err(response) => response,
+        },
+        ("POST", "form", None) | ("GET", "form", None) => form(request, prefix),
+        ("GET", "uml" | "txt" | "svg", Some(payload)) => {
+            result(decode(payload).map(|source| render(route, &source)))
+        }
+        ("POST", "txt" | "svg", None) => match source(request) {
+            Ok(source) => render(route, &source),
+            Err(response) => response,
+        },
+        (_, "" | "coder" | "form" | "uml" | "txt" | "svg", _) => {
+            Response::text(405, "method not allowed")
+        }
+        _ => Response::text(404, "not found"),
+    }
+}
+
+fn source(request: &Request) -> Result<String, Response> {
+    String::from_utf8(request.body.clone())
+        .map_err(|_| Response::text(400, "plantuml source is not UTF-8"))
+}
+
+fn decode(payload: &str) -> Result<String, FromPlantumlError> {
+    if payload.is_empty() {
+        return Err(FromPlantumlError("payload is missing".to_string()));
+    }
+
+    decode_plantuml(payload)
+}
+
+/// Redirect the form with `text` (plantuml source) or `url` (plantuml server URL) field
+fn form(request: &Request, prefix: &str) -> Response {
+    let fields = match request.method.as_str() {
+        "POST" => match source(request) {
+            Ok(body) => parse_form(&body),
+            Err(response) => return response,
+        },
+        _ => parse_form(request.query()),
+    };
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let encoded = match (field("text"), field("url")) {
+        (Some(text), _) => encode_plantuml_deflate(text),
+        (None, Some(url)) => PlantumlUrl::parse(url)
+            .map(|plantuml_url| plantuml_url.encoded)
+            .ok_or_else(|| FromPlantumlError(format!("`{}` is not a plantuml server url", url))),
+        (None, None) => Err(FromPlantumlError(
+            "form expects `text` or `url` field".to_string(),
+        )),
+    };
+
+    result(encoded.map(|encoded| Response::redirect(&format!("{}/uml/{}", prefix, encoded))))
+}
+
+fn render(route: &str, source: &str) -> Response {
+    match route {
+        "svg" => Response::new(200, "image/svg+xml", svg(source).into_bytes()),
+        _ => Response::text(200, source),
+    }
+}
+
+/// Errors are answered as the plantuml server does: 400 with `X-PlantUML-Diagram-Error` header
+fn result(response: Result<Response, FromPlantumlError>) -> Response {
+    response.unwrap_or_else(|FromPlantumlError(err)| {
+        Response::text(400, &err).header("X-PlantUML-Diagram-Error", &err)
+    })
+}
+
+/// Placeholder svg with lines of the source
+fn svg(source: &str) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) * 9 + 20;
+    let height = lines.len() * 18 + 20;
+
+    let texts: String = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            format!(
+                "<text x=\"10\" y=\"{}\" font-family=\"monospace\" font-size=\"14\">{}</text>",
+                i * 18 + 24,
+                escape_xml(line)
+            )
+        })
+        .collect();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">{}</svg>",
+        texts,
+        w = width,
+        h = height
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Fields of `application/x-www-form-urlencoded` text
+fn parse_form(text: &str) -> Vec<(String, String)> {
+    text.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) =>
+            {
+                decoded.push(u8::from_str_radix(&text[i + 1..i + 3], 16).unwrap_or_default());
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle, parse_form};
+
+    use crate::http::Request;
+
+    const SMALL: &str = "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000";
+    const SOURCE: &str = "@startuml\nPUML -> RUST\n@enduml";
+
+    fn request(method: &str, target: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            target: target.to_string(),
+            headers: vec![],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn body(method: &str, target: &str, body: &str) -> (u16, String) {
+        let response = handle(&request(method, target, body), "/plantuml");
+
+        (response.status, String::from_utf8(response.body).unwrap())
+    }
+
+    #[test]
+    fn it_encodes_and_decodes() {
+        assert_eq!(
+            body("POST", "/plantuml/coder", SOURCE),
+            (200, SMALL.to_string())
+        );
+        assert_eq!(
+            body("GET", &format!("/plantuml/uml/{}", SMALL), ""),
+            (200, SOURCE.to_string())
+        );
+        assert_eq!(
+            body(
+                "GET",
+                "/plantuml/txt/~h407374617274756d6c0a50554d4c202d3e20525553540a40656e64756d6c",
+                ""
+            ),
+            (200, SOURCE.to_string())
+        );
+    }
+
+    #[test]
+    fn it_renders_svg_placeholder() {
+        let (status, svg) = body("POST", "/plantuml/svg", "A -> B: <hi>");
+
+        assert_eq!(status, 200);
+        assert!(svg.contains(
+            "<text x=\"10\" y=\"24\" font-family=\"monospace\" font-size=\"14\">A -&gt; B: &lt;hi&gt;</text>"
+        ));
+    }
+
+    #[test]
+    fn it_redirects_forms() {
+        let response = handle(
+            &request(
+                "POST",
+                "/plantuml/form",
+                "text=%40startuml%0APUML+-%3E+RUST%0A%40enduml",
+            ),
+            "/plantuml",
+        );
+
+        assert_eq!(response.status, 302);
+        assert_eq!(
+            response.headers,
+            vec![("Location".to_string(), format!("/plantuml/uml/{}", SMALL))]
+        );
+
+        let response = handle(
+            &request(
+                "GET",
+                &format!(
+                    "/form?url=https%3A%2F%2Fwww.plantuml.com%2Fplantuml%2Fpng%2F{}",
+                    SMALL
+                ),
+                "",
+            ),
+            "",
+        );
+
+        assert_eq!(
+            response.headers,
+            vec![("Location".to_string(), format!("/uml/{}", SMALL))]
+        );
+    }
+
+    #[test]
+    fn it_answers_errors() {
+        let response = handle(&request("GET", "/plantuml/svg/~h1", ""), "/plantuml");
+
+        assert_eq!(response.status, 400);
+        assert_eq!(
+            response.headers,
+            vec![(
+                "X-PlantUML-Diagram-Error".to_string(),
+                "there is a problem during hex decoding: `Odd number of digits`".to_string()
+            )]
+        );
+        assert_eq!(body("GET", "/plantuml/uml/", "").0, 400);
+        assert_eq!(body("DELETE", "/plantuml/coder", "").0, 405);
+        assert_eq!(body("GET", "/plantuml/png/x", "").0, 404);
+        assert_eq!(body("GET", "/other/uml/x", "").0, 404);
+        assert_eq!(body("POST", "/plantuml/form", "name=x").0, 400);
+    }
+
+    #[test]
+    fn it_parses_forms() {
+        assert_eq!(
+            parse_form("a=1+2&b=%3D%zz&c&d=%+1%-f%"),
+            vec![
+                ("a".to_string(), "1 2".to_string()),
+                ("b".to_string(), "=%zz".to_string()),
+                ("c".to_string(), String::new()),
+                ("d".to_string(), "% 1%-f%".to_string())
+            ]
+        );
+    }
+}
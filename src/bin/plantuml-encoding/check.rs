@@ -0,0 +1,335 @@
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use plantuml_encoding::{
+    check_plantuml, check_plantuml_files, CheckRule, CheckedFile, FromPlantumlError,
+    DEFAULT_MAX_URL_LENGTH,
+};
+use serde_json::{json, Value};
+
+use crate::{parse_flags, write_out};
+
+/// `check [--format=text|json|junit|sarif] [--max-length=N] [PATH...]`
+pub fn run(args: &[String], out: &mut dyn Write) -> Result<i32, FromPlantumlError> {
+    let (flags, paths) = parse_flags(args, &["--format=", "--max-length="])?;
+    let mut format = "text";
+    let mut max_length = DEFAULT_MAX_URL_LENGTH;
+
+    for flag in flags {
+        if let Some(value) = flag.strip_prefix("--format=") {
+            format = value;
+        } else if let Some(value) = flag.strip_prefix("--max-length=") {
+            max_length = value
+                .parse()
+                .map_err(|_| FromPlantumlError(format!("`{}` is not a length", value)))?;
+        }
+    }
+
+    let checked = if paths.is_empty() || paths == ["-"] {
+        let mut text = String::new();
+
+        io::stdin().read_to_string(&mut text).map_err(|err| {
+            FromPlantumlError(format!(
+                "there is a problem during reading stdin: `{}`",
+                err
+            ))
+        })?;
+
+        vec![CheckedFile {
+            path: PathBuf::from("-"),
+            links: check_plantuml(text, max_length),
+        }]
+    } else {
+        check_plantuml_files(&paths, max_length)?
+    };
+
+    match format {
+        "text" => {
+            for line in to_text(&checked) {
+                write_out(out, &line)?;
+            }
+        }
+        "json" => write_out(out, &to_json(&checked).to_string())?,
+        "junit" => write_out(out, &to_junit(&checked))?,
+        "sarif" => write_out(out, &to_sarif(&checked).to_string())?,
+        _ => {
+            return Err(FromPlantumlError(format!(
+                "unknown format `{}` (text, json, junit or sarif)",
+                format
+            )))
+        }
+    }
+
+    let failed = checked
+        .iter()
+        .flat_map(|file| file.links.iter())
+        .any(|link| !link.is_ok());
+
+    Ok(if failed { 1 } else { 0 })
+}
+
+/// `path:line:column: kind ok|rule: message` lines with a summary
+pub fn to_text(checked: &[CheckedFile]) -> Vec<String> {
+    let mut lines = vec![];
+    let mut total = 0;
+    let mut failed = 0;
+
+    for file in checked {
+        for link in &file.links {
+            let location = format!(
+                "{}:{}:{}: {}",
+                file.path.display(),
+                link.found.line,
+                link.found.column,
+                link.found.kind.as_str()
+            );
+
+            total += 1;
+
+            if link.is_ok() {
+                lines.push(format!("{} ok", location));
+                continue;
+            }
+
+            failed += 1;
+
+            for problem in &link.problems {
+                lines.push(format!(
+                    "{} {}: {}",
+                    location,
+                    problem.rule.as_str(),
+                    problem.message
+                ));
+            }
+        }
+    }
+
+    lines.push(format!(
+        "{} link(s) checked, {} with problems",
+        total, failed
+    ));
+
+    lines
+}
+
+/// Array of checked links with paths, positions, lengths and problems
+pub fn to_json(checked: &[CheckedFile]) -> Value {
+    Value::Array(
+        checked
+            .iter()
+            .flat_map(|file| {
+                file.links.iter().map(move |link| {
+                    json!({
+                        "path": file.path.display().to_string(),
+                        "line": link.found.line,
+                        "column": link.found.column,
+                        "kind": link.found.kind.as_str(),
+                        "url": link.found.url.as_ref().map(|url| url.url()),
+                        "encoded": link.found.encoded,
+                        "length": link.length(),
+                        "ok": link.is_ok(),
+                        "problems": link.problems.iter().map(|problem| json!({
+                            "rule": problem.rule.as_str(),
+                            "message": problem.message,
+                        })).collect::<Vec<Value>>(),
+                    })
+                })
+            })
+            .collect(),
+    )
+}
+
+/// JUnit XML report with a test case per link
+pub fn to_junit(checked: &[CheckedFile]) -> String {
+    let mut cases = String::new();
+    let mut total = 0;
+    let mut failures = 0;
+
+    for file in checked {
+        for link in &file.links {
+            let path = escape_xml(&file.path.display().to_string());
+
+            total += 1;
+            cases.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}:{}:{} {}\"",
+                path,
+                path,
+                link.found.line,
+                link.found.column,
+                link.found.kind.as_str()
+            ));
+
+            if link.is_ok() {
+                cases.push_str("/>\n");
+                continue;
+            }
+
+            failures += 1;
+            cases.push_str(">\n");
+
+            for problem in &link.problems {
+                cases.push_str(&format!(
+                    "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+                    problem.rule.as_str(),
+                    escape_xml(&problem.message),
+                    escape_xml(&link.found.encoded)
+                ));
+            }
+
+            cases.push_str("    </testcase>\n");
+        }
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuites>\n  \
+         <testsuite name=\"plantuml-encoding check\" tests=\"{}\" failures=\"{}\">\n\
+         {}  </testsuite>\n\
+         </testsuites>",
+        total, failures, cases
+    )
+}
+
+/// SARIF 2.1.0 log with a result per problem
+pub fn to_sarif(checked: &[CheckedFile]) -> Value {
+    let rules: Vec<Value> = CheckRule::ALL
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.as_str(),
+                "shortDescription": { "text": rule.description() },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = checked
+        .iter()
+        .flat_map(|file| {
+            file.links.iter().flat_map(move |link| {
+                link.problems.iter().map(move |problem| {
+                    json!({
+                        "ruleId": problem.rule.as_str(),
+                        "level": if problem.rule == CheckRule::TooLong { "warning" } else { "error" },
+                        "message": { "text": problem.message },
+                        "locations": [{
+                            "physicalLocation": {
+                                "artifactLocation": {
+                                    "uri": file.path.display().to_string().replace('\\', "/"),
+                                },
+                                "region": {
+                                    "startLine": link.found.line,
+                                    "startColumn": link.found.column,
+                                },
+                            },
+                        }],
+                    })
+                })
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "plantuml-encoding",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use plantuml_encoding::{check_plantuml, CheckedFile};
+    use serde_json::json;
+
+    use super::{to_json, to_junit, to_sarif, to_text};
+
+    fn checked() -> Vec<CheckedFile> {
+        vec![CheckedFile {
+            path: PathBuf::from("docs/a.md"),
+            links: check_plantuml(
+                "![a](http://localhost/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)\n~h407",
+                70,
+            ),
+        }]
+    }
+
+    #[test]
+    fn it_check_to_text() {
+        assert_eq!(
+            to_text(&checked()),
+            vec![
+                "docs/a.md:1:6: url ok",
+                "docs/a.md:2:1: hex alphabet: Odd number of digits",
+                "2 link(s) checked, 1 with problems"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_check_to_json() {
+        assert_eq!(
+            to_json(&checked())[1],
+            json!({
+                "path": "docs/a.md",
+                "line": 2,
+                "column": 1,
+                "kind": "hex",
+                "url": null,
+                "encoded": "~h407",
+                "length": 5,
+                "ok": false,
+                "problems": [{ "rule": "alphabet", "message": "Odd number of digits" }]
+            })
+        );
+    }
+
+    #[test]
+    fn it_check_to_junit() {
+        let junit = to_junit(&checked());
+
+        assert!(junit
+            .contains("<testsuite name=\"plantuml-encoding check\" tests=\"2\" failures=\"1\">"));
+        assert!(junit.contains("<testcase classname=\"docs/a.md\" name=\"docs/a.md:1:6 url\"/>"));
+        assert!(junit.contains(
+            "<failure type=\"alphabet\" message=\"Odd number of digits\">~h407</failure>"
+        ));
+    }
+
+    #[test]
+    fn it_check_to_sarif() {
+        let sarif = to_sarif(&checked());
+        let results = &sarif["runs"][0]["results"];
+
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(
+            sarif["runs"][0]["tool"]["driver"]["rules"]
+                .as_array()
+                .unwrap()
+                .len(),
+            5
+        );
+        assert_eq!(results.as_array().unwrap().len(), 1);
+        assert_eq!(results[0]["ruleId"], "alphabet");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["region"],
+            json!({ "startLine": 2, "startColumn": 1 })
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{self, Read, Write};
+
+use plantuml_encoding::{format_plantuml, format_plantuml_files, FormattedFile, FromPlantumlError};
+
+use crate::{parse_flags, write_out};
+
+/// `fmt [--check | --write] [PATH...]`
+pub fn run(args: &[String], out: &mut dyn Write) -> Result<i32, FromPlantumlError> {
+    let (flags, paths) = parse_flags(args, &["--check", "--write"])?;
+
+    if flags.contains(&"--check") && flags.contains(&"--write") {
+        return Err(FromPlantumlError(
+            "`--check` and `--write` can not be used together".to_string(),
+        ));
+    }
+
+    let write = flags.contains(&"--write");
+
+    if paths.is_empty() || paths == ["-"] {
+        if write {
+            return Err(FromPlantumlError(
+                "`--write` expects files or directories".to_string(),
+            ));
+        }
+
+        let mut text = String::new();
+
+        io::stdin().read_to_string(&mut text).map_err(|err| {
+            FromPlantumlError(format!(
+                "there is a problem during reading stdin: `{}`",
+                err
+            ))
+        })?;
+
+        write_out(out, &format_plantuml(text))?;
+
+        return Ok(0);
+    }
+
+    let formatted = format_plantuml_files(&paths, write)?;
+
+    for line in report(&formatted, write) {
+        write_out(out, &line)?;
+    }
+
+    let unformatted = formatted.iter().any(|file| !file.is_formatted());
+
+    Ok(if unformatted && !write { 1 } else { 0 })
+}
+
+/// Diffs of files that are not formatted (check) or list of rewritten files (write)
+pub fn report(formatted: &[FormattedFile], write: bool) -> Vec<String> {
+    formatted
+        .iter()
+        .filter(|file| !file.is_formatted())
+        .map(|file| {
+            if write {
+                format!("{}: formatted", file.path.display())
+            } else {
+                file.diff().trim_end().to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use plantuml_encoding::FormattedFile;
+
+    use super::report;
+
+    #[test]
+    fn it_fmt_report() {
+        let formatted = vec![
+            FormattedFile {
+                path: PathBuf::from("docs/a.puml"),
+                original: "@startuml\nA->B\n@enduml\n".to_string(),
+                formatted: "@startuml\nA -> B\n@enduml\n".to_string(),
+            },
+            FormattedFile {
+                path: PathBuf::from("docs/b.puml"),
+                original: "A -> B\n".to_string(),
+                formatted: "A -> B\n".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            report(&formatted, false),
+            vec!["--- a/docs/a.puml\n+++ b/docs/a.puml\n@@ -1,3 +1,3 @@\n @startuml\n-A->B\n+A -> B\n @enduml"]
+        );
+        assert_eq!(report(&formatted, true), vec!["docs/a.puml: formatted"]);
+    }
+}
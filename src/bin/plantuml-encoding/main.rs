@@ -0,0 +1,130 @@
+//! Command line tool of the `plantuml_encoding` crate (requires `cli` feature).
+
+mod check;
+mod fmt;
+mod scan;
+mod sync;
+mod textconv;
+
+use std::io::{self, Write};
+use std::process;
+
+use plantuml_encoding::FromPlantumlError;
+
+const USAGE: &str = "Usage: plantuml-encoding <command> [options]
+
+Commands:
+    scan [--json] [--broken] [PATH...]
+        Find plantuml server links and bare ~h / deflate payloads in files
+        and directories (stdin without paths) and decode them.
+        Exits with 1 if some of them are broken.
+
+    check [--format=text|json|junit|sarif] [--max-length=N] [PATH...]
+        Validate plantuml links and payloads in files and directories
+        (stdin without paths): strict decoding (alphabet, inflate, UTF-8),
+        balanced @start/@end tags and length (default limit 4000).
+        Exits with 1 if some of them have problems.
+
+    sync [--check | --fix] PATH...
+        Re-encode files referenced by `<!-- plantuml: path/to/file.puml -->`
+        markers and compare (--check, default, exits with 1 and prints
+        a diff for stale links) or rewrite (--fix) the links next to them.
+
+    fmt [--check | --write] [PATH...]
+        Format plantuml files and directories (.puml, .plantuml, .pu, .iuml,
+        .wsd): indentation of blocks, spacing around arrows, keyword casing
+        and blank lines, so equivalent diagrams are encoded to the same links.
+        Prints the formatted stdin without paths, compares files (--check,
+        default, exits with 1 and prints a diff) or rewrites them (--write).
+
+    textconv FILE
+        Print the file with every plantuml link and payload replaced by
+        its decoded source (git textconv):
+            .gitattributes: *.md diff=plantuml
+            git config diff.plantuml.textconv \"plantuml-encoding textconv\"
+
+    diff PATH OLD-FILE OLD-HEX OLD-MODE NEW-FILE NEW-HEX NEW-MODE
+        Unified diff of the files converted as with `textconv`
+        (git external diff driver):
+            git config diff.plantuml.command \"plantuml-encoding diff\"";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let stdout = io::stdout();
+
+    let code = match run(&args, &mut stdout.lock()) {
+        Ok(code) => code,
+        Err(FromPlantumlError(err)) => {
+            eprintln!("error: {}", err);
+            2
+        }
+    };
+
+    process::exit(code);
+}
+
+fn run(args: &[String], out: &mut dyn Write) -> Result<i32, FromPlantumlError> {
+    match args.first().map(String::as_str) {
+        Some("scan") => scan::run(&args[1..], out),
+        Some("check") => check::run(&args[1..], out),
+        Some("sync") => sync::run(&args[1..], out),
+        Some("fmt") => fmt::run(&args[1..], out),
+        Some("textconv") => textconv::run_textconv(&args[1..], out),
+        Some("diff") => textconv::run_diff(&args[1..], out),
+        Some("-h") | Some("--help") | Some("help") => {
+            write_out(out, USAGE)?;
+            Ok(0)
+        }
+        Some("-V") | Some("--version") => {
+            write_out(
+                out,
+                &format!("plantuml-encoding {}", env!("CARGO_PKG_VERSION")),
+            )?;
+            Ok(0)
+        }
+        Some(command) => Err(FromPlantumlError(format!(
+            "unknown command `{}`\n\n{}",
+            command, USAGE
+        ))),
+        None => Err(FromPlantumlError(USAGE.to_string())),
+    }
+}
+
+/// Write the text with a line break
+fn write_out(out: &mut dyn Write, text: &str) -> Result<(), FromPlantumlError> {
+    writeln!(out, "{}", text).map_err(|err| {
+        FromPlantumlError(format!(
+            "there is a problem during writing output: `{}`",
+            err
+        ))
+    })
+}
+
+/// Split arguments into flags (from the allowed list, `--name=` allows `--name=value`)
+/// and positional arguments
+fn parse_flags<'a>(
+    args: &'a [String],
+    allowed: &[&str],
+) -> Result<(Vec<&'a str>, Vec<&'a str>), FromPlantumlError> {
+    let mut flags = vec![];
+    let mut positional = vec![];
+
+    for arg in args.iter().map(String::as_str) {
+        if arg.starts_with("--") {
+            let name = arg.find('=').map_or(arg, |i| &arg[..=i]);
+
+            if !allowed.contains(&name) {
+                return Err(FromPlantumlError(format!(
+                    "unknown option `{}`\n\n{}",
+                    arg, USAGE
+                )));
+            }
+
+            flags.push(arg);
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    Ok((flags, positional))
+}
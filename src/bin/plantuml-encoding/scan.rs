@@ -0,0 +1,164 @@
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use plantuml_encoding::{scan_plantuml, scan_plantuml_files, FromPlantumlError, ScannedFile};
+use serde_json::{json, Value};
+
+use crate::{parse_flags, write_out};
+
+/// `scan [--json] [--broken] [PATH...]`
+pub fn run(args: &[String], out: &mut dyn Write) -> Result<i32, FromPlantumlError> {
+    let (flags, paths) = parse_flags(args, &["--json", "--broken"])?;
+    let only_broken = flags.contains(&"--broken");
+
+    let mut scanned = if paths.is_empty() || paths == ["-"] {
+        let mut text = String::new();
+
+        io::stdin().read_to_string(&mut text).map_err(|err| {
+            FromPlantumlError(format!(
+                "there is a problem during reading stdin: `{}`",
+                err
+            ))
+        })?;
+
+        vec![ScannedFile {
+            path: PathBuf::from("-"),
+            found: scan_plantuml(text),
+        }]
+    } else {
+        scan_plantuml_files(&paths)?
+    };
+
+    let broken = scanned
+        .iter()
+        .flat_map(|file| file.found.iter())
+        .any(|found| found.is_broken());
+
+    if only_broken {
+        for file in scanned.iter_mut() {
+            file.found.retain(|found| found.is_broken());
+        }
+    }
+
+    if flags.contains(&"--json") {
+        write_out(out, &to_json(&scanned).to_string())?;
+    } else {
+        for line in to_text(&scanned) {
+            write_out(out, &line)?;
+        }
+    }
+
+    Ok(if broken { 1 } else { 0 })
+}
+
+/// Array of found plantuml with paths, positions and decoded source or error
+pub fn to_json(scanned: &[ScannedFile]) -> Value {
+    Value::Array(
+        scanned
+            .iter()
+            .flat_map(|file| {
+                file.found.iter().map(move |found| {
+                    json!({
+                        "path": file.path.display().to_string(),
+                        "line": found.line,
+                        "column": found.column,
+                        "start": found.span.start,
+                        "end": found.span.end,
+                        "kind": found.kind.as_str(),
+                        "url": found.url.as_ref().map(|url| url.url()),
+                        "encoded": found.encoded,
+                        "decoded": found.decoded.as_ref().ok(),
+                        "error": found.decoded.as_ref().err().map(|err| &err.0),
+                    })
+                })
+            })
+            .collect(),
+    )
+}
+
+/// `path:line:column: kind ok|broken` lines
+pub fn to_text(scanned: &[ScannedFile]) -> Vec<String> {
+    scanned
+        .iter()
+        .flat_map(|file| {
+            file.found.iter().map(move |found| {
+                let status = match &found.decoded {
+                    Ok(plantuml) => format!("ok ({} lines)", plantuml.lines().count()),
+                    Err(err) => format!("broken: {}", err.0),
+                };
+
+                format!(
+                    "{}:{}:{}: {} {}",
+                    file.path.display(),
+                    found.line,
+                    found.column,
+                    found.kind.as_str(),
+                    status
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use plantuml_encoding::{scan_plantuml, ScannedFile};
+    use serde_json::json;
+
+    use super::{to_json, to_text};
+
+    fn scanned() -> Vec<ScannedFile> {
+        vec![ScannedFile {
+            path: PathBuf::from("docs/a.md"),
+            found: scan_plantuml(
+                "![a](http://localhost/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)\n~h1",
+            ),
+        }]
+    }
+
+    #[test]
+    fn it_scan_to_json() {
+        assert_eq!(
+            to_json(&scanned()),
+            json!([
+                {
+                    "path": "docs/a.md",
+                    "line": 1,
+                    "column": 6,
+                    "start": 5,
+                    "end": 70,
+                    "kind": "url",
+                    "url": "http://localhost/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000",
+                    "encoded": "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000",
+                    "decoded": "@startuml\nPUML -> RUST\n@enduml",
+                    "error": null
+                },
+                {
+                    "path": "docs/a.md",
+                    "line": 2,
+                    "column": 1,
+                    "start": 72,
+                    "end": 75,
+                    "kind": "hex",
+                    "url": null,
+                    "encoded": "~h1",
+                    "decoded": null,
+                    "error": "there is a problem during decoding: `Odd number of digits`"
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn it_scan_to_text() {
+        assert_eq!(
+            to_text(&scanned()),
+            vec![
+                "docs/a.md:1:6: url ok (3 lines)",
+                "docs/a.md:2:1: hex broken: there is a problem during decoding: `Odd number of digits`"
+            ]
+        );
+    }
+}
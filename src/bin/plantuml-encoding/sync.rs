@@ -0,0 +1,84 @@
+use std::io::Write;
+
+use plantuml_encoding::{sync_plantuml_files, FromPlantumlError, SyncedFile};
+
+use crate::{parse_flags, write_out};
+
+/// `sync [--check | --fix] PATH...`
+pub fn run(args: &[String], out: &mut dyn Write) -> Result<i32, FromPlantumlError> {
+    let (flags, paths) = parse_flags(args, &["--check", "--fix"])?;
+
+    if paths.is_empty() {
+        return Err(FromPlantumlError(
+            "`sync` expects files or directories".to_string(),
+        ));
+    }
+
+    if flags.contains(&"--check") && flags.contains(&"--fix") {
+        return Err(FromPlantumlError(
+            "`--check` and `--fix` can not be used together".to_string(),
+        ));
+    }
+
+    let fix = flags.contains(&"--fix");
+    let synced = sync_plantuml_files(&paths, fix)?;
+
+    for line in report(&synced, fix) {
+        write_out(out, &line)?;
+    }
+
+    let stale = synced.iter().any(|file| !file.synced.is_up_to_date());
+
+    Ok(if stale && !fix { 1 } else { 0 })
+}
+
+/// Diffs of stale links (check) or list of rewritten files (fix)
+pub fn report(synced: &[SyncedFile], fix: bool) -> Vec<String> {
+    synced
+        .iter()
+        .filter(|file| !file.synced.is_up_to_date())
+        .map(|file| {
+            if fix {
+                format!(
+                    "{}: {} link(s) updated",
+                    file.path.display(),
+                    file.synced.stale.len()
+                )
+            } else {
+                file.synced.diff(&file.path, &file.original)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use plantuml_encoding::{StaleLink, Synced, SyncedFile};
+
+    use super::report;
+
+    #[test]
+    fn it_sync_report() {
+        let synced = vec![SyncedFile {
+            path: PathBuf::from("README.md"),
+            original: "https://www.plantuml.com/plantuml/svg/old\n".to_string(),
+            synced: Synced {
+                text: "https://www.plantuml.com/plantuml/svg/new\n".to_string(),
+                stale: vec![StaleLink {
+                    line: 1,
+                    source: "arch.puml".to_string(),
+                    old_url: "https://www.plantuml.com/plantuml/svg/old".to_string(),
+                    new_url: "https://www.plantuml.com/plantuml/svg/new".to_string(),
+                }],
+            },
+        }];
+
+        assert_eq!(
+            report(&synced, false),
+            vec!["README.md:1: stale link of `arch.puml`\n-https://www.plantuml.com/plantuml/svg/old\n+https://www.plantuml.com/plantuml/svg/new"]
+        );
+        assert_eq!(report(&synced, true), vec!["README.md: 1 link(s) updated"]);
+    }
+}
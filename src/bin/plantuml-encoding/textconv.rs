@@ -0,0 +1,148 @@
+use std::fs;
+use std::io::Write;
+
+use plantuml_encoding::{textconv_diff_plantuml, textconv_plantuml, FromPlantumlError};
+
+use crate::parse_flags;
+
+/// `textconv FILE`
+pub fn run_textconv(args: &[String], out: &mut dyn Write) -> Result<i32, FromPlantumlError> {
+    let (_, paths) = parse_flags(args, &[])?;
+
+    let path = match paths.as_slice() {
+        [path] => path,
+        _ => {
+            return Err(FromPlantumlError(
+                "`textconv` expects exactly one file".to_string(),
+            ))
+        }
+    };
+
+    write_raw(out, &textconv_plantuml(read(path)?))?;
+
+    Ok(0)
+}
+
+/// `diff PATH OLD-FILE OLD-HEX OLD-MODE NEW-FILE NEW-HEX NEW-MODE`
+/// (arguments of git external diff drivers)
+pub fn run_diff(args: &[String], out: &mut dyn Write) -> Result<i32, FromPlantumlError> {
+    let (path, old_file, new_file) = match args {
+        [path, old_file, _, _, new_file, _, _] => (path, old_file, new_file),
+        _ => {
+            return Err(FromPlantumlError(
+                "`diff` expects 7 arguments of git external diff: PATH OLD-FILE OLD-HEX OLD-MODE NEW-FILE NEW-HEX NEW-MODE".to_string(),
+            ))
+        }
+    };
+
+    let old_label = if old_file == "/dev/null" {
+        old_file.to_string()
+    } else {
+        format!("a/{}", path)
+    };
+    let new_label = if new_file == "/dev/null" {
+        new_file.to_string()
+    } else {
+        format!("b/{}", path)
+    };
+
+    let diff = textconv_diff_plantuml(read(old_file)?, read(new_file)?, old_label, new_label);
+
+    if !diff.is_empty() {
+        write_raw(
+            out,
+            &format!("diff --plantuml a/{} b/{}\n{}", path, path, diff),
+        )?;
+    }
+
+    // git treats non-zero exit codes of external diff drivers as failures
+    Ok(0)
+}
+
+fn read(path: &str) -> Result<String, FromPlantumlError> {
+    fs::read(path)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .map_err(|err| {
+            FromPlantumlError(format!(
+                "there is a problem during reading `{}`: `{}`",
+                path, err
+            ))
+        })
+}
+
+/// Write the text as is (without an additional line break)
+fn write_raw(out: &mut dyn Write, text: &str) -> Result<(), FromPlantumlError> {
+    out.write_all(text.as_bytes()).map_err(|err| {
+        FromPlantumlError(format!(
+            "there is a problem during writing output: `{}`",
+            err
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{run_diff, run_textconv};
+
+    const OLD: &str = "# Flow\n\n![flow](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)\n";
+
+    fn write_temp(name: &str, content: &str) -> String {
+        let dir = std::env::temp_dir().join("plantuml_encoding_cli_textconv");
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(name), content).unwrap();
+
+        dir.join(name).display().to_string()
+    }
+
+    #[test]
+    fn it_textconv_command() {
+        let path = write_temp("textconv.md", OLD);
+        let mut out = vec![];
+
+        assert_eq!(run_textconv(&[path], &mut out), Ok(0));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# Flow\n\n![flow](\n--- plantuml svg https://www.plantuml.com/plantuml ---\n@startuml\nPUML -> RUST\n@enduml\n--- end plantuml ---\n)\n"
+        );
+        assert!(run_textconv(&[], &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn it_diff_command() {
+        let old = write_temp("old.md", OLD);
+        let new = write_temp(
+            "new.md",
+            &OLD.replace(
+                "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000",
+                "~h407374617274756d6c0a50554d4c202d3e20525553540a52555354202d3e2050554d4c0a40656e64756d6c",
+            ),
+        );
+
+        let args: Vec<String> = ["README.md", &old, "a1", "100644", &new, "b2", "100644"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let mut out = vec![];
+
+        assert_eq!(run_diff(&args, &mut out), Ok(0));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "diff --plantuml a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -4,6 +4,7 @@\n --- plantuml svg https://www.plantuml.com/plantuml ---\n @startuml\n PUML -> RUST\n+RUST -> PUML\n @enduml\n --- end plantuml ---\n )\n"
+        );
+
+        let mut same = vec![];
+
+        assert_eq!(
+            run_diff(
+                &[&args[..4], &[args[1].clone()], &args[5..]].concat(),
+                &mut same
+            ),
+            Ok(0)
+        );
+        assert!(same.is_empty());
+        assert!(run_diff(&args[..3], &mut vec![]).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+//! Helpers for `build.rs` scripts of crates that reference plantuml diagrams in docs.
+//!
+//! ## Example
+//!
+//! `build.rs`:
+//!
+//! ```rust,no_run
+//! fn main() -> Result<(), plantuml_encoding::FromPlantumlError> {
+//!     plantuml_encoding::build::encode_dir("docs/diagrams")?;
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! `src/lib.rs` (`docs/diagrams/arch/overview.puml` becomes `ARCH_OVERVIEW`):
+//!
+//! ```rust,ignore
+//! mod diagrams {
+//!     include!(concat!(env!("OUT_DIR"), "/plantuml.rs"));
+//! }
+//!
+//! #[doc = concat!("![overview](", diagrams::ARCH_OVERVIEW, ")")]
+//! pub struct Api;
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::deflate;
+use crate::errors;
+use crate::url;
+use crate::utils;
+
+/// Name of the generated file in `OUT_DIR`
+pub const GENERATED_FILE: &str = "plantuml.rs";
+
+/// Default extensions of plantuml files
+pub const EXTENSIONS: [&str; 4] = ["puml", "plantuml", "pu", "iuml"];
+
+/// Encoder of a directory of plantuml files into a module of `pub const` URLs
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirEncoder {
+    dir: PathBuf,
+    server: String,
+    format: url::OutputFormat,
+    extensions: Vec<String>,
+}
+
+impl DirEncoder {
+    /// Encoder of the directory with the public server, svg format
+    /// and [`EXTENSIONS`]
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        DirEncoder {
+            dir: dir.as_ref().to_path_buf(),
+            server: url::PLANTUML_SERVER.to_string(),
+            format: url::OutputFormat::Svg,
+            extensions: EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    /// Plantuml server of the URLs
+    pub fn server<S: AsRef<str>>(mut self, server: S) -> Self {
+        self.server = server.as_ref().to_string();
+        self
+    }
+
+    /// Output format of the URLs
+    pub fn format(mut self, format: url::OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Extensions of the files to encode (without dot)
+    pub fn extensions<I, T>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.extensions = extensions
+            .into_iter()
+            .map(|e| e.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// Source of the generated module
+    pub fn generate(&self) -> Result<String, errors::FromPlantumlError> {
+        let mut names: HashMap<String, String> = HashMap::new();
+        let mut lines = vec![format!(
+            "// Generated by plantuml_encoding::build from `{}`, do not edit.",
+            self.dir.display()
+        )];
+
+        for (path, relative) in self.files()? {
+            let name = const_name(&relative);
+
+            if let Some(other) = names.insert(name.clone(), relative.clone()) {
+                return Err(errors::FromPlantumlError(format!(
+                    "`{}` and `{}` have the same constant name `{}`",
+                    other, relative, name
+                )));
+            }
+
+            let encoded = deflate::encode_plantuml_deflate(utils::read_file(&path)?)?;
+
+            lines.push(String::new());
+            lines.push(format!("/// `{}`", relative));
+            lines.push(format!(
+                "pub const {}: &str = {:?};",
+                name,
+                url::plantuml_url(&self.server, self.format, encoded)
+            ));
+        }
+
+        Ok(lines.join("\n") + "\n")
+    }
+
+    /// Paths to print as `cargo:rerun-if-changed` (the directory and every encoded file)
+    pub fn rerun_if_changed(&self) -> Result<Vec<PathBuf>, errors::FromPlantumlError> {
+        Ok(std::iter::once(self.dir.clone())
+            .chain(self.files()?.into_iter().map(|(path, _)| path))
+            .collect())
+    }
+
+    /// Write the generated module to the file (only if it changed)
+    /// and print `cargo:rerun-if-changed` lines
+    pub fn write_to<P: AsRef<Path>>(&self, file: P) -> Result<(), errors::FromPlantumlError> {
+        let file = file.as_ref();
+        let generated = self.generate()?;
+
+        if fs::read_to_string(file).ok().as_deref() != Some(generated.as_str()) {
+            fs::write(file, generated).map_err(|err| {
+                errors::FromPlantumlError(format!(
+                    "there is a problem during writing `{}`: `{}`",
+                    file.display(),
+                    err
+                ))
+            })?;
+        }
+
+        for path in self.rerun_if_changed()? {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Write the generated module to [`GENERATED_FILE`] in `OUT_DIR`
+    /// and print `cargo:rerun-if-changed` lines
+    pub fn write(&self) -> Result<PathBuf, errors::FromPlantumlError> {
+        let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+            errors::FromPlantumlError(
+                "`OUT_DIR` is not set: the helper is expected to run from `build.rs`".to_string(),
+            )
+        })?;
+        let file = Path::new(&out_dir).join(GENERATED_FILE);
+
+        self.write_to(&file)?;
+
+        Ok(file)
+    }
+
+    fn files(&self) -> Result<Vec<(PathBuf, String)>, errors::FromPlantumlError> {
+        let mut files: Vec<(PathBuf, String)> = utils::walk_files(&self.dir, false)?
+            .into_iter()
+            .filter(|path| {
+                path.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| self.extensions.iter().any(|x| x == e))
+            })
+            .map(|path| {
+                let relative = path
+                    .strip_prefix(&self.dir)
+                    .unwrap_or(&path)
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                (path, relative)
+            })
+            .collect();
+
+        files.sort_by(|a, b| a.1.cmp(&b.1));
+
+        Ok(files)
+    }
+}
+
+/// Encode plantuml files of the directory into [`GENERATED_FILE`] in `OUT_DIR`
+/// with [`DirEncoder`] defaults and print `cargo:rerun-if-changed` lines
+pub fn encode_dir<P: AsRef<Path>>(dir: P) -> Result<PathBuf, errors::FromPlantumlError> {
+    DirEncoder::new(dir).write()
+}
+
+fn const_name(relative: &str) -> String {
+    let stem = match relative.rfind('.') {
+        Some(dot) if dot > relative.rfind('/').map_or(0, |s| s + 1) => &relative[..dot],
+        _ => relative,
+    };
+
+    let mut name = String::new();
+
+    for c in stem.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_uppercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+
+    let name = name.trim_end_matches('_').to_string();
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{const_name, DirEncoder};
+
+    use crate::errors;
+    use crate::tests::fs::temp_dir;
+    use crate::url::OutputFormat;
+
+    #[test]
+    fn it_const_name() {
+        assert_eq!(const_name("overview.puml"), "OVERVIEW");
+        assert_eq!(const_name("arch/c4-context.v2.puml"), "ARCH_C4_CONTEXT_V2");
+        assert_eq!(const_name("1st step.pu"), "_1ST_STEP");
+    }
+
+    #[test]
+    fn it_dir_encoder_generate() {
+        let dir = temp_dir("build_generate");
+
+        fs::create_dir_all(dir.join("arch")).unwrap();
+        fs::write(
+            dir.join("arch/overview.puml"),
+            "@startuml\nPUML -> RUST\n@enduml",
+        )
+        .unwrap();
+        fs::write(dir.join("readme.md"), "# skipped").unwrap();
+
+        let encoder = DirEncoder::new(&dir)
+            .server("http://localhost:8080")
+            .format(OutputFormat::Png);
+
+        assert_eq!(
+            encoder.generate().map(|g| g.lines().skip(1).collect::<Vec<_>>().join("\n")),
+            Ok("\n/// `arch/overview.puml`\npub const ARCH_OVERVIEW: &str = \"http://localhost:8080/png/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000\";".to_string())
+        );
+        assert_eq!(
+            encoder.rerun_if_changed(),
+            Ok(vec![dir.clone(), dir.join("arch/overview.puml")])
+        );
+
+        let file = dir.join("generated.rs");
+
+        assert_eq!(encoder.write_to(&file), Ok(()));
+        assert_eq!(fs::read_to_string(&file).ok(), encoder.generate().ok());
+    }
+
+    #[test]
+    fn it_dir_encoder_escapes_urls() {
+        let dir = temp_dir("build_escape");
+
+        fs::write(dir.join("a.puml"), "@startuml\nPUML -> RUST\n@enduml").unwrap();
+
+        let generated = DirEncoder::new(&dir)
+            .server("http://localhost/\"quoted\"\\path")
+            .generate()
+            .unwrap();
+
+        assert!(generated.contains(
+            "pub const A: &str = \"http://localhost/\\\"quoted\\\"\\\\path/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000\";"
+        ));
+    }
+
+    #[test]
+    fn it_dir_encoder_duplicate_names() {
+        let dir = temp_dir("build_duplicate");
+
+        fs::write(dir.join("a-b.puml"), "@startuml\n@enduml").unwrap();
+        fs::write(dir.join("a_b.pu"), "@startuml\n@enduml").unwrap();
+
+        assert_eq!(
+            DirEncoder::new(&dir).generate(),
+            Err(errors::FromPlantumlError(
+                "`a-b.puml` and `a_b.pu` have the same constant name `A_B`".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use crate::deflate;
+use crate::errors;
+use crate::normalize;
+use crate::render;
+use crate::url;
+
+/// Default size limit of the cache directory (256 MiB)
+pub const DEFAULT_CACHE_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+const META_EXTENSION: &str = "meta";
+
+/// Renderer that keeps artifacts of another renderer in a directory.
+///
+/// Artifacts are keyed by the identity of the renderer (e.g. the server),
+/// the format and the deflate encoded diagram. Hits are served from the directory
+/// without calling the renderer, the least recently used artifacts are evicted
+/// when the directory grows over [`CachedRenderer::max_bytes`].
+/// Failures of writing the directory do not fail the render, the artifact is just not cached.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{
+///     CachedRenderer, FromPlantumlError, OutputFormat, Rendered, Renderer,
+/// };
+///
+/// struct Echo;
+///
+/// impl Renderer for Echo {
+///     fn render(&self, plantuml: &str, format: OutputFormat) -> Result<Rendered, FromPlantumlError> {
+///         Ok(Rendered { format, content_type: "text/plain".to_string(), bytes: plantuml.as_bytes().to_vec() })
+///     }
+///
+///     fn identity(&self) -> String {
+///         "echo".to_string()
+///     }
+/// }
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let cached = CachedRenderer::new(Echo, std::env::temp_dir().join("plantuml_cache_example"));
+///
+///     let first = cached.render("@startuml\nPUML -> RUST\n@enduml", OutputFormat::Txt)?;
+///     let second = cached.render("@startuml\nPUML -> RUST\n@enduml", OutputFormat::Txt)?;
+///
+///     assert_eq!(first, second);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CachedRenderer<R> {
+    renderer: R,
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl<R: render::Renderer> CachedRenderer<R> {
+    /// Cache of the renderer in the directory (created on the first render)
+    pub fn new<P: AsRef<Path>>(renderer: R, dir: P) -> Self {
+        CachedRenderer {
+            renderer,
+            dir: dir.as_ref().to_path_buf(),
+            max_bytes: DEFAULT_CACHE_MAX_BYTES,
+        }
+    }
+
+    /// Size limit of the artifacts (default is [`DEFAULT_CACHE_MAX_BYTES`])
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Wrapped renderer
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+
+    /// Cache key of the diagram: identity of the renderer, format and deflate encoded diagram
+    pub fn key<T: AsRef<str>>(
+        &self,
+        plantuml: T,
+        format: url::OutputFormat,
+    ) -> Result<String, errors::FromPlantumlError> {
+        Ok(format!(
+            "{}\n{}\n{}",
+            self.renderer.identity(),
+            format.as_str(),
+            deflate::encode_plantuml_deflate(plantuml)?
+        ))
+    }
+
+    /// Cached artifact of the diagram (without rendering)
+    pub fn cached<T: AsRef<str>>(
+        &self,
+        plantuml: T,
+        format: url::OutputFormat,
+    ) -> Result<Option<render::Rendered>, errors::FromPlantumlError> {
+        let key = self.key(plantuml, format)?;
+        let (data, meta) = self.paths(&key, format);
+
+        // artifacts with another key (hash collisions) or without data are misses
+        let content_type = match fs::read_to_string(&meta) {
+            Ok(meta) => match meta.split_once('\n') {
+                Some((content_type, stored)) if stored == key => content_type.to_string(),
+                _ => return Ok(None),
+            },
+            Err(_) => return Ok(None),
+        };
+
+        let bytes = match fs::read(&data) {
+            Ok(bytes) => bytes,
+            Err(_) => return Ok(None),
+        };
+
+        // modification time is the last use for the eviction
+        if let Ok(file) = fs::File::options().append(true).open(&data) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        Ok(Some(render::Rendered {
+            format,
+            content_type,
+            bytes,
+        }))
+    }
+
+    /// Remove all artifacts
+    pub fn clear(&self) -> Result<(), errors::FromPlantumlError> {
+        for (path, _, _) in self.artifacts()? {
+            self.remove(&path)?;
+        }
+
+        Ok(())
+    }
+
+    fn paths(&self, key: &str, format: url::OutputFormat) -> (PathBuf, PathBuf) {
+        let name = normalize::plantuml_hash(key);
+
+        (
+            self.dir.join(format!("{}.{}", name, format.as_str())),
+            self.dir.join(format!("{}.{}", name, META_EXTENSION)),
+        )
+    }
+
+    fn store(
+        &self,
+        key: &str,
+        rendered: &render::Rendered,
+    ) -> Result<(), errors::FromPlantumlError> {
+        let (data, meta) = self.paths(key, rendered.format);
+
+        fs::create_dir_all(&self.dir).map_err(|err| io_error("creating", &self.dir, err))?;
+
+        // data goes first, so a concurrent reader never sees the meta of a missing
+        // or half written data file
+        self.write_atomically(&data, rendered.bytes.as_slice())?;
+        self.write_atomically(
+            &meta,
+            format!("{}\n{}", rendered.content_type, key).as_bytes(),
+        )?;
+
+        self.evict(&data)
+    }
+
+    /// Write a temporary file in the directory and rename it to the path
+    fn write_atomically(&self, path: &Path, bytes: &[u8]) -> Result<(), errors::FromPlantumlError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let temporary = self.dir.join(format!(
+            ".{}.{}-{}.tmp",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        fs::write(&temporary, bytes)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|err| {
+                let _ = fs::remove_file(&temporary);
+
+                io_error("writing", path, err)
+            })
+    }
+
+    /// Remove the least recently used artifacts (except the kept one) over the limit
+    fn evict(&self, keep: &Path) -> Result<(), errors::FromPlantumlError> {
+        let mut artifacts = self.artifacts()?;
+        let mut total: u64 = artifacts.iter().map(|(_, size, _)| size).sum();
+
+        artifacts.sort_by_key(|(_, _, modified)| *modified);
+
+        for (path, size, _) in artifacts {
+            if total <= self.max_bytes {
+                break;
+            }
+
+            if path != keep {
+                self.remove(&path)?;
+                total -= size;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Data files of the artifacts with their sizes and modification times
+    fn artifacts(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>, errors::FromPlantumlError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(vec![]),
+        };
+
+        let mut artifacts = vec![];
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_data = path.with_extension(META_EXTENSION).is_file()
+                && path.extension().is_some_and(|e| e != META_EXTENSION);
+
+            if let (true, Ok(metadata)) = (is_data, entry.metadata()) {
+                artifacts.push((
+                    path,
+                    metadata.len(),
+                    metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                ));
+            }
+        }
+
+        Ok(artifacts)
+    }
+
+    /// Remove the artifact (already removed files are fine, e.g. evicted by another process)
+    fn remove(&self, data: &Path) -> Result<(), errors::FromPlantumlError> {
+        for path in [data.to_path_buf(), data.with_extension(META_EXTENSION)] {
+            match fs::remove_file(&path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => {
+                    return Err(io_error("removing", &path, err))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: render::Renderer> render::Renderer for CachedRenderer<R> {
+    fn render(
+        &self,
+        plantuml: &str,
+        format: url::OutputFormat,
+    ) -> Result<render::Rendered, errors::FromPlantumlError> {
+        if let Some(rendered) = self.cached(plantuml, format)? {
+            return Ok(rendered);
+        }
+
+        let rendered = self.renderer.render(plantuml, format)?;
+
+        // the render succeeded, so a cache that can not be written is just a miss
+        let _ = self.store(&self.key(plantuml, format)?, &rendered);
+
+        Ok(rendered)
+    }
+
+    fn identity(&self) -> String {
+        self.renderer.identity()
+    }
+}
+
+fn io_error(action: &str, path: &Path, err: std::io::Error) -> errors::FromPlantumlError {
+    errors::FromPlantumlError(format!(
+        "there is a problem during {} `{}`: `{}`",
+        action,
+        path.display(),
+        err
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    use super::CachedRenderer;
+
+    use crate::errors;
+    use crate::render::{Rendered, Renderer};
+    use crate::tests::fs::temp_dir;
+    use crate::url::OutputFormat;
+
+    /// Renderer returning the source and recording rendered sources
+    struct Fake {
+        identity: &'static str,
+        rendered: RefCell<Vec<String>>,
+    }
+
+    impl Fake {
+        fn new(identity: &'static str) -> Self {
+            Fake {
+                identity,
+                rendered: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl Renderer for Fake {
+        fn render(
+            &self,
+            plantuml: &str,
+            format: OutputFormat,
+        ) -> Result<Rendered, errors::FromPlantumlError> {
+            if plantuml.is_empty() {
+                return Err(errors::FromPlantumlError("empty".to_string()));
+            }
+
+            self.rendered.borrow_mut().push(plantuml.to_string());
+
+            Ok(Rendered {
+                format,
+                content_type: "text/plain".to_string(),
+                bytes: plantuml.as_bytes().to_vec(),
+            })
+        }
+
+        fn identity(&self) -> String {
+            self.identity.to_string()
+        }
+    }
+
+    /// Set the last use of the artifact explicitly, file systems may have coarse mtimes
+    fn set_used(cached: &CachedRenderer<Fake>, plantuml: &str, seconds: u64) {
+        let (data, _) = cached.paths(
+            &cached.key(plantuml, OutputFormat::Txt).unwrap(),
+            OutputFormat::Txt,
+        );
+
+        fs::File::options()
+            .append(true)
+            .open(data)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn it_serves_hits_without_rendering() {
+        let dir = temp_dir("cache_hits");
+        let cached = CachedRenderer::new(Fake::new("a"), &dir);
+
+        let first = cached.render("A -> B", OutputFormat::Svg).unwrap();
+
+        assert_eq!(cached.render("A -> B", OutputFormat::Svg).unwrap(), first);
+        assert_eq!(cached.renderer().rendered.borrow().len(), 1);
+
+        cached.render("A -> B", OutputFormat::Png).unwrap();
+
+        assert_eq!(cached.renderer().rendered.borrow().len(), 2);
+
+        let other = CachedRenderer::new(Fake::new("b"), &dir);
+
+        assert_eq!(other.cached("A -> B", OutputFormat::Svg), Ok(None));
+        assert!(cached.render("", OutputFormat::Svg).is_err());
+
+        cached.clear().unwrap();
+
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn it_ignores_foreign_artifacts() {
+        let dir = temp_dir("cache_foreign");
+        let cached = CachedRenderer::new(Fake::new("a"), &dir);
+
+        cached.render("A -> B", OutputFormat::Txt).unwrap();
+
+        for entry in fs::read_dir(&dir).unwrap().flatten() {
+            if entry.path().extension().is_some_and(|e| e == "meta") {
+                fs::write(entry.path(), "text/plain\nother key").unwrap();
+            }
+        }
+
+        assert_eq!(cached.cached("A -> B", OutputFormat::Txt), Ok(None));
+    }
+
+    #[test]
+    fn it_renders_when_cache_is_not_writable() {
+        let dir = temp_dir("cache_not_writable");
+        let file = dir.join("file");
+
+        fs::write(&file, "").unwrap();
+
+        let cached = CachedRenderer::new(Fake::new("a"), &file);
+
+        assert!(cached.render("A -> B", OutputFormat::Txt).is_ok());
+        assert_eq!(cached.cached("A -> B", OutputFormat::Txt), Ok(None));
+        assert_eq!(cached.remove(&dir.join("missing.txt")), Ok(()));
+    }
+
+    #[test]
+    fn it_evicts_least_recently_used() {
+        let dir = temp_dir("cache_eviction");
+        let cached = CachedRenderer::new(Fake::new("a"), &dir).max_bytes(12);
+
+        cached.render("A -> B", OutputFormat::Txt).unwrap();
+        set_used(&cached, "A -> B", 1);
+        cached.render("B -> C", OutputFormat::Txt).unwrap();
+        set_used(&cached, "B -> C", 2);
+        cached.render("A -> B", OutputFormat::Txt).unwrap();
+        set_used(&cached, "A -> B", 3);
+        cached.render("C -> D", OutputFormat::Txt).unwrap();
+
+        assert!(cached
+            .cached("A -> B", OutputFormat::Txt)
+            .unwrap()
+            .is_some());
+        assert!(cached
+            .cached("B -> C", OutputFormat::Txt)
+            .unwrap()
+            .is_none());
+        assert!(cached
+            .cached("C -> D", OutputFormat::Txt)
+            .unwrap()
+            .is_some());
+        assert_eq!(cached.renderer().rendered.borrow().len(), 3);
+    }
+
+    #[test]
+    fn it_stores_without_temporary_files() {
+        let dir = temp_dir("cache_temporary");
+        let cached = CachedRenderer::new(Fake::new("a"), &dir);
+
+        cached.render("A -> B", OutputFormat::Txt).unwrap();
+
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| {
+                entry
+                    .path()
+                    .extension()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+
+        names.sort();
+
+        assert_eq!(names, vec!["meta", "txt"]);
+    }
+}
@@ -0,0 +1,329 @@
+use std::path::{Path, PathBuf};
+
+use crate::deflate;
+use crate::errors;
+use crate::scan;
+use crate::url;
+
+/// Rule broken by a plantuml link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckRule {
+    /// Payload has characters out of the deflate (or hex) alphabet or is truncated
+    Alphabet,
+    /// Payload is not a complete deflate stream
+    Inflate,
+    /// Inflated payload is not UTF-8 text
+    Utf8,
+    /// `@start*` and `@end*` tags of the source are not balanced
+    Unbalanced,
+    /// Link is longer than the limit
+    TooLong,
+}
+
+impl CheckRule {
+    /// All rules in the order of checks
+    pub const ALL: [CheckRule; 5] = [
+        CheckRule::Alphabet,
+        CheckRule::Inflate,
+        CheckRule::Utf8,
+        CheckRule::Unbalanced,
+        CheckRule::TooLong,
+    ];
+
+    /// Name of the rule (`alphabet`, `inflate`, `utf8`, `unbalanced` or `too-long`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckRule::Alphabet => "alphabet",
+            CheckRule::Inflate => "inflate",
+            CheckRule::Utf8 => "utf8",
+            CheckRule::Unbalanced => "unbalanced",
+            CheckRule::TooLong => "too-long",
+        }
+    }
+
+    /// Short description of the rule
+    pub fn description(&self) -> &'static str {
+        match self {
+            CheckRule::Alphabet => "payload uses the plantuml alphabet and is not truncated",
+            CheckRule::Inflate => "payload is a complete deflate stream",
+            CheckRule::Utf8 => "decoded payload is UTF-8 text",
+            CheckRule::Unbalanced => "@start and @end tags of the source are balanced",
+            CheckRule::TooLong => "link is not longer than the limit",
+        }
+    }
+}
+
+/// Problem of a plantuml link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckProblem {
+    /// Broken rule
+    pub rule: CheckRule,
+    /// Details of the problem
+    pub message: String,
+}
+
+/// Plantuml link with its problems
+#[derive(Debug, PartialEq)]
+pub struct CheckedLink {
+    /// Found link or payload
+    pub found: scan::Found,
+    /// Problems (empty for valid links)
+    pub problems: Vec<CheckProblem>,
+}
+
+impl CheckedLink {
+    /// Whether the link has no problems
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Length of the link (or of the bare payload)
+    pub fn length(&self) -> usize {
+        self.found.span.len()
+    }
+}
+
+/// Checked links of a file
+#[derive(Debug, PartialEq)]
+pub struct CheckedFile {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Checked links
+    pub links: Vec<CheckedLink>,
+}
+
+/// Check the found link strictly: alphabet, inflate and UTF-8 decoding of the payload,
+/// balanced `@start*` / `@end*` tags of the source and the length limit
+pub fn check_plantuml_found(found: &scan::Found, max_length: usize) -> Vec<CheckProblem> {
+    let mut problems = vec![];
+
+    match decode_strict(&found.encoded) {
+        Ok(plantuml) => {
+            if let Some(message) = unbalanced_tags(&plantuml) {
+                problems.push(CheckProblem {
+                    rule: CheckRule::Unbalanced,
+                    message,
+                });
+            }
+        }
+        Err(problem) => problems.push(problem),
+    }
+
+    if found.span.len() > max_length {
+        problems.push(CheckProblem {
+            rule: CheckRule::TooLong,
+            message: format!(
+                "link is {} characters long (limit is {})",
+                found.span.len(),
+                max_length
+            ),
+        });
+    }
+
+    problems
+}
+
+/// Find plantuml links in the text with [`crate::scan_plantuml`] and check them
+/// with [`check_plantuml_found`]
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{check_plantuml, CheckRule, DEFAULT_MAX_URL_LENGTH};
+///
+/// let text = "![ok](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)\n\
+///             ![broken](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8)";
+///
+/// let checked = check_plantuml(text, DEFAULT_MAX_URL_LENGTH);
+///
+/// assert!(checked[0].is_ok());
+/// assert_eq!(checked[1].problems[0].rule, CheckRule::Inflate);
+/// assert!(!check_plantuml(text, 50)[0].is_ok());
+/// ```
+pub fn check_plantuml<T: AsRef<str>>(text: T, max_length: usize) -> Vec<CheckedLink> {
+    check_links(scan::scan_plantuml(text), max_length)
+}
+
+/// Check files and directories (recursively, skipping hidden ones) with [`check_plantuml`],
+/// files without plantuml links are not returned
+pub fn check_plantuml_files<I, P>(
+    paths: I,
+    max_length: usize,
+) -> Result<Vec<CheckedFile>, errors::FromPlantumlError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    Ok(scan::scan_plantuml_files(paths)?
+        .into_iter()
+        .map(|file| CheckedFile {
+            path: file.path,
+            links: check_links(file.found, max_length),
+        })
+        .collect())
+}
+
+fn check_links(found: Vec<scan::Found>, max_length: usize) -> Vec<CheckedLink> {
+    found
+        .into_iter()
+        .map(|found| CheckedLink {
+            problems: check_plantuml_found(&found, max_length),
+            found,
+        })
+        .collect()
+}
+
+/// Decode the payload with [`url::decode_plantuml_strict`], classifying the problem
+fn decode_strict(encoded: &str) -> Result<String, CheckProblem> {
+    url::decode_plantuml_strict(encoded).map_err(|(step, message)| CheckProblem {
+        rule: match step {
+            deflate::DecodeStep::Alphabet => CheckRule::Alphabet,
+            deflate::DecodeStep::Inflate => CheckRule::Inflate,
+            deflate::DecodeStep::Utf8 => CheckRule::Utf8,
+        },
+        message,
+    })
+}
+
+/// Description of the first unbalanced `@start*` / `@end*` tag
+fn unbalanced_tags(plantuml: &str) -> Option<String> {
+    let mut open: Option<(&str, usize)> = None;
+
+    for (index, line) in plantuml.lines().enumerate() {
+        let line = line.trim();
+        let tag = |prefix: &str| {
+            line.strip_prefix(prefix).map(|rest| {
+                rest.split(|c: char| c.is_whitespace() || c == '(')
+                    .next()
+                    .unwrap_or_default()
+            })
+        };
+
+        if let Some(name) = tag("@start") {
+            if let Some((open_name, open_line)) = open {
+                return Some(format!(
+                    "`@start{}` at line {} is not closed before `@start{}` at line {}",
+                    open_name,
+                    open_line,
+                    name,
+                    index + 1
+                ));
+            }
+
+            open = Some((name, index + 1));
+        } else if let Some(name) = tag("@end") {
+            match open.take() {
+                Some((open_name, _)) if open_name == name => {}
+                Some((open_name, open_line)) => {
+                    return Some(format!(
+                        "`@start{}` at line {} is closed by `@end{}` at line {}",
+                        open_name,
+                        open_line,
+                        name,
+                        index + 1
+                    ))
+                }
+                None => {
+                    return Some(format!(
+                        "`@end{}` at line {} has no `@start{}`",
+                        name,
+                        index + 1,
+                        name
+                    ))
+                }
+            }
+        }
+    }
+
+    open.map(|(name, line)| format!("`@start{}` at line {} is not closed", name, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{check_plantuml, check_plantuml_files, decode_strict, unbalanced_tags, CheckRule};
+
+    use crate::deflate::encode_plantuml_deflate;
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_hex_str::PLANTUML_HEX_SMALL,
+        plantuml_str::PLANTUML_SMALL,
+    };
+    use crate::tests::fs::temp_dir;
+
+    fn rule(encoded: &str) -> Option<CheckRule> {
+        decode_strict(encoded).err().map(|problem| problem.rule)
+    }
+
+    #[test]
+    fn it_decodes_strictly() {
+        assert_eq!(
+            decode_strict(PLANTUML_DEFLATED_SMALL),
+            Ok(PLANTUML_SMALL.to_string())
+        );
+        assert_eq!(
+            decode_strict(PLANTUML_HEX_SMALL),
+            Ok(PLANTUML_SMALL.to_string())
+        );
+        assert_eq!(rule("SoWk.ImgAStDuGe8"), Some(CheckRule::Alphabet));
+        assert_eq!(rule("SoWkIImgAStDuGe"), Some(CheckRule::Alphabet));
+        assert_eq!(rule("~h40zz"), Some(CheckRule::Alphabet));
+        assert_eq!(rule("~h407"), Some(CheckRule::Alphabet));
+        assert_eq!(rule("SoWkIImgAStDuGe8"), Some(CheckRule::Inflate));
+        assert_eq!(rule("~hff"), Some(CheckRule::Utf8));
+    }
+
+    #[test]
+    fn it_finds_unbalanced_tags() {
+        assert_eq!(unbalanced_tags("A -> B"), None);
+        assert_eq!(unbalanced_tags(PLANTUML_SMALL), None);
+        assert_eq!(
+            unbalanced_tags("@startuml\nA -> B\n@startuml\n@enduml"),
+            Some("`@startuml` at line 1 is not closed before `@startuml` at line 3".to_string())
+        );
+        assert_eq!(
+            unbalanced_tags("@startmindmap\n* a\n@enduml"),
+            Some("`@startmindmap` at line 1 is closed by `@enduml` at line 3".to_string())
+        );
+        assert_eq!(
+            unbalanced_tags("@startuml(id=a)\nA -> B"),
+            Some("`@startuml` at line 1 is not closed".to_string())
+        );
+    }
+
+    #[test]
+    fn it_check_plantuml() {
+        let unbalanced = encode_plantuml_deflate("@startuml\nA -> B").unwrap();
+        let text = format!(
+            "https://www.plantuml.com/plantuml/svg/{}\nhttps://www.plantuml.com/plantuml/png/{}",
+            PLANTUML_DEFLATED_SMALL, unbalanced
+        );
+
+        let checked = check_plantuml(&text, 100);
+
+        assert_eq!(checked.len(), 2);
+        assert!(checked[0].is_ok());
+        assert_eq!(checked[0].length(), 38 + PLANTUML_DEFLATED_SMALL.len());
+        assert_eq!(checked[1].problems[0].rule, CheckRule::Unbalanced);
+
+        let checked = check_plantuml(&text, 60);
+
+        assert_eq!(
+            checked[0].problems[0].message,
+            "link is 98 characters long (limit is 60)"
+        );
+    }
+
+    #[test]
+    fn it_check_plantuml_files() {
+        let dir = temp_dir("check_files");
+
+        fs::write(dir.join("a.md"), PLANTUML_HEX_SMALL).unwrap();
+        fs::write(dir.join("b.md"), "nothing").unwrap();
+
+        let checked = check_plantuml_files([&dir], 4000).unwrap();
+
+        assert_eq!(checked.len(), 1);
+        assert!(checked[0].links[0].is_ok());
+    }
+}
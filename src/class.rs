@@ -0,0 +1,596 @@
+use std::collections::BTreeMap;
+
+use crate::deflate;
+use crate::errors;
+use crate::url;
+use crate::utils;
+
+const KEYWORDS: [&str; 12] = [
+    "as",
+    "class",
+    "interface",
+    "enum",
+    "abstract",
+    "package",
+    "component",
+    "node",
+    "database",
+    "artifact",
+    "cloud",
+    "actor",
+];
+
+/// Kind of the element of class, component or deployment diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ElementKind {
+    /// `class`
+    Class,
+    /// `abstract class`
+    AbstractClass,
+    /// `interface`
+    Interface,
+    /// `enum`
+    Enum,
+    /// `annotation`
+    Annotation,
+    /// `component`
+    Component,
+    /// `node`
+    Node,
+    /// `database`
+    Database,
+    /// `artifact`
+    Artifact,
+    /// `cloud`
+    Cloud,
+    /// `actor`
+    Actor,
+}
+
+impl ElementKind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            ElementKind::Class => "class",
+            ElementKind::AbstractClass => "abstract class",
+            ElementKind::Interface => "interface",
+            ElementKind::Enum => "enum",
+            ElementKind::Annotation => "annotation",
+            ElementKind::Component => "component",
+            ElementKind::Node => "node",
+            ElementKind::Database => "database",
+            ElementKind::Artifact => "artifact",
+            ElementKind::Cloud => "cloud",
+            ElementKind::Actor => "actor",
+        }
+    }
+
+    fn has_body(&self) -> bool {
+        matches!(
+            self,
+            ElementKind::Class
+                | ElementKind::AbstractClass
+                | ElementKind::Interface
+                | ElementKind::Enum
+                | ElementKind::Annotation
+        )
+    }
+}
+
+/// Element (class, interface, component, node and so on) of the diagram
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    kind: ElementKind,
+    name: String,
+    alias: Option<String>,
+    stereotype: Option<String>,
+    package: Option<String>,
+    members: Vec<String>,
+}
+
+impl Element {
+    /// Element of the given kind
+    pub fn new<T: AsRef<str>>(kind: ElementKind, name: T) -> Self {
+        Element {
+            kind,
+            name: name.as_ref().to_string(),
+            alias: None,
+            stereotype: None,
+            package: None,
+            members: vec![],
+        }
+    }
+
+    /// `class`
+    pub fn class<T: AsRef<str>>(name: T) -> Self {
+        Element::new(ElementKind::Class, name)
+    }
+
+    /// `interface`
+    pub fn interface<T: AsRef<str>>(name: T) -> Self {
+        Element::new(ElementKind::Interface, name)
+    }
+
+    /// `enum`
+    pub fn enumeration<T: AsRef<str>>(name: T) -> Self {
+        Element::new(ElementKind::Enum, name)
+    }
+
+    /// `component`
+    pub fn component<T: AsRef<str>>(name: T) -> Self {
+        Element::new(ElementKind::Component, name)
+    }
+
+    /// `node`
+    pub fn node<T: AsRef<str>>(name: T) -> Self {
+        Element::new(ElementKind::Node, name)
+    }
+
+    /// Short alias used to reference the element in relations
+    pub fn alias<T: AsRef<str>>(mut self, alias: T) -> Self {
+        self.alias = Some(alias.as_ref().to_string());
+        self
+    }
+
+    /// Stereotype (`<<stereotype>>`)
+    pub fn stereotype<T: AsRef<str>>(mut self, stereotype: T) -> Self {
+        self.stereotype = Some(stereotype.as_ref().to_string());
+        self
+    }
+
+    /// Package the element is rendered in
+    pub fn package<T: AsRef<str>>(mut self, package: T) -> Self {
+        self.package = Some(package.as_ref().to_string());
+        self
+    }
+
+    /// Member (field, method or enum value) in plantuml syntax, for example `+name : String`
+    /// (rendered only for classes, interfaces, enums and annotations,
+    /// a leading `}` is escaped so it does not close the body)
+    pub fn member<T: AsRef<str>>(mut self, member: T) -> Self {
+        let member = utils::escape_label(member.as_ref());
+        let trimmed = member.trim_start();
+
+        self.members.push(match trimmed.strip_prefix('}') {
+            Some(rest) => format!("&#125;{}", rest),
+            None => member,
+        });
+        self
+    }
+
+    pub(crate) fn id(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    /// `package.id` for elements in packages, `id` otherwise
+    fn qualified_id(&self) -> String {
+        match &self.package {
+            Some(package) => format!("{}.{}", package, self.id()),
+            None => self.id().to_string(),
+        }
+    }
+
+    fn render(&self, indent: &str, lines: &mut Vec<String>) {
+        let mut line = format!("{}{} ", indent, self.kind.keyword());
+
+        match &self.alias {
+            Some(alias) => {
+                line += &format!(
+                    "{} as {}",
+                    utils::quote_name(&self.name),
+                    utils::name_or_quoted(alias, &KEYWORDS)
+                );
+            }
+            None => line += &utils::name_or_quoted(&self.name, &KEYWORDS),
+        }
+
+        if let Some(stereotype) = &self.stereotype {
+            line += &format!(" <<{}>>", utils::escape_label(stereotype));
+        }
+
+        if self.kind.has_body() && !self.members.is_empty() {
+            lines.push(line + " {");
+            lines.extend(self.members.iter().map(|m| format!("{}  {}", indent, m)));
+            lines.push(format!("{}}}", indent));
+        } else {
+            lines.push(line);
+        }
+    }
+}
+
+/// Kind of the relation between elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RelationKind {
+    /// `--`
+    Association,
+    /// `-->`
+    DirectedAssociation,
+    /// `..>`
+    Dependency,
+    /// `--|>` (from child to parent)
+    Inheritance,
+    /// `..|>` (from implementation to interface)
+    Realization,
+    /// `*--` (from whole to part)
+    Composition,
+    /// `o--` (from whole to part)
+    Aggregation,
+}
+
+impl RelationKind {
+    fn arrow(&self) -> &'static str {
+        match self {
+            RelationKind::Association => "--",
+            RelationKind::DirectedAssociation => "-->",
+            RelationKind::Dependency => "..>",
+            RelationKind::Inheritance => "--|>",
+            RelationKind::Realization => "..|>",
+            RelationKind::Composition => "*--",
+            RelationKind::Aggregation => "o--",
+        }
+    }
+}
+
+/// Relation between elements (referenced by alias or name)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Relation {
+    from: String,
+    to: String,
+    kind: RelationKind,
+    from_multiplicity: Option<String>,
+    to_multiplicity: Option<String>,
+    label: Option<String>,
+    stereotype: Option<String>,
+}
+
+impl Relation {
+    /// Relation of the given kind
+    pub fn new<F: AsRef<str>, T: AsRef<str>>(kind: RelationKind, from: F, to: T) -> Self {
+        Relation {
+            from: from.as_ref().to_string(),
+            to: to.as_ref().to_string(),
+            kind,
+            from_multiplicity: None,
+            to_multiplicity: None,
+            label: None,
+            stereotype: None,
+        }
+    }
+
+    /// Multiplicities of both ends (for example `"1"` and `"0..*"`)
+    pub fn multiplicity<F: AsRef<str>, T: AsRef<str>>(mut self, from: F, to: T) -> Self {
+        self.from_multiplicity = Some(from.as_ref().to_string());
+        self.to_multiplicity = Some(to.as_ref().to_string());
+        self
+    }
+
+    pub(crate) fn target_multiplicity<T: AsRef<str>>(mut self, to: T) -> Self {
+        self.to_multiplicity = Some(to.as_ref().to_string());
+        self
+    }
+
+    /// Label of the relation
+    pub fn label<T: AsRef<str>>(mut self, label: T) -> Self {
+        self.label = Some(label.as_ref().to_string());
+        self
+    }
+
+    /// Stereotype of the relation (rendered in the label)
+    pub fn stereotype<T: AsRef<str>>(mut self, stereotype: T) -> Self {
+        self.stereotype = Some(stereotype.as_ref().to_string());
+        self
+    }
+}
+
+/// Builder of [class](https://plantuml.com/class-diagram),
+/// [component](https://plantuml.com/component-diagram) and
+/// [deployment](https://plantuml.com/deployment-diagram) diagrams.
+///
+/// Elements are rendered sorted by package and alias (or name), relations are
+/// sorted and deduplicated, so the same model always produces the same plantuml
+/// regardless of the order it was added in.
+///
+/// Relations reference elements by alias or name, or by `package.alias` when
+/// the same alias is used in several packages (a bare alias is an error then).
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{ClassDiagram, Element, FromPlantumlError, Relation, RelationKind};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let diagram = ClassDiagram::new()
+///         .element(Element::class("RUST").member("+encode() : String"))
+///         .element(Element::interface("PUML"))
+///         .relation(Relation::new(RelationKind::Realization, "RUST", "PUML"));
+///
+///     assert_eq!(
+///         diagram.plantuml()?,
+///         "@startuml\ninterface PUML\nclass RUST {\n  +encode() : String\n}\nRUST ..|> PUML\n@enduml"
+///     );
+///
+///     let encoded_deflate = diagram.encode_deflate()?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClassDiagram {
+    title: Option<String>,
+    elements: BTreeMap<(Option<String>, String), Element>,
+    relations: Vec<Relation>,
+}
+
+impl ClassDiagram {
+    /// Empty diagram
+    pub fn new() -> Self {
+        ClassDiagram::default()
+    }
+
+    /// Title of the diagram
+    pub fn title<T: AsRef<str>>(mut self, title: T) -> Self {
+        self.title = Some(title.as_ref().to_string());
+        self
+    }
+
+    /// Add element (an element with the same package and alias or name is replaced)
+    pub fn element(mut self, element: Element) -> Self {
+        self.elements
+            .insert((element.package.clone(), element.id().to_string()), element);
+        self
+    }
+
+    /// Add relation
+    pub fn relation(mut self, relation: Relation) -> Self {
+        self.relations.push(relation);
+        self
+    }
+
+    /// Plantuml of the diagram (fails on ambiguous references of relations)
+    pub fn plantuml(&self) -> Result<String, errors::FromPlantumlError> {
+        let mut lines = vec!["@startuml".to_string()];
+
+        if let Some(title) = &self.title {
+            lines.push(format!("title {}", utils::escape_label(title)));
+        }
+
+        let mut package: Option<&String> = None;
+
+        for ((element_package, _), element) in self.elements.iter() {
+            if element_package.as_ref() != package {
+                if package.is_some() {
+                    lines.push("}".to_string());
+                }
+
+                if let Some(p) = element_package {
+                    lines.push(format!("package {} {{", utils::quote_name(p)));
+                }
+
+                package = element_package.as_ref();
+            }
+
+            element.render(if package.is_some() { "  " } else { "" }, &mut lines);
+        }
+
+        if package.is_some() {
+            lines.push("}".to_string());
+        }
+
+        let mut relations = self.relations.clone();
+        relations.sort();
+        relations.dedup();
+
+        for relation in relations.iter() {
+            let mut line = self.reference(&relation.from)?;
+
+            if let Some(m) = &relation.from_multiplicity {
+                line += &format!(" {}", utils::quote_name(m));
+            }
+
+            line += &format!(" {}", relation.kind.arrow());
+
+            if let Some(m) = &relation.to_multiplicity {
+                line += &format!(" {}", utils::quote_name(m));
+            }
+
+            line += &format!(" {}", self.reference(&relation.to)?);
+
+            let label = match (&relation.stereotype, &relation.label) {
+                (Some(s), Some(l)) => Some(format!("<<{}>> {}", s, l)),
+                (Some(s), None) => Some(format!("<<{}>>", s)),
+                (None, Some(l)) => Some(l.clone()),
+                (None, None) => None,
+            };
+
+            if let Some(label) = label {
+                line += &format!(" : {}", utils::escape_label(&label));
+            }
+
+            lines.push(line);
+        }
+
+        lines.push("@enduml".to_string());
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Encode the diagram with deflate compression
+    pub fn encode_deflate(&self) -> Result<String, errors::FromPlantumlError> {
+        deflate::encode_plantuml_deflate(self.plantuml()?)
+    }
+
+    /// Server URL of the diagram
+    pub fn url<S: AsRef<str>>(
+        &self,
+        server: S,
+        format: url::OutputFormat,
+    ) -> Result<String, errors::FromPlantumlError> {
+        Ok(url::plantuml_url(server, format, self.encode_deflate()?))
+    }
+
+    fn reference(&self, element: &str) -> Result<String, errors::FromPlantumlError> {
+        let qualified: Vec<&Element> = self
+            .elements
+            .values()
+            .filter(|e| e.package.is_some() && e.qualified_id() == element)
+            .collect();
+        let found: Vec<&Element> = if qualified.is_empty() {
+            self.elements
+                .values()
+                .filter(|e| e.id() == element || e.name == element)
+                .collect()
+        } else {
+            qualified
+        };
+
+        match found.as_slice() {
+            [] => Ok(utils::name_or_quoted(element, &KEYWORDS)),
+            [found] => {
+                // elements of other packages with the same alias need the package
+                let shared = self
+                    .elements
+                    .values()
+                    .filter(|e| e.id() == found.id())
+                    .count()
+                    > 1;
+                let id = if shared {
+                    found.qualified_id()
+                } else {
+                    found.id().to_string()
+                };
+
+                Ok(utils::name_or_quoted(&id, &KEYWORDS))
+            }
+            _ => Err(errors::FromPlantumlError(format!(
+                "reference `{}` is ambiguous, use one of {}",
+                element,
+                found
+                    .iter()
+                    .map(|e| format!("`{}`", e.qualified_id()))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassDiagram, Element, ElementKind, Relation, RelationKind};
+
+    use crate::deflate::decode_plantuml_deflate;
+    use crate::errors;
+
+    fn model() -> Vec<Element> {
+        vec![
+            Element::class("Order")
+                .package("shop")
+                .member("+id : u64")
+                .member("+lines : Vec<Line>"),
+            Element::class("Line").package("shop"),
+            Element::enumeration("Status").member("NEW").member("PAID"),
+            Element::new(ElementKind::AbstractClass, "Base Entity")
+                .alias("Base")
+                .stereotype("entity"),
+        ]
+    }
+
+    #[test]
+    fn it_class_diagram_stable_ordering() {
+        let relations = [
+            Relation::new(RelationKind::Composition, "Order", "Line").multiplicity("1", "1..*"),
+            Relation::new(RelationKind::Inheritance, "Order", "Base"),
+            Relation::new(RelationKind::DirectedAssociation, "Order", "Status").label("status"),
+        ];
+
+        let forward = model()
+            .into_iter()
+            .fold(ClassDiagram::new(), ClassDiagram::element);
+        let forward = relations
+            .iter()
+            .cloned()
+            .fold(forward, ClassDiagram::relation);
+
+        let backward = model()
+            .into_iter()
+            .rev()
+            .fold(ClassDiagram::new(), ClassDiagram::element);
+        let backward = relations
+            .iter()
+            .rev()
+            .cloned()
+            .chain(relations.iter().cloned())
+            .fold(backward, ClassDiagram::relation);
+
+        assert_eq!(forward.plantuml(), backward.plantuml());
+        assert_eq!(forward.encode_deflate(), backward.encode_deflate());
+        assert_eq!(
+            forward.plantuml().unwrap(),
+            "@startuml\nabstract class \"Base Entity\" as Base <<entity>>\nenum Status {\n  NEW\n  PAID\n}\npackage \"shop\" {\n  class Line\n  class Order {\n    +id : u64\n    +lines : Vec<Line>\n  }\n}\nOrder --|> Base\nOrder \"1\" *-- \"1..*\" Line\nOrder --> Status : status\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_component_diagram() {
+        let diagram = ClassDiagram::new()
+            .title("Deployment\nview")
+            .element(Element::node("k8s cluster").alias("k8s"))
+            .element(
+                Element::component("API Gateway")
+                    .alias("gw")
+                    .member("ignored"),
+            )
+            .element(Element::new(ElementKind::Database, "postgres"))
+            .relation(Relation::new(RelationKind::Dependency, "gw", "postgres").stereotype("jdbc"))
+            .relation(Relation::new(
+                RelationKind::Association,
+                "k8s",
+                "API Gateway",
+            ));
+
+        assert_eq!(
+            diagram.plantuml().unwrap(),
+            "@startuml\ntitle Deployment\\nview\ncomponent \"API Gateway\" as gw\nnode \"k8s cluster\" as k8s\ndatabase postgres\ngw ..> postgres : <<jdbc>>\nk8s -- gw\n@enduml"
+        );
+        assert_eq!(
+            decode_plantuml_deflate(diagram.encode_deflate().unwrap()),
+            diagram.plantuml()
+        );
+    }
+
+    #[test]
+    fn it_class_diagram_escapes_members() {
+        let diagram =
+            ClassDiagram::new().element(Element::class("A").member("}").member("{static} +x : u8"));
+
+        assert_eq!(
+            diagram.plantuml().unwrap(),
+            "@startuml\nclass A {\n  &#125;\n  {static} +x : u8\n}\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_class_diagram_qualified_references() {
+        let diagram = ClassDiagram::new()
+            .element(Element::class("Line").package("shop"))
+            .element(Element::class("Line").package("billing"))
+            .element(Element::class("Order").package("shop"))
+            .relation(Relation::new(
+                RelationKind::Composition,
+                "Order",
+                "shop.Line",
+            ));
+
+        assert_eq!(
+            diagram.plantuml().unwrap(),
+            "@startuml\npackage \"billing\" {\n  class Line\n}\npackage \"shop\" {\n  class Line\n  class Order\n}\nOrder *-- shop.Line\n@enduml"
+        );
+        assert_eq!(
+            diagram
+                .relation(Relation::new(RelationKind::Association, "Order", "Line"))
+                .plantuml(),
+            Err(errors::FromPlantumlError(
+                "reference `Line` is ambiguous, use one of `billing.Line`, `shop.Line`".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+
+use crate::deflate;
+use crate::errors;
+use crate::render;
+use crate::url;
+
+/// Request made by [`HttpRenderer`] for a diagram
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpRequest {
+    /// GET of the plantuml server URL with the encoded diagram
+    Get(String),
+    /// POST of the raw source to the format URL of the server
+    Post {
+        /// Format URL (`{server}/{format}`)
+        url: String,
+        /// Plantuml source
+        body: String,
+    },
+}
+
+/// Renderer with a plantuml server over HTTP (requires `client` feature).
+///
+/// Diagrams are requested with GET of [`crate::plantuml_url`], sources whose URLs
+/// would be longer than [`HttpRenderer::max_url_length`] are sent with POST.
+/// Transport errors and `5xx` responses are retried.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use plantuml_encoding::{FromPlantumlError, HttpRenderer, OutputFormat, Renderer};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let renderer = HttpRenderer::new("http://localhost:8080/plantuml")
+///         .timeout(Duration::from_secs(10))
+///         .retries(2);
+///
+///     let rendered = renderer.render("@startuml\nPUML -> RUST\n@enduml", OutputFormat::Svg)?;
+///
+///     std::fs::write("diagram.svg", rendered.bytes)?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRenderer {
+    server: String,
+    max_url_length: usize,
+    timeout: Duration,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Default for HttpRenderer {
+    fn default() -> Self {
+        HttpRenderer::new(url::PLANTUML_SERVER)
+    }
+}
+
+impl HttpRenderer {
+    /// Renderer with the server (e.g. [`crate::PLANTUML_SERVER`]),
+    /// 30 seconds timeout and without retries
+    pub fn new<S: AsRef<str>>(server: S) -> Self {
+        HttpRenderer {
+            server: server.as_ref().trim_end_matches('/').to_string(),
+            max_url_length: url::DEFAULT_MAX_URL_LENGTH,
+            timeout: Duration::from_secs(30),
+            retries: 0,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+
+    /// Limit of GET URLs, longer diagrams are sent with POST
+    /// (default is [`crate::DEFAULT_MAX_URL_LENGTH`])
+    pub fn max_url_length(mut self, max_url_length: usize) -> Self {
+        self.max_url_length = max_url_length;
+        self
+    }
+
+    /// Timeout of every request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Number of retries after transport errors and `5xx` responses
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Delay before every retry (default is 500 milliseconds)
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Request that renders the diagram (GET or POST by the URL length)
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use plantuml_encoding::{FromPlantumlError, HttpRenderer, HttpRequest, OutputFormat};
+    ///
+    /// fn main() -> Result<(), FromPlantumlError> {
+    ///     let renderer = HttpRenderer::new("http://localhost:8080");
+    ///
+    ///     assert_eq!(
+    ///         renderer.request("@startuml\nPUML -> RUST\n@enduml", OutputFormat::Png)?,
+    ///         HttpRequest::Get("http://localhost:8080/png/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000".to_string())
+    ///     );
+    ///     assert!(matches!(
+    ///         renderer.max_url_length(20).request("@startuml\nPUML -> RUST\n@enduml", OutputFormat::Png)?,
+    ///         HttpRequest::Post { .. }
+    ///     ));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn request<T: AsRef<str>>(
+        &self,
+        plantuml: T,
+        format: url::OutputFormat,
+    ) -> Result<HttpRequest, errors::FromPlantumlError> {
+        let plantuml = plantuml.as_ref();
+        let get = url::plantuml_url(
+            &self.server,
+            format,
+            deflate::encode_plantuml_deflate(plantuml)?,
+        );
+
+        if get.len() <= self.max_url_length {
+            return Ok(HttpRequest::Get(get));
+        }
+
+        Ok(HttpRequest::Post {
+            url: format!("{}/{}", self.server, format.as_str()),
+            body: plantuml.to_string(),
+        })
+    }
+}
+
+impl render::Renderer for HttpRenderer {
+    fn render(
+        &self,
+        plantuml: &str,
+        format: url::OutputFormat,
+    ) -> Result<render::Rendered, errors::FromPlantumlError> {
+        let request = self.request(plantuml, format)?;
+        let target = match &request {
+            HttpRequest::Get(url) => url,
+            HttpRequest::Post { url, .. } => url,
+        };
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+        let mut attempt = 0;
+
+        let response = loop {
+            let result = match &request {
+                HttpRequest::Get(url) => agent.get(url).call(),
+                HttpRequest::Post { url, body } => agent
+                    .post(url)
+                    .set("Content-Type", "text/plain; charset=utf-8")
+                    .send_string(body),
+            };
+
+            let error = match result {
+                Ok(response) => break response,
+                Err(ureq::Error::Status(status, response)) if status < 500 => {
+                    return Err(status_error(target, status, &response))
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    status_error(target, status, &response)
+                }
+                Err(err) => errors::FromPlantumlError(format!(
+                    "there is a problem during rendering `{}`: `{}`",
+                    target, err
+                )),
+            };
+
+            if attempt >= self.retries {
+                return Err(error);
+            }
+
+            attempt += 1;
+            thread::sleep(self.retry_delay);
+        };
+
+        let content_type = response
+            .header("Content-Type")
+            .unwrap_or_else(|| render::content_type(format))
+            .to_string();
+        let mut bytes = vec![];
+
+        response
+            .into_reader()
+            .read_to_end(&mut bytes)
+            .map_err(|err| {
+                errors::FromPlantumlError(format!(
+                    "there is a problem during reading `{}`: `{}`",
+                    target, err
+                ))
+            })?;
+
+        Ok(render::Rendered {
+            format,
+            content_type,
+            bytes,
+        })
+    }
+
+    fn identity(&self) -> String {
+        self.server.clone()
+    }
+}
+
+/// Error of the response, with the diagram error reported by the plantuml server
+fn status_error(url: &str, status: u16, response: &ureq::Response) -> errors::FromPlantumlError {
+    match response.header("X-PlantUML-Diagram-Error") {
+        Some(error) => errors::FromPlantumlError(format!(
+            "diagram error{}: {} (`{}`)",
+            response
+                .header("X-PlantUML-Diagram-Error-Line")
+                .map(|line| format!(" at line {}", line))
+                .unwrap_or_default(),
+            error,
+            url
+        )),
+        None => errors::FromPlantumlError(format!(
+            "there is a problem during rendering `{}`: status {}",
+            url, status
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    use super::HttpRenderer;
+
+    use crate::errors;
+    use crate::render::Renderer;
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_str::PLANTUML_SMALL,
+    };
+    use crate::url::OutputFormat;
+
+    /// Server answering connections with the responses, returns received requests
+    fn mock(responses: Vec<&'static str>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = format!("http://{}/plantuml", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut length = 0;
+
+                loop {
+                    let mut line = String::new();
+
+                    reader.read_line(&mut line).unwrap();
+
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+
+                    if line == "\r\n" {
+                        break;
+                    }
+
+                    request.push_str(&line);
+                }
+
+                let mut body = vec![0; length];
+
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                requests.push(request);
+
+                if response.is_empty() {
+                    thread::sleep(Duration::from_millis(500));
+                } else {
+                    stream.write_all(response.as_bytes()).unwrap();
+                }
+            }
+
+            requests
+        });
+
+        (server, handle)
+    }
+
+    #[test]
+    fn it_renders_with_get() {
+        let (server, handle) = mock(vec![
+            "HTTP/1.1 200 OK\r\nContent-Type: image/svg+xml\r\nContent-Length: 6\r\n\r\n<svg/>",
+        ]);
+
+        let rendered = HttpRenderer::new(&server)
+            .render(PLANTUML_SMALL, OutputFormat::Svg)
+            .unwrap();
+        let requests = handle.join().unwrap();
+
+        assert_eq!(rendered.bytes, b"<svg/>");
+        assert_eq!(rendered.content_type, "image/svg+xml");
+        assert!(requests[0].starts_with(&format!(
+            "GET /plantuml/svg/{} HTTP/1.1\r\n",
+            PLANTUML_DEFLATED_SMALL
+        )));
+    }
+
+    #[test]
+    fn it_renders_oversized_with_post_and_retries() {
+        let (server, handle) = mock(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nPUML",
+        ]);
+
+        let rendered = HttpRenderer::new(&server)
+            .max_url_length(16)
+            .retries(1)
+            .retry_delay(Duration::from_millis(1))
+            .render(PLANTUML_SMALL, OutputFormat::Txt)
+            .unwrap();
+        let requests = handle.join().unwrap();
+
+        assert_eq!(rendered.bytes, b"PUML");
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("POST /plantuml/txt HTTP/1.1\r\n"));
+        assert!(requests[1].ends_with(PLANTUML_SMALL));
+    }
+
+    #[test]
+    fn it_reports_diagram_errors() {
+        let (server, handle) = mock(vec![
+            "HTTP/1.1 400 Bad Request\r\nX-PlantUML-Diagram-Error: Syntax Error?\r\nX-PlantUML-Diagram-Error-Line: 2\r\nContent-Length: 0\r\n\r\n",
+        ]);
+
+        let result = HttpRenderer::new(&server)
+            .retries(3)
+            .render("@startuml\nPUML -> \n@enduml", OutputFormat::Png);
+
+        assert_eq!(handle.join().unwrap().len(), 1);
+        assert_eq!(
+            result.map_err(|err| err.0.split(" (").next().map(str::to_string)),
+            Err(Some("diagram error at line 2: Syntax Error?".to_string()))
+        );
+    }
+
+    #[test]
+    fn it_times_out() {
+        let (server, handle) = mock(vec![""]);
+
+        let result = HttpRenderer::new(&server)
+            .timeout(Duration::from_millis(200))
+            .render(PLANTUML_SMALL, OutputFormat::Svg);
+
+        handle.join().unwrap();
+
+        assert!(
+            matches!(result, Err(errors::FromPlantumlError(err)) if err.contains("there is a problem during rendering"))
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use flate2::write;
+use flate2::{read, write};
 use std::io::prelude::*;
 
 use crate::errors;
@@ -70,9 +70,62 @@ pub fn decode_plantuml_deflate<T: AsRef<str>>(
     Ok(String::from_utf8(decoded_bytes)?)
 }
 
+const DEFLATE_ALPHABET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz-_";
+
+/// Step of strict decoding that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DecodeStep {
+    /// Characters out of the alphabet or truncated payload
+    Alphabet,
+    /// Incomplete or corrupt deflate stream
+    Inflate,
+    /// Decoded bytes are not UTF-8 text
+    Utf8,
+}
+
+/// Decode plantuml with deflate compression without the leniency of
+/// [`decode_plantuml_deflate`]: truncated or garbage payloads are errors
+pub(crate) fn decode_plantuml_deflate_strict(
+    plantuml_deflated: &str,
+) -> Result<String, (DecodeStep, String)> {
+    if let Some((i, c)) = plantuml_deflated
+        .char_indices()
+        .find(|(_, c)| !DEFLATE_ALPHABET.contains(*c))
+    {
+        return Err((
+            DecodeStep::Alphabet,
+            format!("invalid character `{}` at {}", c, i + 1),
+        ));
+    }
+
+    let compressed = match utils::decode_plantuml_for_deflate(plantuml_deflated) {
+        Some(compressed) if !plantuml_deflated.is_empty() => compressed,
+        _ => {
+            return Err((
+                DecodeStep::Alphabet,
+                format!(
+                    "payload length {} is not a multiple of 4 (truncated link?)",
+                    plantuml_deflated.len()
+                ),
+            ))
+        }
+    };
+
+    let mut inflated = vec![];
+
+    read::DeflateDecoder::new(compressed.as_slice())
+        .read_to_end(&mut inflated)
+        .map_err(|err| (DecodeStep::Inflate, err.to_string()))?;
+
+    String::from_utf8(inflated).map_err(|err| (DecodeStep::Utf8, err.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{decode_plantuml_deflate, encode_plantuml_deflate};
+    use super::{
+        decode_plantuml_deflate, decode_plantuml_deflate_strict, encode_plantuml_deflate,
+        DecodeStep,
+    };
 
     use crate::errors;
     use crate::tests::constants::{
@@ -148,4 +201,19 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn it_decode_plantuml_deflate_strict() {
+        let step = |encoded: &str| decode_plantuml_deflate_strict(encoded).err().map(|e| e.0);
+
+        assert_eq!(
+            decode_plantuml_deflate_strict(PLANTUML_DEFLATED_SMALL),
+            Ok(PLANTUML_SMALL.to_string())
+        );
+        assert_eq!(step("SoWk.ImgAStDuGe8"), Some(DecodeStep::Alphabet));
+        assert_eq!(step("SoWkIImgAStDuGe"), Some(DecodeStep::Alphabet));
+        assert_eq!(step(""), Some(DecodeStep::Alphabet));
+        assert_eq!(step("SoWkIImgAStDuGe8"), Some(DecodeStep::Inflate));
+        assert_eq!(step("logo"), Some(DecodeStep::Inflate));
+    }
 }
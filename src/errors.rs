@@ -26,3 +26,13 @@ impl convert::From<hex::FromHexError> for FromPlantumlError {
         FromPlantumlError(format!("there is a problem during hex decoding: `{}`", err))
     }
 }
+
+#[cfg(feature = "json")]
+impl convert::From<serde_json::Error> for FromPlantumlError {
+    fn from(err: serde_json::Error) -> Self {
+        FromPlantumlError(format!(
+            "there is a problem during json serialization: `{}`",
+            err
+        ))
+    }
+}
@@ -0,0 +1,569 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::deflate;
+use crate::errors;
+use crate::minify;
+use crate::normalize;
+use crate::textconv;
+use crate::utils;
+use crate::validate;
+
+/// Extensions of the files looked up in directories by [`format_plantuml_files`]
+pub const FORMAT_EXTENSIONS: [&str; 5] = ["puml", "plantuml", "pu", "iuml", "wsd"];
+
+const INDENT: &str = "  ";
+
+/// Keywords written in lowercase when they start a line (not a link)
+const KEYWORDS: &str = "\
+    abstract activate actor agent alt annotation artifact autonumber boundary box break card \
+    case circle class cloud collections component control create critical database deactivate \
+    destroy detach else elseif end endif endswitch endwhile entity enum file folder footer \
+    fork frame group header hide hnote if interface kill label legend loop namespace newpage \
+    node note object opt package par participant partition queue rectangle ref repeat return \
+    rnote show skinparam split stack start state stop storage switch title usecase while";
+
+/// Lines written in lowercase
+const PHRASES: [&str; 2] = ["left to right direction", "top to bottom direction"];
+
+/// Block opened by a line and closed by one of the closers
+struct Frame {
+    closers: &'static [&'static str],
+    /// Text lines (notes, legends...) are re-indented only
+    verbatim: bool,
+    /// Members of classes, skinparams... are indented only
+    plain: bool,
+}
+
+/// Formatted lines with pending blank line
+#[derive(Default)]
+struct Output {
+    lines: Vec<String>,
+    /// Blank line before the next line
+    blank: bool,
+    /// The last line opened a block (no blank line after it)
+    opened: bool,
+}
+
+impl Output {
+    fn blank(&mut self) {
+        self.blank = !self.lines.is_empty() && !self.opened;
+    }
+
+    /// Indented line, `closing` lines (`end`, `else`, `@enduml`...) drop the blank line before them
+    fn push(&mut self, depth: usize, line: &str, closing: bool) {
+        if self.blank && !closing {
+            self.lines.push(String::new());
+        }
+
+        self.lines.push(format!("{}{}", INDENT.repeat(depth), line));
+        self.blank = false;
+        self.opened = false;
+    }
+
+    fn push_raw(&mut self, line: &str) {
+        self.lines.push(line.to_string());
+        self.blank = false;
+        self.opened = false;
+    }
+
+    /// Text of a verbatim block without its common indentation
+    fn push_text(&mut self, depth: usize, text: &[&str]) {
+        let indent = text
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+            .min()
+            .unwrap_or(0);
+
+        for line in text {
+            if line.trim().is_empty() {
+                self.push_raw("");
+            } else {
+                self.push_raw(&format!("{}{}", INDENT.repeat(depth), &line[indent..]));
+            }
+        }
+    }
+}
+
+/// Format plantuml source deterministically (after [`crate::normalize_plantuml`]),
+/// so equivalent sources are encoded to the same links:
+///
+/// - blocks (`alt` / `end`, `if` / `endif`, `note` / `end note`, `{ ... }`...) are indented
+///   by two spaces, `else`, `fork again`, `case`... are at the level of their block
+/// - arrows of links have one space around them (`A -> B`)
+/// - keywords starting lines (`participant`, `alt`, `end note`, `skinparam`...) are lowercase
+/// - blank lines are collapsed, none are kept after openers or before closers
+///
+/// Text of notes, legends..., multi-line activity labels, block comments and diagrams
+/// other than `@startuml` are kept as is (re-indented for notes and legends).
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::format_plantuml;
+///
+/// let formatted = format_plantuml(
+///     "@startuml\n\nParticipant Alice\n   ALT ok\nAlice->Bob : hi\n\n\n  Else\nBob-->Alice\nEnd\n@enduml\n",
+/// );
+///
+/// assert_eq!(
+///     formatted,
+///     "@startuml\nparticipant Alice\nalt ok\n  Alice -> Bob : hi\nelse\n  Bob --> Alice\nend\n@enduml"
+/// );
+/// ```
+pub fn format_plantuml<T: AsRef<str>>(plantuml: T) -> String {
+    let normalized = normalize::normalize_plantuml(plantuml);
+    let mut out = Output::default();
+    let mut blocks: Vec<Frame> = vec![];
+    let mut text: Vec<&str> = vec![];
+    let mut uml = true;
+    let mut in_comment = false;
+    let mut in_label = false;
+
+    for raw in normalized.lines() {
+        let trimmed = raw.trim();
+
+        if let Some(name) = validate::tag_name(trimmed, "@start") {
+            out.push_text(blocks.len(), &text);
+            out.push(0, trimmed, false);
+            out.opened = true;
+            text.clear();
+            blocks.clear();
+            uml = name == "uml";
+            in_comment = false;
+            in_label = false;
+            continue;
+        }
+
+        if validate::tag_name(trimmed, "@end").is_some() {
+            out.push_text(blocks.len(), &text);
+            out.push(0, trimmed, true);
+            text.clear();
+            blocks.clear();
+            uml = true;
+            in_comment = false;
+            in_label = false;
+            continue;
+        }
+
+        // other diagrams (mindmap, ditaa, json...) have their own syntax
+        if !uml {
+            out.push_raw(raw);
+            continue;
+        }
+
+        let parts = validate::block_line(trimmed);
+        let lowercase = parts.lowercase.as_str();
+
+        if let Some(frame) = blocks.last().filter(|frame| frame.verbatim) {
+            if frame
+                .closers
+                .iter()
+                .any(|closer| validate::is_closer(lowercase, closer))
+            {
+                out.push_text(blocks.len(), &text);
+                text.clear();
+                blocks.pop();
+
+                let closer = parts.closer.unwrap_or_default();
+
+                out.push(blocks.len(), &with_keyword(trimmed, closer), true);
+            } else {
+                text.push(raw);
+            }
+
+            continue;
+        }
+
+        if in_comment || in_label {
+            in_label = in_label && !trimmed.ends_with(';');
+            minify::strip_block_comments(raw, &mut in_comment);
+            out.push_raw(raw);
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            out.blank();
+            continue;
+        }
+
+        let depth = blocks.len();
+        let code = minify::strip_block_comments(raw, &mut in_comment);
+        let code = code.trim();
+
+        if code.is_empty() || code.starts_with(['\'', '!']) {
+            out.push(depth, trimmed, false);
+            continue;
+        }
+
+        let first = parts.first.as_str();
+
+        if let Some(closer) = parts.closer {
+            if let Some(position) = blocks
+                .iter()
+                .rposition(|frame| frame.closers.contains(&closer))
+            {
+                blocks.truncate(position);
+            }
+
+            out.push(blocks.len(), &with_keyword(trimmed, closer), true);
+            continue;
+        }
+
+        if blocks
+            .last()
+            .is_some_and(|frame| validate::is_branch(first, lowercase, frame.closers))
+        {
+            out.push(depth - 1, &with_keyword(trimmed, first), true);
+            out.opened = true;
+            continue;
+        }
+
+        if blocks.last().is_some_and(|frame| frame.plain) {
+            out.push(depth, trimmed, false);
+
+            if code.ends_with('{') {
+                blocks.push(Frame {
+                    closers: validate::BRACE_CLOSERS,
+                    verbatim: false,
+                    plain: true,
+                });
+                out.opened = true;
+            }
+
+            continue;
+        }
+
+        if let Some((closers, verbatim)) = parts.opener {
+            out.push(depth, &with_keyword(trimmed, first), false);
+            out.opened = true;
+            blocks.push(Frame {
+                closers,
+                verbatim,
+                plain: false,
+            });
+            continue;
+        }
+
+        let is_link = validate::is_link(code);
+        let mut line = match PHRASES.iter().find(|phrase| lowercase == **phrase) {
+            Some(phrase) => phrase.to_string(),
+            None if !is_link && KEYWORDS.split_whitespace().any(|k| k == first) => {
+                with_keyword(trimmed, first)
+            }
+            None => trimmed.to_string(),
+        };
+
+        // arrows of activity labels are text, comments are not moved
+        if is_link && code == trimmed && !code.starts_with(':') {
+            line = spaced_arrow(&line).unwrap_or(line);
+        }
+
+        out.push(depth, &line, false);
+
+        if code.ends_with('{') && !is_link {
+            out.opened = true;
+            blocks.push(Frame {
+                closers: validate::BRACE_CLOSERS,
+                verbatim: false,
+                plain: validate::MEMBER_BODIES.contains(&first) || first.starts_with("skinparam"),
+            });
+        } else if matches!(minify::verbatim_start(code), Some(minify::Verbatim::Label)) {
+            in_label = true;
+        }
+    }
+
+    out.push_text(blocks.len(), &text);
+
+    out.lines.join("\n")
+}
+
+/// Encode plantuml with deflate compression (as [`crate::encode_plantuml_deflate`])
+/// after formatting it with [`format_plantuml`]
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{encode_plantuml_deflate_formatted, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     assert_eq!(
+///         encode_plantuml_deflate_formatted("@startuml\n\n  PUML->RUST\n@enduml\n")?,
+///         "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000"
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+pub fn encode_plantuml_deflate_formatted<T: AsRef<str>>(
+    plantuml: T,
+) -> Result<String, errors::FromPlantumlError> {
+    deflate::encode_plantuml_deflate(format_plantuml(plantuml))
+}
+
+/// File formatted by [`format_plantuml_files`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormattedFile {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Original content of the file
+    pub original: String,
+    /// Formatted content of the file (with a final line break)
+    pub formatted: String,
+}
+
+impl FormattedFile {
+    /// Whether the file is already formatted
+    pub fn is_formatted(&self) -> bool {
+        self.original == self.formatted
+    }
+
+    /// Unified diff from the original to the formatted content (empty if formatted)
+    pub fn diff(&self) -> String {
+        let path = self.path.display().to_string().replace('\\', "/");
+
+        textconv::unified_diff(
+            &self.original,
+            &self.formatted,
+            &format!("a/{}", path),
+            &format!("b/{}", path),
+        )
+    }
+}
+
+/// Format files and directories (recursively, [`FORMAT_EXTENSIONS`] only)
+/// with [`format_plantuml`], rewriting files that are not formatted if `write` is set
+pub fn format_plantuml_files<I, P>(
+    paths: I,
+    write: bool,
+) -> Result<Vec<FormattedFile>, errors::FromPlantumlError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut formatted_files = vec![];
+
+    for path in paths {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            utils::walk_files(path, true)?
+                .into_iter()
+                .filter(|file| {
+                    file.extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| FORMAT_EXTENSIONS.contains(&e))
+                })
+                .collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        for file in files {
+            let io_error = |action: &str, err: std::io::Error| {
+                errors::FromPlantumlError(format!(
+                    "there is a problem during {} `{}`: `{}`",
+                    action,
+                    file.display(),
+                    err
+                ))
+            };
+
+            let original = fs::read_to_string(&file).map_err(|err| io_error("reading", err))?;
+            let mut formatted = format_plantuml(&original);
+
+            if !formatted.is_empty() {
+                formatted.push('\n');
+            }
+
+            if write && original != formatted {
+                fs::write(&file, &formatted).map_err(|err| io_error("writing", err))?;
+            }
+
+            formatted_files.push(FormattedFile {
+                path: file,
+                original,
+                formatted,
+            });
+        }
+    }
+
+    Ok(formatted_files)
+}
+
+/// Line with its first words (lowercase, separated by one space) written as `words`
+fn with_keyword(trimmed: &str, words: &str) -> String {
+    let mut rest = trimmed;
+
+    for word in words.split(' ') {
+        rest = rest.trim_start();
+
+        match rest.get(..word.len()) {
+            Some(start) if start.eq_ignore_ascii_case(word) => rest = &rest[word.len()..],
+            _ => return trimmed.to_string(),
+        }
+    }
+
+    format!("{}{}", words, rest)
+}
+
+/// Link line with one space around its arrow (none inside `[` / `]` of incoming
+/// and outgoing messages), `None` for malformed arrows
+fn spaced_arrow(line: &str) -> Option<String> {
+    let (arrow, rest, offset) = validate::arrow_of(line)?;
+
+    if validate::bad_arrow(line).is_some() {
+        return None;
+    }
+
+    let left = line[..offset].trim_end();
+    let mut spaced = left.to_string();
+
+    if !left.ends_with('[') {
+        spaced.push(' ');
+    }
+
+    spaced.push_str(arrow);
+
+    if !rest.is_empty() && !rest.starts_with(']') {
+        spaced.push(' ');
+    }
+
+    spaced.push_str(rest);
+
+    Some(spaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{encode_plantuml_deflate_formatted, format_plantuml, format_plantuml_files};
+
+    use crate::tests::constants::plantuml_str::{PLANTUML_LARGE, PLANTUML_SMALL};
+    use crate::tests::fs::temp_dir;
+
+    #[test]
+    fn it_format_plantuml_indentation() {
+        assert_eq!(
+            format_plantuml(
+                "@startuml\nbox \"Services\"\nparticipant A\nend box\nloop 10 times\nalt ok\nA -> B\nelse error\n    note right\n        first\n          second\n    end note\nend\nend\nclass Car {\n  Wheel wheels\n}\npackage p {\nclass Engine\n}\n@enduml"
+            ),
+            "@startuml\nbox \"Services\"\n  participant A\nend box\nloop 10 times\n  alt ok\n    A -> B\n  else error\n    note right\n      first\n        second\n    end note\n  end\nend\nclass Car {\n  Wheel wheels\n}\npackage p {\n  class Engine\n}\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_format_plantuml_activity() {
+        assert_eq!(
+            format_plantuml(
+                "@startuml\nstart\nif (ok?) then (yes)\n:multi\n line;\nelseif (maybe) then\nfork\n:a;\nfork again\n:b;\nend fork\nelse\nswitch (x)\ncase (1)\n:c->d;\nendswitch\nendif\nrepeat\n:e;\nrepeat while (more?)\nstop\n@enduml"
+            ),
+            "@startuml\nstart\nif (ok?) then (yes)\n  :multi\n line;\nelseif (maybe) then\n  fork\n    :a;\n  fork again\n    :b;\n  end fork\nelse\n  switch (x)\n  case (1)\n    :c->d;\n  endswitch\nendif\nrepeat\n  :e;\nrepeat while (more?)\nstop\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_format_plantuml_arrows() {
+        assert_eq!(
+            format_plantuml(
+                "A->B: hi\nA  -->  B : x->y\n[->A\nA ->] : out\nA \"1\" *--   \"many\" B\nA => B\nA -> B /' kept '/"
+            ),
+            "A -> B: hi\nA --> B : x->y\n[-> A\nA ->] : out\nA \"1\" *-- \"many\" B\nA => B\nA -> B /' kept '/"
+        );
+    }
+
+    #[test]
+    fn it_format_plantuml_keywords() {
+        assert_eq!(
+            format_plantuml(
+                "@startuml\nLeft To Right Direction\nACTOR User\nParticipant -> User\nNote Left Of User\ntext\nEnd  Note\nSkinparam Sequence {\n  ArrowColor red\n}\n@enduml"
+            ),
+            "@startuml\nleft to right direction\nactor User\nParticipant -> User\nnote Left Of User\n  text\nend note\nskinparam Sequence {\n  ArrowColor red\n}\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_format_plantuml_blank_lines() {
+        assert_eq!(
+            format_plantuml(
+                "\n\n@startuml\n\nA -> B\n\n\n\nB -> C\n' comment\nalt x\n\nC -> D\n\nelse\n\nD -> E\n\nend\n\n@enduml\n\n\n@startuml\nnote over A\nfirst\n\nsecond\nend note\n@enduml\n\n"
+            ),
+            "@startuml\nA -> B\n\nB -> C\n' comment\nalt x\n  C -> D\nelse\n  D -> E\nend\n@enduml\n\n@startuml\nnote over A\n  first\n\n  second\nend note\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_format_plantuml_keeps_other_diagrams() {
+        let mindmap = "@startmindmap\n* root\n  ** child\n\n\n** other\n@endmindmap";
+
+        assert_eq!(format_plantuml(mindmap), mindmap);
+        assert_eq!(
+            format_plantuml("/' block\n   Comment -> A\n'/\nA->B\n!if %true()\nB->C\n!endif"),
+            "/' block\n   Comment -> A\n'/\nA -> B\n!if %true()\nB -> C\n!endif"
+        );
+    }
+
+    #[test]
+    fn it_format_plantuml_non_ascii_first_token() {
+        assert_eq!(
+            format_plantuml("@startuml\n\u{2126}->B\n  \u{212A}(x) -> B\n@enduml"),
+            "@startuml\n\u{2126} -> B\n\u{212A}(x) -> B\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_format_plantuml_is_idempotent() {
+        for plantuml in [
+            PLANTUML_SMALL,
+            PLANTUML_LARGE,
+            "@startuml\nalt a\n  note left\n      x\n    y\n  endnote\nelse\nclass A {\n  +f()\n}\nend\n@enduml",
+        ] {
+            let formatted = format_plantuml(plantuml);
+
+            assert_eq!(format_plantuml(&formatted), formatted);
+        }
+    }
+
+    #[test]
+    fn it_encode_plantuml_deflate_formatted() {
+        assert_eq!(
+            encode_plantuml_deflate_formatted("@startuml\r\nLOOP x\r\nA->B\r\nEND\r\n@enduml"),
+            encode_plantuml_deflate_formatted(
+                "@startuml\n\nloop x\n    A  ->  B\n\nend\n@enduml\n"
+            )
+        );
+    }
+
+    #[test]
+    fn it_format_plantuml_files_write() {
+        let dir = temp_dir("format_files");
+
+        fs::write(dir.join("a.puml"), "@startuml\nA->B\n@enduml").unwrap();
+        fs::write(dir.join("b.puml"), "@startuml\nA -> B\n@enduml\n").unwrap();
+        fs::write(dir.join("notes.txt"), "A->B").unwrap();
+
+        let mut checked = format_plantuml_files([&dir], false).unwrap();
+
+        checked.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(checked.len(), 2);
+        assert!(!checked[0].is_formatted());
+        assert!(checked[1].is_formatted());
+        assert!(checked[0]
+            .diff()
+            .ends_with("a.puml\n@@ -1,3 +1,3 @@\n @startuml\n-A->B\n+A -> B\n @enduml\n"));
+        assert_eq!(checked[1].diff(), "");
+
+        format_plantuml_files([&dir], true).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("a.puml")).unwrap(),
+            "@startuml\nA -> B\n@enduml\n"
+        );
+        assert!(format_plantuml_files([&dir], false)
+            .unwrap()
+            .iter()
+            .all(|file| file.is_formatted()));
+    }
+}
@@ -0,0 +1,635 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use crate::errors;
+use crate::utils;
+
+/// File inside the mirror directory that maps include URLs to mirrored files
+pub const MIRROR_MANIFEST: &str = "manifest.txt";
+
+/// Directory inside the mirror directory that holds the plantuml standard library
+pub const MIRROR_STDLIB: &str = "stdlib";
+
+/// Single line of the inlined plantuml together with the place it comes from
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SourceLine {
+    pub text: String,
+    pub origin: String,
+    pub line: usize,
+}
+
+/// Resolver of `!include`, `!include_once`, `!include_many` and `!includeurl`
+/// directives that inlines all referenced sources
+/// (local files, [standard library](https://plantuml.com/stdlib) and URLs)
+/// without any network access.
+///
+/// Standard library includes (`!include <C4/C4_Container>`) are looked up in
+/// the `stdlib` directory of the mirror, URL includes are looked up in the
+/// `manifest.txt` of the mirror with lines `<url> <relative path>`.
+/// A `!ID` suffix (`!include file.puml!ID`) selects the `@startuml(id=ID)` block
+/// of the file or the block with the index if `ID` is a number.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{encode_plantuml_deflate, FromPlantumlError, IncludeResolver};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let resolver = IncludeResolver::new(".");
+///
+///     let inlined = resolver.resolve("@startuml\nPUML -> RUST\n@enduml")?;
+///     let encoded_deflate = encode_plantuml_deflate(inlined)?;
+///
+///     assert_eq!(encoded_deflate, "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000");
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct IncludeResolver {
+    base_dir: PathBuf,
+    mirror_dir: Option<PathBuf>,
+    manifest: HashMap<String, PathBuf>,
+}
+
+enum IncludeTarget {
+    File(PathBuf),
+    Stdlib(String),
+    Url(String),
+}
+
+impl IncludeResolver {
+    /// Create resolver with local includes relative to `base_dir`
+    pub fn new<P: AsRef<Path>>(base_dir: P) -> Self {
+        IncludeResolver {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            mirror_dir: None,
+            manifest: HashMap::new(),
+        }
+    }
+
+    /// Use `mirror_dir` for standard library and URL includes
+    /// (reads `manifest.txt` of the mirror if it exists)
+    pub fn with_mirror<P: AsRef<Path>>(
+        mut self,
+        mirror_dir: P,
+    ) -> Result<Self, errors::FromPlantumlError> {
+        let mirror_dir = mirror_dir.as_ref().to_path_buf();
+        let manifest_path = mirror_dir.join(MIRROR_MANIFEST);
+
+        self.manifest = HashMap::new();
+
+        if manifest_path.is_file() {
+            let manifest = utils::read_file(&manifest_path)?;
+
+            for (index, line) in manifest.lines().enumerate() {
+                let line = line.trim();
+
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                match line.split_once(char::is_whitespace) {
+                    Some((url, file)) => {
+                        self.manifest
+                            .insert(url.to_string(), mirror_dir.join(file.trim()));
+                    }
+                    None => {
+                        return Err(errors::FromPlantumlError(format!(
+                            "invalid mirror manifest line {} in `{}`: `{}`",
+                            index + 1,
+                            manifest_path.display(),
+                            line
+                        )));
+                    }
+                }
+            }
+        }
+
+        self.mirror_dir = Some(mirror_dir);
+
+        Ok(self)
+    }
+
+    /// Inline all includes of `plantuml`
+    pub fn resolve<T: AsRef<str>>(&self, plantuml: T) -> Result<String, errors::FromPlantumlError> {
+        Ok(join_lines(
+            &self.resolve_lines(plantuml.as_ref(), "<input>")?,
+        ))
+    }
+
+    /// Read file and inline all its includes
+    /// (local includes are resolved relative to the file)
+    pub fn resolve_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<String, errors::FromPlantumlError> {
+        Ok(join_lines(&self.resolve_file_lines(path.as_ref())?))
+    }
+
+    pub(crate) fn resolve_lines(
+        &self,
+        plantuml: &str,
+        origin: &str,
+    ) -> Result<Vec<SourceLine>, errors::FromPlantumlError> {
+        let mut state = State::default();
+        let mut result = vec![];
+
+        self.inline(plantuml, origin, &self.base_dir, &mut state, &mut result)?;
+
+        Ok(result)
+    }
+
+    pub(crate) fn resolve_file_lines(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<SourceLine>, errors::FromPlantumlError> {
+        let mut state = State::default();
+        let mut result = vec![];
+
+        self.inline_file(path, &mut state, &mut result)?;
+
+        Ok(result)
+    }
+
+    fn inline(
+        &self,
+        plantuml: &str,
+        origin: &str,
+        dir: &Path,
+        state: &mut State,
+        result: &mut Vec<SourceLine>,
+    ) -> Result<(), errors::FromPlantumlError> {
+        for (index, line) in plantuml.lines().enumerate() {
+            let directive = match parse_include(line) {
+                Some(d) => d,
+                None => {
+                    result.push(SourceLine {
+                        text: line.to_string(),
+                        origin: origin.to_string(),
+                        line: index + 1,
+                    });
+                    continue;
+                }
+            };
+
+            let (keyword, argument) = directive;
+            let (file, subpart) = split_subpart(argument);
+            let target = self.target(keyword, file, dir);
+            let path = self.locate(&target, origin, index + 1)?;
+            let key = include_key(&path, subpart);
+
+            // as plantuml: `!include` inlines a file once, `!include_many` every time
+            // and `!include_once` fails if the file is already included (recursion fails below)
+            if state.included.contains(&key) && !state.stack.contains(&key) {
+                match keyword {
+                    "!include_many" => {}
+                    "!include_once" => {
+                        return Err(errors::FromPlantumlError(format!(
+                            "`{}` is already included ({}:{})",
+                            argument,
+                            origin,
+                            index + 1
+                        )))
+                    }
+                    _ => continue,
+                }
+            }
+
+            state.included.insert(key);
+
+            let included = match target {
+                IncludeTarget::File(_) => path.display().to_string(),
+                IncludeTarget::Stdlib(name) => format!("<{}>", name),
+                IncludeTarget::Url(url) => url,
+            };
+
+            let subpart = subpart.map(|id| (id, format!("{}:{}", origin, index + 1)));
+
+            self.inline_included(&path, &included, subpart, state, result)?;
+        }
+
+        Ok(())
+    }
+
+    fn inline_file(
+        &self,
+        path: &Path,
+        state: &mut State,
+        result: &mut Vec<SourceLine>,
+    ) -> Result<(), errors::FromPlantumlError> {
+        let origin = path.display().to_string();
+
+        self.inline_included(path, &origin, None, state, result)
+    }
+
+    fn inline_included(
+        &self,
+        path: &Path,
+        origin: &str,
+        subpart: Option<(&str, String)>,
+        state: &mut State,
+        result: &mut Vec<SourceLine>,
+    ) -> Result<(), errors::FromPlantumlError> {
+        let key = include_key(path, subpart.as_ref().map(|(id, _)| *id));
+
+        if state.stack.contains(&key) {
+            return Err(errors::FromPlantumlError(format!(
+                "recursive include of `{}`",
+                origin
+            )));
+        }
+
+        let content = utils::read_file(path)?;
+        let dir = path.parent().unwrap_or(&self.base_dir).to_path_buf();
+        let body = match subpart {
+            Some((id, location)) => select_subpart(&content, id).ok_or_else(|| {
+                errors::FromPlantumlError(format!(
+                    "subpart `{}` is not found in `{}` ({})",
+                    id, origin, location
+                ))
+            })?,
+            None => strip_start_end(&content),
+        };
+
+        state.stack.push(key);
+        self.inline(body, origin, &dir, state, result)?;
+        state.stack.pop();
+
+        Ok(())
+    }
+
+    fn target(&self, keyword: &str, argument: &str, dir: &Path) -> IncludeTarget {
+        if let Some(name) = argument.strip_prefix('<').and_then(|a| a.strip_suffix('>')) {
+            return IncludeTarget::Stdlib(name.to_string());
+        }
+
+        if keyword == "!includeurl"
+            || argument.starts_with("http://")
+            || argument.starts_with("https://")
+        {
+            return IncludeTarget::Url(argument.to_string());
+        }
+
+        IncludeTarget::File(dir.join(argument))
+    }
+
+    fn locate(
+        &self,
+        target: &IncludeTarget,
+        origin: &str,
+        line: usize,
+    ) -> Result<PathBuf, errors::FromPlantumlError> {
+        match target {
+            IncludeTarget::File(path) => {
+                if path.is_file() {
+                    Ok(path.clone())
+                } else {
+                    Err(errors::FromPlantumlError(format!(
+                        "included file `{}` is not found ({}:{})",
+                        path.display(),
+                        origin,
+                        line
+                    )))
+                }
+            }
+            IncludeTarget::Stdlib(name) => {
+                // stdlib names must stay inside the mirror
+                if !Path::new(name)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)))
+                {
+                    return Err(errors::FromPlantumlError(format!(
+                        "include `<{}>` is not a standard library name ({}:{})",
+                        name, origin, line
+                    )));
+                }
+
+                let mirror_dir = self.mirror_dir(&format!("<{}>", name), origin, line)?;
+                let mut path = mirror_dir.join(MIRROR_STDLIB).join(name);
+
+                if path.extension().is_none() {
+                    path.set_extension("puml");
+                }
+
+                if path.is_file() {
+                    Ok(path)
+                } else {
+                    Err(errors::FromPlantumlError(format!(
+                        "include `<{}>` is not mirrored: `{}` is not found ({}:{})",
+                        name,
+                        path.display(),
+                        origin,
+                        line
+                    )))
+                }
+            }
+            IncludeTarget::Url(url) => {
+                self.mirror_dir(url, origin, line)?;
+
+                match self.manifest.get(url) {
+                    Some(path) if path.is_file() => Ok(path.clone()),
+                    Some(path) => Err(errors::FromPlantumlError(format!(
+                        "include `{}` is not mirrored: `{}` is not found ({}:{})",
+                        url,
+                        path.display(),
+                        origin,
+                        line
+                    ))),
+                    None => Err(errors::FromPlantumlError(format!(
+                        "include `{}` is not mirrored: no entry in `{}` ({}:{})",
+                        url, MIRROR_MANIFEST, origin, line
+                    ))),
+                }
+            }
+        }
+    }
+
+    fn mirror_dir(
+        &self,
+        include: &str,
+        origin: &str,
+        line: usize,
+    ) -> Result<&Path, errors::FromPlantumlError> {
+        self.mirror_dir.as_deref().ok_or_else(|| {
+            errors::FromPlantumlError(format!(
+                "include `{}` is not mirrored: mirror directory is not configured ({}:{})",
+                include, origin, line
+            ))
+        })
+    }
+}
+
+#[derive(Default)]
+struct State {
+    stack: Vec<PathBuf>,
+    included: HashSet<PathBuf>,
+}
+
+fn parse_include(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim();
+    let (keyword, argument) = trimmed
+        .split_once(char::is_whitespace)
+        .unwrap_or((trimmed, ""));
+
+    match keyword {
+        "!include" | "!include_once" | "!include_many" | "!includeurl" => {
+            Some((keyword, argument.trim()))
+        }
+        _ => None,
+    }
+}
+
+/// Argument without the `!ID` suffix of the subpart and the subpart
+fn split_subpart(argument: &str) -> (&str, Option<&str>) {
+    match argument.rsplit_once('!') {
+        Some((file, id))
+            if !file.is_empty()
+                && !id.is_empty()
+                && id
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-') =>
+        {
+            (file, Some(id))
+        }
+        _ => (argument, None),
+    }
+}
+
+/// Key of the included file (and its subpart) for once-only and recursion checks
+fn include_key(path: &Path, subpart: Option<&str>) -> PathBuf {
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    match subpart {
+        Some(id) => PathBuf::from(format!("{}!{}", key.display(), id)),
+        None => key,
+    }
+}
+
+/// Body of the `@start*(id=ID)` block (or of the block with the index if `ID` is a number)
+fn select_subpart<'a>(content: &'a str, id: &str) -> Option<&'a str> {
+    let index = id.parse::<usize>().ok();
+    let mut blocks = 0;
+    let mut offset = 0;
+    let mut body_start = None;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+
+        match body_start {
+            None if trimmed.starts_with("@start") => {
+                let tag_id = trimmed
+                    .split_once("(id=")
+                    .and_then(|(_, rest)| rest.split_once(')'))
+                    .map(|(tag_id, _)| tag_id.trim());
+
+                if tag_id == Some(id) || index == Some(blocks) {
+                    body_start = Some(offset + line.len());
+                }
+
+                blocks += 1;
+            }
+            Some(start) if trimmed.starts_with("@end") => return Some(&content[start..offset]),
+            _ => {}
+        }
+
+        offset += line.len();
+    }
+
+    body_start.map(|start| &content[start..])
+}
+
+fn strip_start_end(content: &str) -> &str {
+    let start = content
+        .lines()
+        .position(|l| l.trim_start().starts_with("@start"));
+
+    let start = match start {
+        Some(s) => s,
+        None => return content,
+    };
+
+    let mut offset = 0;
+    let mut body_start = 0;
+    let mut body_end = content.len();
+
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        if index == start {
+            body_start = offset + line.len();
+        } else if index > start && line.trim_start().starts_with("@end") {
+            body_end = offset;
+            break;
+        }
+
+        offset += line.len();
+    }
+
+    &content[body_start.min(body_end)..body_end]
+}
+
+pub(crate) fn join_lines(lines: &[SourceLine]) -> String {
+    lines
+        .iter()
+        .map(|l| l.text.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::IncludeResolver;
+
+    use crate::errors;
+    use crate::tests::fs::temp_dir;
+
+    #[test]
+    fn it_resolve_without_includes() {
+        assert_eq!(
+            IncludeResolver::new(".").resolve("@startuml\nA -> B\n@enduml"),
+            Ok("@startuml\nA -> B\n@enduml".to_string())
+        );
+    }
+
+    #[test]
+    fn it_resolve_local_include() {
+        let dir = temp_dir("include_local");
+        fs::write(
+            dir.join("common.puml"),
+            "@startuml\nskinparam handwritten true\n@enduml",
+        )
+        .unwrap();
+
+        assert_eq!(
+            IncludeResolver::new(&dir).resolve("@startuml\n!include common.puml\nA -> B\n@enduml"),
+            Ok("@startuml\nskinparam handwritten true\nA -> B\n@enduml".to_string())
+        );
+    }
+
+    #[test]
+    fn it_resolve_include_once() {
+        let dir = temp_dir("include_once");
+        fs::write(dir.join("a.iuml"), "A -> B").unwrap();
+
+        let resolver = IncludeResolver::new(&dir);
+
+        assert_eq!(
+            resolver.resolve("!include a.iuml\n!include a.iuml"),
+            Ok("A -> B".to_string())
+        );
+        assert_eq!(
+            resolver.resolve("!include_many a.iuml\n!include_many a.iuml"),
+            Ok("A -> B\nA -> B".to_string())
+        );
+        assert_eq!(
+            resolver.resolve("!include a.iuml\n!include_once a.iuml"),
+            Err(errors::FromPlantumlError(
+                "`a.iuml` is already included (<input>:2)".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_resolve_include_subparts() {
+        let dir = temp_dir("include_subparts");
+        fs::write(
+            dir.join("parts.puml"),
+            "@startuml(id=FIRST)\nA -> B\n@enduml\n@startuml(id=SECOND)\nB -> C\n@enduml\n",
+        )
+        .unwrap();
+
+        let resolver = IncludeResolver::new(&dir);
+
+        assert_eq!(
+            resolver.resolve("!include parts.puml!SECOND\n!include parts.puml!0"),
+            Ok("B -> C\nA -> B".to_string())
+        );
+        assert_eq!(
+            resolver.resolve("!include parts.puml!THIRD"),
+            Err(errors::FromPlantumlError(format!(
+                "subpart `THIRD` is not found in `{}` (<input>:1)",
+                dir.join("parts.puml").display()
+            )))
+        );
+    }
+
+    #[test]
+    fn it_resolve_stdlib_outside_mirror_error() {
+        let dir = temp_dir("include_stdlib_escape");
+        fs::write(dir.join("secret.puml"), "SECRET").unwrap();
+
+        let resolver = IncludeResolver::new(&dir)
+            .with_mirror(dir.join("mirror"))
+            .unwrap();
+
+        assert_eq!(
+            resolver.resolve("!include <../secret>"),
+            Err(errors::FromPlantumlError(
+                "include `<../secret>` is not a standard library name (<input>:1)".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_resolve_stdlib_and_url_from_mirror() {
+        let dir = temp_dir("include_mirror");
+        fs::create_dir_all(dir.join("stdlib/C4")).unwrap();
+        fs::write(
+            dir.join("stdlib/C4/C4_Container.puml"),
+            "!include C4_Context.puml\nC4",
+        )
+        .unwrap();
+        fs::write(dir.join("stdlib/C4/C4_Context.puml"), "CONTEXT").unwrap();
+        fs::write(dir.join("theme.puml"), "THEME").unwrap();
+        fs::write(
+            dir.join("manifest.txt"),
+            "# mirrored urls\nhttps://example.com/theme.puml theme.puml\n",
+        )
+        .unwrap();
+
+        let resolver = IncludeResolver::new(&dir).with_mirror(&dir).unwrap();
+
+        assert_eq!(
+            resolver
+                .resolve("!include <C4/C4_Container>\n!includeurl https://example.com/theme.puml"),
+            Ok("CONTEXT\nC4\nTHEME".to_string())
+        );
+    }
+
+    #[test]
+    fn it_resolve_unmirrored_url_error() {
+        let dir = temp_dir("include_unmirrored");
+        let resolver = IncludeResolver::new(&dir).with_mirror(&dir).unwrap();
+
+        assert_eq!(
+            resolver.resolve("A -> B\n!includeurl https://example.com/missing.puml"),
+            Err(errors::FromPlantumlError(
+                "include `https://example.com/missing.puml` is not mirrored: no entry in `manifest.txt` (<input>:2)"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_resolve_without_mirror_error() {
+        assert_eq!(
+            IncludeResolver::new(".").resolve("!include <C4/C4_Container>"),
+            Err(errors::FromPlantumlError(
+                "include `<C4/C4_Container>` is not mirrored: mirror directory is not configured (<input>:1)"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_resolve_recursive_include_error() {
+        let dir = temp_dir("include_recursive");
+        fs::write(dir.join("a.puml"), "!include b.puml").unwrap();
+        fs::write(dir.join("b.puml"), "!include a.puml").unwrap();
+
+        assert!(IncludeResolver::new(&dir)
+            .resolve("!include a.puml")
+            .is_err());
+    }
+}
@@ -0,0 +1,364 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::errors;
+use crate::kind;
+use crate::render;
+use crate::url;
+
+/// Default delimiter printed by plantuml after every diagram of the pipe
+pub const PIPE_DELIMITER: &str = "~~~plantuml-encoding~~~";
+
+/// Renderer with a local plantuml command in pipe mode (`java -jar plantuml.jar -pipe`).
+///
+/// Several diagrams are rendered by one process with a delimiter between outputs,
+/// diagram errors are reported with their line numbers.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// use plantuml_encoding::{FromPlantumlError, JarRenderer, OutputFormat, Renderer};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let renderer = JarRenderer::new("/opt/plantuml/plantuml.jar");
+///
+///     let rendered = renderer.render("@startuml\nPUML -> RUST\n@enduml", OutputFormat::Svg)?;
+///     let all = renderer.render_all(&["A -> B", "B -> C"], OutputFormat::Png)?;
+///
+///     assert_eq!(all.len(), 2);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JarRenderer {
+    program: String,
+    args: Vec<String>,
+    delimiter: String,
+}
+
+impl JarRenderer {
+    /// Renderer with `java -jar <jar>`
+    pub fn new<P: AsRef<Path>>(jar: P) -> Self {
+        JarRenderer::command(
+            "java",
+            ["-jar".to_string(), jar.as_ref().display().to_string()],
+        )
+    }
+
+    /// Renderer with another command (e.g. `plantuml` script or java with options),
+    /// pipe options are appended to the arguments
+    pub fn command<P, I, S>(program: P, args: I) -> Self
+    where
+        P: AsRef<str>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        JarRenderer {
+            program: program.as_ref().to_string(),
+            args: args.into_iter().map(|a| a.as_ref().to_string()).collect(),
+            delimiter: PIPE_DELIMITER.to_string(),
+        }
+    }
+
+    /// Delimiter between outputs of diagrams (default is [`PIPE_DELIMITER`])
+    pub fn delimiter<T: AsRef<str>>(mut self, delimiter: T) -> Self {
+        self.delimiter = delimiter.as_ref().to_string();
+        self
+    }
+
+    /// Render sources (wrapped with [`crate::wrap_plantuml`] if needed) by one process,
+    /// results are in the order of the sources
+    pub fn render_all<T: AsRef<str>>(
+        &self,
+        sources: &[T],
+        format: url::OutputFormat,
+    ) -> Result<Vec<Result<render::Rendered, errors::FromPlantumlError>>, errors::FromPlantumlError>
+    {
+        let flag = match format {
+            url::OutputFormat::Png => "-tpng",
+            url::OutputFormat::Svg => "-tsvg",
+            url::OutputFormat::Txt => "-ttxt",
+            url::OutputFormat::Uml => {
+                return Err(errors::FromPlantumlError(
+                    "plantuml command can not render `uml` format".to_string(),
+                ))
+            }
+        };
+
+        let input: String = sources
+            .iter()
+            .map(|source| format!("{}\n", kind::wrap_plantuml(source.as_ref())))
+            .collect();
+
+        let command_error = |err: std::io::Error| {
+            errors::FromPlantumlError(format!(
+                "there is a problem during running `{}`: `{}`",
+                self.program, err
+            ))
+        };
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .args([
+                "-pipe",
+                flag,
+                "-pipeNoStderr",
+                "-pipedelimitor",
+                &self.delimiter,
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(command_error)?;
+
+        // stdin is written by another thread, so large outputs can not block the pipe
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| errors::FromPlantumlError(format!("`{}` has no stdin", self.program)))?;
+        let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+        let output = child.wait_with_output().map_err(command_error)?;
+
+        writer
+            .join()
+            .map_err(|_| errors::FromPlantumlError("stdin writer panicked".to_string()))?
+            .map_err(command_error)?;
+
+        let mut outputs = split_outputs(&output.stdout, &self.delimiter);
+
+        if outputs.len() != sources.len() {
+            return Err(errors::FromPlantumlError(format!(
+                "`{}` returned {} output(s) for {} diagram(s) ({}): {}",
+                self.program,
+                outputs.len(),
+                sources.len(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        if format != url::OutputFormat::Png {
+            for bytes in outputs.iter_mut() {
+                if bytes.last() == Some(&b'\n') {
+                    bytes.pop();
+                }
+            }
+        }
+
+        Ok(outputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, bytes)| match diagram_error(&bytes) {
+                Some(err) => Err(errors::FromPlantumlError(format!(
+                    "{} (diagram {})",
+                    err,
+                    index + 1
+                ))),
+                None => Ok(render::Rendered {
+                    format,
+                    content_type: render::content_type(format).to_string(),
+                    bytes,
+                }),
+            })
+            .collect())
+    }
+
+    /// Decode the encoded plantuml with [`crate::decode_plantuml`] and render it
+    pub fn render_encoded<T: AsRef<str>>(
+        &self,
+        encoded: T,
+        format: url::OutputFormat,
+    ) -> Result<render::Rendered, errors::FromPlantumlError> {
+        render::Renderer::render(self, &url::decode_plantuml(encoded)?, format)
+    }
+}
+
+impl render::Renderer for JarRenderer {
+    fn render(
+        &self,
+        plantuml: &str,
+        format: url::OutputFormat,
+    ) -> Result<render::Rendered, errors::FromPlantumlError> {
+        self.render_all(&[plantuml], format)?.remove(0)
+    }
+
+    fn identity(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(self.args.iter())
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+/// Outputs of diagrams, each of them is followed by the delimiter and a line break
+/// (right after the output, binary outputs do not end with a line break)
+fn split_outputs(stdout: &[u8], delimiter: &str) -> Vec<Vec<u8>> {
+    let delimiter = format!("{}\n", delimiter);
+    let delimiter = delimiter.as_bytes();
+    let mut outputs = vec![];
+    let mut start = 0;
+    let mut i = 0;
+
+    while i + delimiter.len() <= stdout.len() {
+        if stdout[i..].starts_with(delimiter) {
+            outputs.push(stdout[start..i].to_vec());
+            i += delimiter.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+
+    outputs
+}
+
+/// `ERROR`, line number and message printed instead of the diagram (`-pipeNoStderr`)
+fn diagram_error(bytes: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let mut lines = text.lines();
+
+    if lines.next()? != "ERROR" {
+        return None;
+    }
+
+    let line = lines.next().unwrap_or_default().trim();
+    let message = lines.collect::<Vec<&str>>().join(" ");
+
+    Some(match line.parse::<usize>() {
+        Ok(line) => format!("diagram error at line {}: {}", line, message.trim()),
+        Err(_) => format!("diagram error: {} {}", line, message)
+            .trim()
+            .to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diagram_error, split_outputs, JarRenderer};
+
+    use crate::url::OutputFormat;
+
+    #[test]
+    fn it_splits_outputs() {
+        assert_eq!(
+            split_outputs(b"<svg/>\n~~\n\x89PNG~~\n~~\n", "~~"),
+            vec![b"<svg/>\n".to_vec(), b"\x89PNG".to_vec(), vec![]]
+        );
+    }
+
+    #[test]
+    fn it_parses_diagram_errors() {
+        assert_eq!(
+            diagram_error(b"ERROR\n2\nSyntax Error?\n"),
+            Some("diagram error at line 2: Syntax Error?".to_string())
+        );
+        assert_eq!(diagram_error(b"<svg/>"), None);
+    }
+
+    #[test]
+    fn it_rejects_uml_format() {
+        assert!(JarRenderer::new("plantuml.jar")
+            .render_all(&["A -> B"], OutputFormat::Uml)
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_renders_with_stub_command() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::errors;
+        use crate::render::Renderer;
+        use crate::tests::constants::plantuml_deflated_str::PLANTUML_DEFLATED_SMALL;
+        use crate::tests::fs::temp_dir;
+
+        // stub of `plantuml -pipe`: prints the format flag and lines of every diagram
+        // (or binary PNG without a line break, as plantuml does)
+        let script = "#!/bin/sh
+delimiter=''
+format=''
+previous=''
+for arg in \"$@\"; do
+    case \"$arg\" in -t*) format=\"$arg\" ;; esac
+    if [ \"$previous\" = '-pipedelimitor' ]; then delimiter=\"$arg\"; fi
+    previous=\"$arg\"
+done
+body=''
+while IFS= read -r line; do
+    case \"$line\" in
+        @start*) ;;
+        @end*)
+            case \"$body\" in
+                *BROKEN*) printf 'ERROR\\n2\\nSyntax Error?\\n' ;;
+                *)
+                    if [ \"$format\" = '-tpng' ]; then
+                        printf '\\211PNG %s' \"$body\"
+                    else
+                        printf '%s %s\\n' \"$format\" \"$body\"
+                    fi ;;
+            esac
+            printf '%s\\n' \"$delimiter\"
+            body='' ;;
+        *) body=\"$body$line;\" ;;
+    esac
+done
+";
+        let dir = temp_dir("jar_stub");
+        let stub = dir.join("plantuml");
+
+        fs::write(&stub, script).unwrap();
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let renderer = JarRenderer::command(stub.display().to_string(), ["-charset", "UTF-8"]);
+        let rendered = renderer
+            .render_all(
+                &["A -> B", "BROKEN ->", "@startuml\nC -> D\n@enduml"],
+                OutputFormat::Svg,
+            )
+            .unwrap();
+
+        assert_eq!(rendered.len(), 3);
+        assert_eq!(rendered[0].as_ref().unwrap().bytes, b"-tsvg A -> B;");
+        assert_eq!(
+            rendered[1],
+            Err(errors::FromPlantumlError(
+                "diagram error at line 2: Syntax Error? (diagram 2)".to_string()
+            ))
+        );
+        assert_eq!(rendered[2].as_ref().unwrap().content_type, "image/svg+xml");
+        assert_eq!(
+            renderer
+                .render_encoded(PLANTUML_DEFLATED_SMALL, OutputFormat::Txt)
+                .map(|r| r.bytes),
+            Ok(b"-ttxt PUML -> RUST: HELLO ;".to_vec())
+        );
+        assert_eq!(
+            renderer
+                .render_all(&["A -> B", "B -> C"], OutputFormat::Png)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.map(|r| r.bytes))
+                .collect::<Vec<_>>(),
+            vec![
+                Ok(b"\x89PNG A -> B;".to_vec()),
+                Ok(b"\x89PNG B -> C;".to_vec())
+            ]
+        );
+        assert_eq!(
+            renderer.identity(),
+            format!("{} -charset UTF-8", stub.display())
+        );
+        assert!(
+            JarRenderer::command(dir.join("missing").display().to_string(), [""; 0])
+                .render("A -> B", OutputFormat::Svg)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::deflate;
+use crate::errors;
+use crate::kind::DiagramKind;
+use crate::url;
+
+/// [JSON](https://plantuml.com/json) or [YAML](https://plantuml.com/yaml) diagram
+/// of any `serde::Serialize` value (requires `json` feature)
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{DataDiagram, FromPlantumlError};
+///
+/// #[derive(serde::Serialize)]
+/// struct Config {
+///     name: &'static str,
+///     replicas: u8,
+/// }
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let diagram = DataDiagram::yaml(&Config { name: "api", replicas: 2 })?
+///         .highlight(["replicas"]);
+///
+///     assert_eq!(
+///         diagram.plantuml(),
+///         "@startyaml\n#highlight \"replicas\"\nname: api\nreplicas: 2\n@endyaml"
+///     );
+///
+///     let encoded_deflate = diagram.encode_deflate()?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDiagram {
+    kind: DiagramKind,
+    body: String,
+    highlights: Vec<Vec<String>>,
+    style: Option<String>,
+}
+
+impl DataDiagram {
+    /// `@startjson` diagram of the value
+    pub fn json<S: Serialize + ?Sized>(value: &S) -> Result<Self, errors::FromPlantumlError> {
+        Ok(DataDiagram::new(
+            DiagramKind::Json,
+            serde_json::to_string_pretty(value)?,
+        ))
+    }
+
+    /// `@startyaml` diagram of the value
+    pub fn yaml<S: Serialize + ?Sized>(value: &S) -> Result<Self, errors::FromPlantumlError> {
+        let mut lines = vec![];
+
+        yaml_lines(&serde_json::to_value(value)?, 0, &mut lines);
+
+        Ok(DataDiagram::new(DiagramKind::Yaml, lines.join("\n")))
+    }
+
+    fn new(kind: DiagramKind, body: String) -> Self {
+        DataDiagram {
+            kind,
+            body,
+            highlights: vec![],
+            style: None,
+        }
+    }
+
+    /// Highlight the path of keys (array indices as strings) with `#highlight`
+    pub fn highlight<I, T>(mut self, path: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.highlights
+            .push(path.into_iter().map(|k| k.as_ref().to_string()).collect());
+        self
+    }
+
+    /// Add `<style>` block content (for example `jsonDiagram { node { BackGroundColor Khaki } }`)
+    pub fn style<T: AsRef<str>>(mut self, style: T) -> Self {
+        self.style = Some(style.as_ref().to_string());
+        self
+    }
+
+    /// Plantuml of the diagram
+    pub fn plantuml(&self) -> String {
+        let mut lines = vec![format!("@start{}", self.kind.tag())];
+
+        if let Some(style) = &self.style {
+            lines.push(format!("<style>\n{}\n</style>", style.trim()));
+        }
+
+        for path in self.highlights.iter() {
+            let path = path
+                .iter()
+                .map(|k| format!("\"{}\"", k.replace('"', "\\\"")))
+                .collect::<Vec<String>>()
+                .join(" / ");
+
+            lines.push(format!("#highlight {}", path));
+        }
+
+        lines.push(self.body.clone());
+        lines.push(format!("@end{}", self.kind.tag()));
+
+        lines.join("\n")
+    }
+
+    /// Encode the diagram with deflate compression
+    pub fn encode_deflate(&self) -> Result<String, errors::FromPlantumlError> {
+        deflate::encode_plantuml_deflate(self.plantuml())
+    }
+
+    /// Server URL of the diagram
+    pub fn url<S: AsRef<str>>(
+        &self,
+        server: S,
+        format: url::OutputFormat,
+    ) -> Result<String, errors::FromPlantumlError> {
+        Ok(url::plantuml_url(server, format, self.encode_deflate()?))
+    }
+}
+
+/// Render the value as `@startjson` diagram and encode it with deflate compression
+/// (requires `json` feature)
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{decode_plantuml_deflate, encode_plantuml_json, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let encoded_deflate = encode_plantuml_json(&vec![1, 2])?;
+///
+///     assert_eq!(
+///         decode_plantuml_deflate(encoded_deflate)?,
+///         "@startjson\n[\n  1,\n  2\n]\n@endjson"
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+pub fn encode_plantuml_json<S: Serialize + ?Sized>(
+    value: &S,
+) -> Result<String, errors::FromPlantumlError> {
+    DataDiagram::json(value)?.encode_deflate()
+}
+
+/// Render the value as `@startyaml` diagram and encode it with deflate compression
+/// (requires `json` feature)
+pub fn encode_plantuml_yaml<S: Serialize + ?Sized>(
+    value: &S,
+) -> Result<String, errors::FromPlantumlError> {
+    DataDiagram::yaml(value)?.encode_deflate()
+}
+
+fn yaml_lines(value: &Value, indent: usize, lines: &mut Vec<String>) {
+    let padding = " ".repeat(indent);
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, item) in map.iter() {
+                match item {
+                    Value::Object(m) if !m.is_empty() => {
+                        lines.push(format!("{}{}:", padding, yaml_scalar(key)));
+                        yaml_lines(item, indent + 2, lines);
+                    }
+                    Value::Array(a) if !a.is_empty() => {
+                        lines.push(format!("{}{}:", padding, yaml_scalar(key)));
+                        yaml_lines(item, indent + 2, lines);
+                    }
+                    _ => lines.push(format!(
+                        "{}{}: {}",
+                        padding,
+                        yaml_scalar(key),
+                        yaml_value(item)
+                    )),
+                }
+            }
+        }
+        Value::Array(array) if !array.is_empty() => {
+            for item in array.iter() {
+                let start = lines.len();
+
+                yaml_lines(item, indent + 2, lines);
+
+                if let Some(first) = lines.get_mut(start) {
+                    *first = format!("{}- {}", padding, &first[indent + 2..]);
+                }
+            }
+        }
+        _ => lines.push(format!("{}{}", padding, yaml_value(value))),
+    }
+}
+
+fn yaml_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => yaml_scalar(s),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+    }
+}
+
+fn yaml_scalar(s: &str) -> String {
+    let is_plain = !s.is_empty()
+        && s.trim() == s
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.ends_with(':')
+        && !s.contains(['\n', '"', '\''])
+        && !s.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '%', '@', '`',
+        ])
+        && !matches!(
+            s.to_lowercase().as_str(),
+            "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "~"
+        )
+        && s.parse::<f64>().is_err();
+
+    if is_plain {
+        s.to_string()
+    } else {
+        Value::String(s.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::{encode_plantuml_json, DataDiagram};
+
+    use crate::deflate::decode_plantuml_deflate;
+    use crate::errors;
+
+    #[derive(Serialize)]
+    struct Service {
+        name: String,
+        ports: Vec<u16>,
+        env: Vec<(String, String)>,
+        limits: Limits,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Limits {
+        cpu: f32,
+        memory: Option<String>,
+    }
+
+    fn service() -> Service {
+        Service {
+            name: "api: v2".to_string(),
+            ports: vec![80, 443],
+            env: vec![("MODE".to_string(), "true".to_string())],
+            limits: Limits {
+                cpu: 0.5,
+                memory: None,
+            },
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn it_data_diagram_json() {
+        assert_eq!(
+            DataDiagram::json(&service().limits)
+                .unwrap()
+                .highlight(["cpu"])
+                .style("jsonDiagram {\n  node { BackGroundColor Khaki }\n}")
+                .plantuml(),
+            "@startjson\n<style>\njsonDiagram {\n  node { BackGroundColor Khaki }\n}\n</style>\n#highlight \"cpu\"\n{\n  \"cpu\": 0.5,\n  \"memory\": null\n}\n@endjson"
+        );
+    }
+
+    #[test]
+    fn it_data_diagram_yaml() {
+        assert_eq!(
+            DataDiagram::yaml(&service())
+                .unwrap()
+                .highlight(["ports", "1"])
+                .plantuml(),
+            "@startyaml\n#highlight \"ports\" / \"1\"\nname: \"api: v2\"\nports:\n  - 80\n  - 443\nenv:\n  - - MODE\n    - \"true\"\nlimits:\n  cpu: 0.5\n  memory: null\ntags: []\n@endyaml"
+        );
+    }
+
+    #[test]
+    fn it_encode_plantuml_json() {
+        assert_eq!(
+            encode_plantuml_json(&serde_json::json!({"a": 1})).and_then(decode_plantuml_deflate),
+            Ok("@startjson\n{\n  \"a\": 1\n}\n@endjson".to_string())
+        );
+    }
+
+    #[test]
+    fn it_data_diagram_serialize_error() {
+        let mut map = std::collections::HashMap::new();
+        map.insert((1, 2), 3);
+
+        assert_eq!(
+            DataDiagram::json(&map),
+            Err(errors::FromPlantumlError(
+                "there is a problem during json serialization: `key must be a string`".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use crate::deflate;
+use crate::errors;
+
+/// Diagram type determined by the `@start...` / `@end...` tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagramKind {
+    /// `@startuml` (sequence, class, activity, component and other UML diagrams)
+    Uml,
+    /// `@startjson`
+    Json,
+    /// `@startyaml`
+    Yaml,
+    /// `@startdot` (graphviz)
+    Dot,
+    /// `@startmindmap`
+    Mindmap,
+    /// `@startwbs`
+    Wbs,
+    /// `@startgantt`
+    Gantt,
+    /// `@startsalt`
+    Salt,
+    /// `@startditaa`
+    Ditaa,
+    /// `@startmath`
+    Math,
+    /// `@startlatex`
+    Latex,
+    /// `@startcreole`
+    Creole,
+    /// `@startregex`
+    Regex,
+    /// `@startebnf`
+    Ebnf,
+    /// `@startchronology`
+    Chronology,
+    /// `@startfiles`
+    Files,
+    /// `@startchen`
+    Chen,
+    /// `@startwire`
+    Wire,
+    /// `@startboard`
+    Board,
+}
+
+const KINDS: [DiagramKind; 19] = [
+    DiagramKind::Uml,
+    DiagramKind::Json,
+    DiagramKind::Yaml,
+    DiagramKind::Dot,
+    DiagramKind::Mindmap,
+    DiagramKind::Wbs,
+    DiagramKind::Gantt,
+    DiagramKind::Salt,
+    DiagramKind::Ditaa,
+    DiagramKind::Math,
+    DiagramKind::Latex,
+    DiagramKind::Creole,
+    DiagramKind::Regex,
+    DiagramKind::Ebnf,
+    DiagramKind::Chronology,
+    DiagramKind::Files,
+    DiagramKind::Chen,
+    DiagramKind::Wire,
+    DiagramKind::Board,
+];
+
+impl DiagramKind {
+    /// Tag of the kind (`uml` for `@startuml` and so on)
+    pub fn tag(&self) -> &'static str {
+        match self {
+            DiagramKind::Uml => "uml",
+            DiagramKind::Json => "json",
+            DiagramKind::Yaml => "yaml",
+            DiagramKind::Dot => "dot",
+            DiagramKind::Mindmap => "mindmap",
+            DiagramKind::Wbs => "wbs",
+            DiagramKind::Gantt => "gantt",
+            DiagramKind::Salt => "salt",
+            DiagramKind::Ditaa => "ditaa",
+            DiagramKind::Math => "math",
+            DiagramKind::Latex => "latex",
+            DiagramKind::Creole => "creole",
+            DiagramKind::Regex => "regex",
+            DiagramKind::Ebnf => "ebnf",
+            DiagramKind::Chronology => "chronology",
+            DiagramKind::Files => "files",
+            DiagramKind::Chen => "chen",
+            DiagramKind::Wire => "wire",
+            DiagramKind::Board => "board",
+        }
+    }
+
+    /// Kind by the tag (`uml`, `mindmap` and so on, case insensitive)
+    pub fn from_tag<T: AsRef<str>>(tag: T) -> Option<DiagramKind> {
+        let tag = tag.as_ref().to_lowercase();
+
+        KINDS.iter().copied().find(|kind| kind.tag() == tag)
+    }
+
+    /// Kind of the plantuml by its start tag
+    /// or with heuristics of the body if the plantuml is not wrapped
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use plantuml_encoding::DiagramKind;
+    ///
+    /// assert_eq!(DiagramKind::detect("@startmindmap\n* root\n@endmindmap"), DiagramKind::Mindmap);
+    /// assert_eq!(DiagramKind::detect("{\"name\": \"plantuml\"}"), DiagramKind::Json);
+    /// assert_eq!(DiagramKind::detect("digraph G { a -> b }"), DiagramKind::Dot);
+    /// assert_eq!(DiagramKind::detect("PUML -> RUST"), DiagramKind::Uml);
+    /// ```
+    pub fn detect<T: AsRef<str>>(plantuml: T) -> DiagramKind {
+        let plantuml = plantuml.as_ref();
+
+        match start_tag(plantuml) {
+            Some(tag) => DiagramKind::from_tag(tag).unwrap_or(DiagramKind::Uml),
+            None => detect_body(plantuml),
+        }
+    }
+
+    /// Wrap body with `@start...` and `@end...` tags of the kind
+    pub fn wrap<T: AsRef<str>>(&self, body: T) -> String {
+        format!(
+            "@start{}\n{}\n@end{}",
+            self.tag(),
+            body.as_ref().trim_end_matches('\n'),
+            self.tag()
+        )
+    }
+}
+
+/// Whether the plantuml starts with `@start...` tag
+/// (comments, blank lines and preprocessor lines like `!include` are skipped)
+pub fn is_wrapped_plantuml<T: AsRef<str>>(plantuml: T) -> bool {
+    start_tag(plantuml.as_ref()).is_some()
+}
+
+/// Wrap plantuml with start and end tags of the detected [`DiagramKind`]
+/// if it is not wrapped yet
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::wrap_plantuml;
+///
+/// assert_eq!(wrap_plantuml("* root\n** child"), "@startmindmap\n* root\n** child\n@endmindmap");
+/// assert_eq!(wrap_plantuml("@startuml\nA -> B\n@enduml"), "@startuml\nA -> B\n@enduml");
+/// ```
+pub fn wrap_plantuml<T: AsRef<str>>(plantuml: T) -> String {
+    let plantuml = plantuml.as_ref();
+
+    if is_wrapped_plantuml(plantuml) {
+        plantuml.to_string()
+    } else {
+        DiagramKind::detect(plantuml).wrap(plantuml)
+    }
+}
+
+/// Wrap plantuml with [`wrap_plantuml`] and encode it with deflate compression
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{encode_plantuml_deflate_wrapped, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let encoded_deflate = encode_plantuml_deflate_wrapped("PUML -> RUST")?;
+///
+///     assert_eq!(encoded_deflate, "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn encode_plantuml_deflate_wrapped<T: AsRef<str>>(
+    plantuml: T,
+) -> Result<String, errors::FromPlantumlError> {
+    deflate::encode_plantuml_deflate(wrap_plantuml(plantuml))
+}
+
+fn start_tag(plantuml: &str) -> Option<&str> {
+    let line = significant_lines(plantuml).next()?;
+    let tag = line.strip_prefix("@start")?;
+    let end = tag
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(tag.len());
+
+    Some(&tag[..end]).filter(|t| !t.is_empty())
+}
+
+fn significant_lines(plantuml: &str) -> impl Iterator<Item = &str> {
+    plantuml
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('\'') && !l.starts_with('!'))
+}
+
+fn detect_body(body: &str) -> DiagramKind {
+    let first = match significant_lines(body).next() {
+        Some(first) => first,
+        None => return DiagramKind::Uml,
+    };
+
+    let text = significant_lines(body).collect::<Vec<&str>>().join("\n");
+
+    if is_json(&text) {
+        return DiagramKind::Json;
+    }
+
+    if first.starts_with('{') && body.contains('|') {
+        return DiagramKind::Salt;
+    }
+
+    let first_word = first.split_whitespace().next().unwrap_or_default();
+
+    if matches!(first_word, "digraph" | "graph" | "strict") {
+        return DiagramKind::Dot;
+    }
+
+    let lines: Vec<&str> = significant_lines(body).collect();
+
+    if lines.iter().all(|l| is_tree_line(l, '*')) {
+        return if lines
+            .iter()
+            .any(|l| l.trim_start_matches('*').starts_with(['<', '>']))
+        {
+            DiagramKind::Wbs
+        } else {
+            DiagramKind::Mindmap
+        };
+    }
+
+    if lines.iter().any(|l| is_tree_line(l, '+'))
+        && lines
+            .iter()
+            .all(|l| is_tree_line(l, '+') || is_tree_line(l, '-'))
+    {
+        return DiagramKind::Mindmap;
+    }
+
+    if lines.iter().any(|l| {
+        l.starts_with("project starts")
+            || l.starts_with('[') && (l.contains("] lasts") || l.contains("] starts"))
+    }) {
+        return DiagramKind::Gantt;
+    }
+
+    if first == "---" || lines.iter().all(|l| is_yaml_line(l)) {
+        return DiagramKind::Yaml;
+    }
+
+    DiagramKind::Uml
+}
+
+/// Whether the text is a JSON object or array
+fn is_json(text: &str) -> bool {
+    let mut json = Json {
+        bytes: text.as_bytes(),
+        position: 0,
+        depth: 0,
+    };
+
+    if !text.trim_start().starts_with(['{', '[']) || !json.value() {
+        return false;
+    }
+
+    json.whitespace();
+    json.position == json.bytes.len()
+}
+
+/// Syntax checker of JSON values
+struct Json<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl Json<'_> {
+    /// Deeper documents are not detected instead of overflowing the stack
+    const MAX_DEPTH: usize = 128;
+
+    fn value(&mut self) -> bool {
+        self.whitespace();
+
+        match self.bytes.get(self.position) {
+            Some(b'{') => self.container(b'}', true),
+            Some(b'[') => self.container(b']', false),
+            Some(b'"') => self.string(),
+            Some(b't') => self.literal("true"),
+            Some(b'f') => self.literal("false"),
+            Some(b'n') => self.literal("null"),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => false,
+        }
+    }
+
+    fn container(&mut self, close: u8, object: bool) -> bool {
+        if self.depth == Self::MAX_DEPTH {
+            return false;
+        }
+
+        self.depth += 1;
+        self.position += 1;
+        self.whitespace();
+
+        if self.bytes.get(self.position) == Some(&close) {
+            self.position += 1;
+            self.depth -= 1;
+            return true;
+        }
+
+        loop {
+            if object {
+                self.whitespace();
+
+                if !self.string() || !self.byte(b':') {
+                    return false;
+                }
+            }
+
+            if !self.value() {
+                return false;
+            }
+
+            if self.byte(close) {
+                self.depth -= 1;
+                return true;
+            }
+
+            if !self.byte(b',') {
+                return false;
+            }
+        }
+    }
+
+    fn string(&mut self) -> bool {
+        if self.bytes.get(self.position) != Some(&b'"') {
+            return false;
+        }
+
+        self.position += 1;
+
+        while let Some(byte) = self.bytes.get(self.position) {
+            self.position += 1;
+
+            match byte {
+                b'"' => return true,
+                b'\\' => self.position += 1,
+                b'\n' => return false,
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    fn number(&mut self) -> bool {
+        let start = self.position;
+
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'))
+        {
+            self.position += 1;
+        }
+
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .is_ok_and(|number| number.parse::<f64>().is_ok())
+    }
+
+    fn literal(&mut self, literal: &str) -> bool {
+        let matches = self.bytes[self.position..].starts_with(literal.as_bytes());
+
+        if matches {
+            self.position += literal.len();
+        }
+
+        matches
+    }
+
+    /// Skip whitespace and consume the byte if it is next
+    fn byte(&mut self, byte: u8) -> bool {
+        self.whitespace();
+
+        let matches = self.bytes.get(self.position) == Some(&byte);
+
+        if matches {
+            self.position += 1;
+        }
+
+        matches
+    }
+
+    fn whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+}
+
+fn is_tree_line(line: &str, marker: char) -> bool {
+    let rest = line.trim_start_matches(marker);
+
+    rest.len() < line.len() && rest.starts_with([' ', ':', '[', '_', '<', '>'])
+}
+
+fn is_yaml_line(line: &str) -> bool {
+    if line == "---" || line.starts_with("- ") || line == "-" {
+        return true;
+    }
+
+    match line.split_once(':') {
+        Some((key, value)) => {
+            let key = key.trim_matches(|c| c == '"' || c == '\'');
+
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                && (value.is_empty() || value.starts_with(' '))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_plantuml_deflate_wrapped, wrap_plantuml, DiagramKind};
+
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_str::PLANTUML_SMALL,
+    };
+
+    #[test]
+    fn it_detect_by_start_tag() {
+        assert_eq!(
+            DiagramKind::detect("' comment\n@startgantt\n@endgantt"),
+            DiagramKind::Gantt
+        );
+        assert_eq!(DiagramKind::detect("@startfoo\n@endfoo"), DiagramKind::Uml);
+        assert_eq!(DiagramKind::from_tag("WBS"), Some(DiagramKind::Wbs));
+        assert_eq!(DiagramKind::from_tag("foo"), None);
+    }
+
+    #[test]
+    fn it_detect_by_body() {
+        assert_eq!(DiagramKind::detect("[1, 2, 3]"), DiagramKind::Json);
+        assert_eq!(
+            DiagramKind::detect("{\n  Login | \"MyName\"\n}"),
+            DiagramKind::Salt
+        );
+        assert_eq!(
+            DiagramKind::detect("root:\n  child: 1\n  list:\n    - a"),
+            DiagramKind::Yaml
+        );
+        assert_eq!(DiagramKind::detect("- a\n- b"), DiagramKind::Yaml);
+        assert_eq!(DiagramKind::detect("* root\n**> right"), DiagramKind::Wbs);
+        assert_eq!(DiagramKind::detect("+ root\n-- left"), DiagramKind::Mindmap);
+        assert_eq!(
+            DiagramKind::detect("[Design] lasts 5 days"),
+            DiagramKind::Gantt
+        );
+        assert_eq!(DiagramKind::detect("class A\nA : +field"), DiagramKind::Uml);
+        assert_eq!(DiagramKind::detect("[A]->[B]"), DiagramKind::Uml);
+        assert_eq!(
+            DiagramKind::detect("[A] --> [B]\n[B] ..> [C]"),
+            DiagramKind::Uml
+        );
+        assert_eq!(
+            DiagramKind::detect("{\n  \"a\": [1, -2.5e3, true, null, {\"b\": \"c\\\"\"}]\n}"),
+            DiagramKind::Json
+        );
+        assert_eq!(
+            DiagramKind::detect("!theme plain\n* root"),
+            DiagramKind::Mindmap
+        );
+    }
+
+    #[test]
+    fn it_wrap_plantuml() {
+        assert_eq!(
+            wrap_plantuml("{\"a\": 1}\n"),
+            "@startjson\n{\"a\": 1}\n@endjson"
+        );
+        assert_eq!(wrap_plantuml(PLANTUML_SMALL), PLANTUML_SMALL);
+        assert_eq!(
+            wrap_plantuml("!include common.puml\n@startuml\nA -> B\n@enduml"),
+            "!include common.puml\n@startuml\nA -> B\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_encode_plantuml_deflate_wrapped_small() {
+        assert_eq!(
+            encode_plantuml_deflate_wrapped(PLANTUML_SMALL),
+            Ok(PLANTUML_DEFLATED_SMALL.to_string())
+        );
+        assert_eq!(
+            encode_plantuml_deflate_wrapped("PUML -> RUST: HELLO "),
+            Ok(PLANTUML_DEFLATED_SMALL.to_string())
+        );
+    }
+}
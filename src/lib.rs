@@ -146,12 +146,92 @@
 //!
 //! Also, you can consider tests inside the files.
 
+pub mod build;
+mod cache;
+mod check;
+mod class;
+#[cfg(feature = "client")]
+mod client;
 mod deflate;
 mod errors;
+mod format;
 mod hex;
+mod include;
+mod jar;
+#[cfg(feature = "json")]
+mod json;
+mod kind;
+#[cfg(feature = "markdown")]
+mod markdown;
+mod minify;
+mod model;
+mod normalize;
+mod preprocessor;
+mod render;
+mod scan;
+mod semantic;
+mod sequence;
+mod split;
+mod sync;
 mod tests;
+mod textconv;
+mod url;
 mod utils;
+mod validate;
 
+pub use crate::cache::{CachedRenderer, DEFAULT_CACHE_MAX_BYTES};
+pub use crate::check::{
+    check_plantuml, check_plantuml_files, check_plantuml_found, CheckProblem, CheckRule,
+    CheckedFile, CheckedLink,
+};
+pub use crate::class::{ClassDiagram, Element, ElementKind, Relation, RelationKind};
+#[cfg(feature = "client")]
+pub use crate::client::{HttpRenderer, HttpRequest};
 pub use crate::deflate::{decode_plantuml_deflate, encode_plantuml_deflate};
 pub use crate::errors::FromPlantumlError;
+pub use crate::format::{
+    encode_plantuml_deflate_formatted, format_plantuml, format_plantuml_files, FormattedFile,
+    FORMAT_EXTENSIONS,
+};
 pub use crate::hex::{decode_plantuml_hex, encode_plantuml_hex};
+pub use crate::include::{IncludeResolver, MIRROR_MANIFEST, MIRROR_STDLIB};
+pub use crate::jar::{JarRenderer, PIPE_DELIMITER};
+#[cfg(feature = "json")]
+pub use crate::json::{encode_plantuml_json, encode_plantuml_yaml, DataDiagram};
+pub use crate::kind::{
+    encode_plantuml_deflate_wrapped, is_wrapped_plantuml, wrap_plantuml, DiagramKind,
+};
+#[cfg(feature = "markdown")]
+pub use crate::markdown::{
+    markdown_fenced_blocks, markdown_to_fences, markdown_to_links, FencedBlock,
+};
+pub use crate::minify::{minify_plantuml, minify_plantuml_report, MinifyReport};
+pub use crate::model::{ClassReference, ClassRegistry, PlantUmlClass};
+pub use crate::normalize::{encode_plantuml_deflate_normalized, normalize_plantuml, plantuml_hash};
+#[cfg(feature = "nfc")]
+pub use crate::normalize::{
+    encode_plantuml_deflate_normalized_nfc, normalize_plantuml_nfc, plantuml_hash_nfc,
+};
+pub use crate::preprocessor::{preprocess_plantuml, Preprocessed, Preprocessor};
+pub use crate::render::{content_type, Rendered, Renderer};
+pub use crate::scan::{scan_plantuml, scan_plantuml_files, Found, FoundKind, ScannedFile};
+pub use crate::semantic::{
+    diff_plantuml, diff_plantuml_sources, Change, ChangeKind, DiagramDiff, ItemKind,
+};
+pub use crate::sequence::{
+    Arrow, GroupKind, Message, NotePosition, Participant, ParticipantKind, SequenceDiagram,
+};
+pub use crate::split::{
+    encode_plantuml_deflate_split, plantuml_urls_split, split_plantuml, Diagram,
+};
+pub use crate::sync::{
+    sync_plantuml_files, sync_plantuml_links, StaleLink, Synced, SyncedFile, SYNC_EXTENSIONS,
+};
+pub use crate::textconv::{textconv_diff_plantuml, textconv_plantuml};
+pub use crate::url::{
+    decode_plantuml, plantuml_url, OutputFormat, PlantumlUrl, DEFAULT_MAX_URL_LENGTH,
+    PLANTUML_SERVER,
+};
+pub use crate::validate::{
+    encode_plantuml_deflate_checked, validate_plantuml, Diagnostic, Severity,
+};
@@ -0,0 +1,340 @@
+use std::ops::Range;
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+use crate::errors;
+use crate::kind;
+use crate::url;
+
+const LANGUAGES: [&str; 2] = ["plantuml", "puml"];
+
+/// Replace fenced `plantuml` / `puml` code blocks of the markdown with
+/// `![plantuml](<server url>)` image links (requires `markdown` feature).
+///
+/// Bodies without `@start...` tag are wrapped with [`crate::wrap_plantuml`],
+/// everything outside of the replaced blocks is kept byte-for-byte.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{markdown_to_links, FromPlantumlError, OutputFormat, PLANTUML_SERVER};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let markdown = "# Flow\n\n```plantuml\n@startuml\nPUML -> RUST\n@enduml\n```\n";
+///
+///     assert_eq!(
+///         markdown_to_links(markdown, PLANTUML_SERVER, OutputFormat::Svg)?,
+///         "# Flow\n\n![plantuml](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)\n"
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+pub fn markdown_to_links<T: AsRef<str>, S: AsRef<str>>(
+    markdown: T,
+    server: S,
+    format: url::OutputFormat,
+) -> Result<String, errors::FromPlantumlError> {
+    let markdown = markdown.as_ref();
+    let mut replacements = vec![];
+
+    for block in markdown_fenced_blocks(markdown) {
+        if !block.is_plantuml() {
+            continue;
+        }
+
+        let encoded =
+            kind::encode_plantuml_deflate_wrapped(block.body.trim_end_matches(['\r', '\n']))?;
+        let image = format!(
+            "![plantuml]({})",
+            url::plantuml_url(server.as_ref(), format, encoded)
+        );
+
+        replacements.push((block.range, image));
+    }
+
+    Ok(replace(markdown, replacements))
+}
+
+/// Fenced code block of a markdown (requires `markdown` feature)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FencedBlock {
+    /// Byte range of the block from the opening fence to the closing one
+    /// (without the line ending after it)
+    pub range: Range<usize>,
+    /// Info string after the opening fence
+    pub info: String,
+    /// Content of the block (without the indentation and quote markers)
+    pub body: String,
+}
+
+impl FencedBlock {
+    /// Whether the language of the block is `plantuml` or `puml`
+    pub fn is_plantuml(&self) -> bool {
+        is_plantuml(&self.info)
+    }
+}
+
+/// Fenced code blocks of the markdown, including ones nested in lists and quotes
+/// (requires `markdown` feature).
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::markdown_fenced_blocks;
+///
+/// let blocks = markdown_fenced_blocks("# Flow\n\n```puml\nPUML -> RUST\n```\n");
+///
+/// assert_eq!(blocks.len(), 1);
+/// assert!(blocks[0].is_plantuml());
+/// assert_eq!(blocks[0].range, 8..32);
+/// assert_eq!(blocks[0].body, "PUML -> RUST\n");
+/// ```
+pub fn markdown_fenced_blocks<T: AsRef<str>>(markdown: T) -> Vec<FencedBlock> {
+    let markdown = markdown.as_ref();
+    let mut blocks = vec![];
+    let mut block: Option<FencedBlock> = None;
+
+    for (event, range) in parser(markdown).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                block = Some(FencedBlock {
+                    range: without_line_ending(markdown, range),
+                    info: info.to_string(),
+                    body: String::new(),
+                });
+            }
+            Event::Text(text) => {
+                if let Some(block) = block.as_mut() {
+                    block.body.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => blocks.extend(block.take()),
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+/// Replace image links to a plantuml server (standing alone on their lines)
+/// with fenced `plantuml` code blocks of the decoded source (requires `markdown` feature).
+///
+/// Links are recognized with [`crate::PlantumlUrl::parse`] and decoded with
+/// [`crate::decode_plantuml`], links whose payload does not decode and everything
+/// outside of the replaced links are kept byte-for-byte.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{markdown_to_fences, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let markdown = "# Flow\n\n![flow](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)\n";
+///
+///     assert_eq!(
+///         markdown_to_fences(markdown)?,
+///         "# Flow\n\n```plantuml\n@startuml\nPUML -> RUST\n@enduml\n```\n"
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+pub fn markdown_to_fences<T: AsRef<str>>(markdown: T) -> Result<String, errors::FromPlantumlError> {
+    let markdown = markdown.as_ref();
+    let mut replacements = vec![];
+
+    for (event, range) in parser(markdown).into_offset_iter() {
+        let dest_url = match event {
+            Event::Start(Tag::Image { dest_url, .. }) => dest_url,
+            _ => continue,
+        };
+
+        let plantuml_url = match url::PlantumlUrl::parse(&dest_url) {
+            Some(plantuml_url) => plantuml_url,
+            None => continue,
+        };
+
+        let line_start = markdown[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = markdown[range.end..]
+            .find('\n')
+            .map_or(markdown.len(), |i| range.end + i);
+        let prefix = &markdown[line_start..range.start];
+
+        if !markdown[range.end..line_end].trim().is_empty()
+            || !prefix.chars().all(|c| c.is_whitespace() || c == '>')
+        {
+            continue;
+        }
+
+        let plantuml = match plantuml_url.decode() {
+            Ok(plantuml) => plantuml,
+            Err(_) => continue,
+        };
+
+        replacements.push((range, fenced(&plantuml, prefix)));
+    }
+
+    Ok(replace(markdown, replacements))
+}
+
+fn parser(markdown: &str) -> Parser<'_> {
+    Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS,
+    )
+}
+
+fn is_plantuml(info: &str) -> bool {
+    let language = info
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '{')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    LANGUAGES.contains(&language.as_str())
+}
+
+fn without_line_ending(markdown: &str, range: Range<usize>) -> Range<usize> {
+    let end = range.start + markdown[range.clone()].trim_end_matches(['\r', '\n']).len();
+
+    range.start..end
+}
+
+fn fenced(plantuml: &str, prefix: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+
+    for c in plantuml.chars() {
+        current = if c == '`' { current + 1 } else { 0 };
+        longest = longest.max(current);
+    }
+
+    let fence = "`".repeat(longest.max(2) + 1);
+    let mut block = format!("{}plantuml\n", fence);
+
+    for line in plantuml.trim_end_matches('\n').split('\n') {
+        if line.is_empty() {
+            block.push_str(prefix.trim_end());
+        } else {
+            block.push_str(prefix);
+            block.push_str(line);
+        }
+
+        block.push('\n');
+    }
+
+    block.push_str(prefix);
+    block.push_str(&fence);
+
+    block
+}
+
+fn replace(markdown: &str, mut replacements: Vec<(Range<usize>, String)>) -> String {
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut output = String::with_capacity(markdown.len());
+    let mut position = 0;
+
+    for (range, replacement) in replacements {
+        output.push_str(&markdown[position..range.start]);
+        output.push_str(&replacement);
+        position = range.end;
+    }
+
+    output.push_str(&markdown[position..]);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{markdown_fenced_blocks, markdown_to_fences, markdown_to_links};
+
+    use crate::url::{OutputFormat, PLANTUML_SERVER};
+
+    const URL: &str =
+        "https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000";
+
+    #[test]
+    fn it_markdown_to_links() {
+        let markdown = "Intro  \n\n* item\n\n  ```puml\n  PUML -> RUST\n  ```\n\n```rust\nlet x = 1;\n```\n\n~~~plantuml\n@startuml\nPUML -> RUST\n@enduml\n~~~";
+
+        assert_eq!(
+            markdown_to_links(markdown, PLANTUML_SERVER, OutputFormat::Svg),
+            Ok(format!(
+                "Intro  \n\n* item\n\n  ![plantuml]({})\n\n```rust\nlet x = 1;\n```\n\n![plantuml]({})",
+                URL, URL
+            ))
+        );
+    }
+
+    #[test]
+    fn it_markdown_to_fences() {
+        let markdown = format!(
+            "Inline ![a]({url}) stays.\n\n> ![quoted]({url})\n\n![b]({url} \"title\")\r\n\n![other](https://example.com/a.png)\n",
+            url = URL
+        );
+
+        assert_eq!(
+            markdown_to_fences(markdown),
+            Ok(format!(
+                "Inline ![a]({}) stays.\n\n> ```plantuml\n> @startuml\n> PUML -> RUST\n> @enduml\n> ```\n\n```plantuml\n@startuml\nPUML -> RUST\n@enduml\n```\r\n\n![other](https://example.com/a.png)\n",
+                URL
+            ))
+        );
+    }
+
+    #[test]
+    fn it_markdown_round_trip() {
+        let markdown = "# Title\n\n```plantuml\n@startuml\nPUML -> RUST\n@enduml\n```\n\nText *kept*  as   is.\n";
+
+        assert_eq!(
+            markdown_to_links(markdown, PLANTUML_SERVER, OutputFormat::Png)
+                .and_then(markdown_to_fences),
+            Ok(markdown.to_string())
+        );
+    }
+
+    #[test]
+    fn it_markdown_to_fences_longer_fence() {
+        assert_eq!(
+            markdown_to_fences("![a](https://www.plantuml.com/plantuml/uml/~h60606060)"),
+            Ok("`````plantuml\n````\n`````".to_string())
+        );
+    }
+
+    #[test]
+    fn it_markdown_to_fences_broken_link() {
+        assert_eq!(
+            markdown_to_fences("text\n\n![a](https://www.plantuml.com/plantuml/svg/~h1)\n\n![logo](https://cdn.example.com/assets/svg/logo)\n"),
+            Ok("text\n\n![a](https://www.plantuml.com/plantuml/svg/~h1)\n\n![logo](https://cdn.example.com/assets/svg/logo)\n".to_string())
+        );
+    }
+
+    #[test]
+    fn it_markdown_fenced_blocks() {
+        let blocks = markdown_fenced_blocks(
+            "> ~~~ PUML {.x}\n> A -> B\n> ~~~\r\n\n```rust\nlet x = 1;\n```",
+        );
+
+        assert_eq!(
+            blocks
+                .iter()
+                .map(|b| (
+                    b.range.clone(),
+                    b.info.as_str(),
+                    b.body.as_str(),
+                    b.is_plantuml()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (2..30, "PUML {.x}", "A -> B\n", true),
+                (33..55, "rust", "let x = 1;\n", false)
+            ]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use crate::deflate;
+use crate::errors;
+
+/// Diagram types whose bodies are kept as is (whitespace is meaningful there)
+const VERBATIM_DIAGRAMS: [&str; 7] = [
+    "@startditaa",
+    "@startjson",
+    "@startyaml",
+    "@startsalt",
+    "@startlatex",
+    "@startmath",
+    "@startcreole",
+];
+
+pub(crate) enum Verbatim {
+    Block(&'static [&'static str]),
+    Label,
+    Diagram,
+}
+
+/// Result of [`minify_plantuml_report`] with sizes before and after minification
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinifyReport {
+    /// Minified plantuml
+    pub minified: String,
+    /// Minified plantuml encoded with deflate
+    pub encoded: String,
+    /// Bytes of the original plantuml
+    pub raw_before: usize,
+    /// Bytes of the minified plantuml
+    pub raw_after: usize,
+    /// Bytes of the original plantuml encoded with deflate
+    pub encoded_before: usize,
+    /// Bytes of the minified plantuml encoded with deflate
+    pub encoded_after: usize,
+}
+
+impl MinifyReport {
+    /// Bytes saved in the raw plantuml
+    pub fn raw_saved(&self) -> usize {
+        self.raw_before.saturating_sub(self.raw_after)
+    }
+
+    /// Bytes saved in the deflate encoded plantuml
+    pub fn encoded_saved(&self) -> usize {
+        self.encoded_before.saturating_sub(self.encoded_after)
+    }
+}
+
+/// Minify plantuml before encoding to shorten URLs
+///
+/// Strips `'` and `/' ... '/` comments, blank lines, indentation and trailing whitespace.
+/// Multiline `note`, `legend`, `title`, `header`, `footer` blocks, multiline `:...;` labels
+/// and bodies of `@startjson`, `@startyaml`, `@startditaa` (and similar) diagrams are kept as is.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::minify_plantuml;
+///
+/// let minified = minify_plantuml("@startuml\n' comment\n\n    PUML -> RUST /' inline '/  \n@enduml\n");
+///
+/// assert_eq!(minified, "@startuml\nPUML -> RUST\n@enduml");
+/// ```
+pub fn minify_plantuml<T: AsRef<str>>(plantuml: T) -> String {
+    let mut result: Vec<String> = vec![];
+    let mut verbatim: Option<Verbatim> = None;
+    let mut in_comment = false;
+
+    for line in plantuml.as_ref().lines() {
+        if let Some(v) = &verbatim {
+            let trimmed = line.trim();
+            let lowercase = trimmed.to_lowercase();
+
+            let is_end = match v {
+                Verbatim::Block(ends) => ends.iter().any(|e| lowercase.starts_with(e)),
+                Verbatim::Label => trimmed.ends_with(';'),
+                Verbatim::Diagram => lowercase.starts_with("@end"),
+            };
+
+            if is_end && !matches!(v, Verbatim::Label) {
+                result.push(trimmed.to_string());
+            } else {
+                result.push(line.to_string());
+            }
+
+            if is_end {
+                verbatim = None;
+            }
+
+            continue;
+        }
+
+        let line = if in_comment || line.contains("/'") {
+            strip_block_comments(line, &mut in_comment)
+        } else {
+            line.to_string()
+        };
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('\'') {
+            continue;
+        }
+
+        verbatim = verbatim_start(trimmed);
+        result.push(trimmed.to_string());
+    }
+
+    result.join("\n")
+}
+
+/// Minify plantuml and report bytes saved in raw and deflate encoded forms
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{minify_plantuml_report, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let report = minify_plantuml_report("@startuml\n' comment\n\n    PUML -> RUST\n@enduml\n")?;
+///
+///     assert_eq!(report.minified, "@startuml\nPUML -> RUST\n@enduml");
+///     assert_eq!(report.encoded, "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000");
+///     assert_eq!(report.raw_saved(), 16);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn minify_plantuml_report<T: AsRef<str>>(
+    plantuml: T,
+) -> Result<MinifyReport, errors::FromPlantumlError> {
+    let plantuml = plantuml.as_ref();
+    let minified = minify_plantuml(plantuml);
+    let encoded = deflate::encode_plantuml_deflate(&minified)?;
+
+    Ok(MinifyReport {
+        raw_before: plantuml.len(),
+        raw_after: minified.len(),
+        encoded_before: deflate::encode_plantuml_deflate(plantuml)?.len(),
+        encoded_after: encoded.len(),
+        minified,
+        encoded,
+    })
+}
+
+pub(crate) fn verbatim_start(trimmed: &str) -> Option<Verbatim> {
+    let lowercase = trimmed.to_lowercase();
+    let words: Vec<&str> = lowercase.split_whitespace().collect();
+
+    if VERBATIM_DIAGRAMS.iter().any(|d| words.first() == Some(d)) {
+        return Some(Verbatim::Diagram);
+    }
+
+    match words.first() {
+        Some(&"note") | Some(&"rnote") | Some(&"hnote") => {
+            let rest = trimmed[words[0].len()..].trim_start();
+
+            if !rest.contains(':') && !rest.starts_with('"') {
+                return Some(Verbatim::Block(&[
+                    "end note",
+                    "endnote",
+                    "end rnote",
+                    "endrnote",
+                    "end hnote",
+                    "endhnote",
+                ]));
+            }
+
+            return None;
+        }
+        _ => {}
+    }
+
+    let is_alignment = |w: &&str| matches!(*w, "left" | "right" | "center" | "top" | "bottom");
+
+    if words.iter().filter(|w| !is_alignment(w)).count() == 1 {
+        match words.iter().find(|w| !is_alignment(w)) {
+            Some(&"legend") => return Some(Verbatim::Block(&["endlegend", "end legend"])),
+            Some(&"title") => return Some(Verbatim::Block(&["endtitle", "end title"])),
+            Some(&"header") => return Some(Verbatim::Block(&["endheader", "end header"])),
+            Some(&"footer") => return Some(Verbatim::Block(&["endfooter", "end footer"])),
+            _ => {}
+        }
+    }
+
+    let label = trimmed.trim_start_matches(['*', '+', '-', '#']);
+
+    if label.starts_with(':') && !label.ends_with([';', '|', '<', '>', '/', '\\', ']', '}']) {
+        return Some(Verbatim::Label);
+    }
+
+    None
+}
+
+pub(crate) fn strip_block_comments(line: &str, in_comment: &mut bool) -> String {
+    let mut result = String::new();
+    let mut in_string = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if *in_comment {
+            if c == '\'' && chars.peek() == Some(&'/') {
+                chars.next();
+                *in_comment = false;
+            }
+
+            continue;
+        }
+
+        if c == '"' {
+            in_string = !in_string;
+        } else if !in_string && c == '/' && chars.peek() == Some(&'\'') {
+            chars.next();
+            *in_comment = true;
+            continue;
+        }
+
+        result.push(c);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{minify_plantuml, minify_plantuml_report};
+
+    use crate::deflate::decode_plantuml_deflate;
+    use crate::tests::constants::plantuml_str::PLANTUML_LARGE;
+
+    #[test]
+    fn it_minify_plantuml_comments_and_whitespace() {
+        assert_eq!(
+            minify_plantuml(
+                "@startuml\n  ' line comment\n/' block\n   comment '/\n\n\tA -> B : \"/' kept '/\" /' dropped '/\t\n@enduml"
+            ),
+            "@startuml\nA -> B : \"/' kept '/\"\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_minify_plantuml_keeps_whitespace_blocks() {
+        assert_eq!(
+            minify_plantuml(
+                "  note left of A\n    indented\n\n  end note\n  note right: single\n  legend right\n   two  spaces\n  endlegend\n:multi\n  line;"
+            ),
+            "note left of A\n    indented\n\nend note\nnote right: single\nlegend right\n   two  spaces\nendlegend\n:multi\n  line;"
+        );
+    }
+
+    #[test]
+    fn it_minify_plantuml_keeps_yaml_body() {
+        assert_eq!(
+            minify_plantuml("@startyaml\nroot:\n  child: 1\n@endyaml"),
+            "@startyaml\nroot:\n  child: 1\n@endyaml"
+        );
+    }
+
+    #[test]
+    fn it_minify_plantuml_report_large() {
+        let report = minify_plantuml_report(PLANTUML_LARGE).unwrap();
+
+        assert_eq!(report.raw_before, PLANTUML_LARGE.len());
+        assert!(report.raw_saved() > 0);
+        assert_eq!(report.raw_before - report.raw_saved(), report.raw_after);
+        assert_eq!(
+            decode_plantuml_deflate(&report.encoded),
+            Ok(report.minified)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::class;
+use crate::errors;
+use crate::url;
+
+/// Reference from a field of the type to another type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassReference {
+    /// Field (or variant) of the type
+    pub field: &'static str,
+    /// Name of the referenced type
+    pub target: &'static str,
+    /// Multiplicity of the reference (`1`, `0..1` or `*`)
+    pub multiplicity: &'static str,
+}
+
+/// Type that can describe itself as plantuml class.
+///
+/// Usually implemented with `#[derive(PlantUmlClass)]` from the companion
+/// `plantuml_encoding_macros` crate.
+pub trait PlantUmlClass {
+    /// Element of the class diagram describing the type
+    fn plantuml_element() -> class::Element;
+
+    /// References from the fields of the type to other types
+    fn plantuml_references() -> Vec<ClassReference> {
+        vec![]
+    }
+}
+
+/// Registry of [`PlantUmlClass`] types that collects them with relations
+/// between registered types into one class diagram
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{ClassReference, ClassRegistry, Element, FromPlantumlError, PlantUmlClass};
+///
+/// struct Rust;
+///
+/// impl PlantUmlClass for Rust {
+///     fn plantuml_element() -> Element {
+///         Element::class("Rust")
+///     }
+/// }
+///
+/// struct Puml;
+///
+/// impl PlantUmlClass for Puml {
+///     fn plantuml_element() -> Element {
+///         Element::class("Puml").member("+rust : Rust")
+///     }
+///
+///     fn plantuml_references() -> Vec<ClassReference> {
+///         vec![ClassReference { field: "rust", target: "Rust", multiplicity: "1" }]
+///     }
+/// }
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let registry = ClassRegistry::new().register::<Puml>().register::<Rust>();
+///
+///     assert_eq!(
+///         registry.diagram().plantuml()?,
+///         "@startuml\nclass Puml {\n  +rust : Rust\n}\nclass Rust\nPuml --> \"1\" Rust : rust\n@enduml"
+///     );
+///
+///     let encoded_deflate = registry.encode_deflate()?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClassRegistry {
+    elements: Vec<class::Element>,
+    references: Vec<(String, ClassReference)>,
+}
+
+impl ClassRegistry {
+    /// Empty registry
+    pub fn new() -> Self {
+        ClassRegistry::default()
+    }
+
+    /// Register the type
+    pub fn register<T: PlantUmlClass>(mut self) -> Self {
+        let element = T::plantuml_element();
+        let name = element.id().to_string();
+
+        self.references.extend(
+            T::plantuml_references()
+                .into_iter()
+                .map(|reference| (name.clone(), reference)),
+        );
+        self.elements.push(element);
+        self
+    }
+
+    /// Class diagram of the registered types
+    /// (relations are added only between registered types)
+    pub fn diagram(&self) -> class::ClassDiagram {
+        let diagram = self
+            .elements
+            .iter()
+            .cloned()
+            .fold(class::ClassDiagram::new(), class::ClassDiagram::element);
+
+        self.references
+            .iter()
+            .filter(|(_, r)| self.elements.iter().any(|e| e.id() == r.target))
+            .fold(diagram, |diagram, (source, reference)| {
+                diagram.relation(
+                    class::Relation::new(
+                        class::RelationKind::DirectedAssociation,
+                        source,
+                        reference.target,
+                    )
+                    .target_multiplicity(reference.multiplicity)
+                    .label(reference.field),
+                )
+            })
+    }
+
+    /// Encode the class diagram with deflate compression
+    pub fn encode_deflate(&self) -> Result<String, errors::FromPlantumlError> {
+        self.diagram().encode_deflate()
+    }
+
+    /// Server URL of the class diagram
+    pub fn url<S: AsRef<str>>(
+        &self,
+        server: S,
+        format: url::OutputFormat,
+    ) -> Result<String, errors::FromPlantumlError> {
+        self.diagram().url(server, format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassReference, ClassRegistry, PlantUmlClass};
+
+    use crate::class::Element;
+
+    struct Order;
+    struct Line;
+    struct Unregistered;
+
+    impl PlantUmlClass for Order {
+        fn plantuml_element() -> Element {
+            Element::class("Order")
+                .member("+lines : Vec<Line>")
+                .member("+note : Option<Unregistered>")
+        }
+
+        fn plantuml_references() -> Vec<ClassReference> {
+            vec![
+                ClassReference {
+                    field: "lines",
+                    target: "Line",
+                    multiplicity: "*",
+                },
+                ClassReference {
+                    field: "note",
+                    target: "Unregistered",
+                    multiplicity: "0..1",
+                },
+            ]
+        }
+    }
+
+    impl PlantUmlClass for Line {
+        fn plantuml_element() -> Element {
+            Element::class("Line")
+        }
+    }
+
+    impl PlantUmlClass for Unregistered {
+        fn plantuml_element() -> Element {
+            Element::class("Unregistered")
+        }
+    }
+
+    #[test]
+    fn it_class_registry_relations_between_registered_types() {
+        assert_eq!(
+            ClassRegistry::new()
+                .register::<Order>()
+                .register::<Line>()
+                .diagram()
+                .plantuml()
+                .unwrap(),
+            "@startuml\nclass Line\nclass Order {\n  +lines : Vec<Line>\n  +note : Option<Unregistered>\n}\nOrder --> \"*\" Line : lines\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_class_registry_order_independent() {
+        let forward = ClassRegistry::new()
+            .register::<Order>()
+            .register::<Line>()
+            .register::<Unregistered>();
+        let backward = ClassRegistry::new()
+            .register::<Unregistered>()
+            .register::<Line>()
+            .register::<Order>();
+
+        assert_eq!(forward.encode_deflate(), backward.encode_deflate());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::deflate;
+use crate::errors;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Normalize plantuml to the canonical form:
+/// LF line endings, no BOM, no trailing whitespace on lines and no trailing blank lines
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::normalize_plantuml;
+///
+/// let normalized = normalize_plantuml("\u{feff}@startuml\r\nPUML -> RUST  \r\n@enduml\r\n\r\n");
+///
+/// assert_eq!(normalized, "@startuml\nPUML -> RUST\n@enduml");
+/// ```
+pub fn normalize_plantuml<T: AsRef<str>>(plantuml: T) -> String {
+    let plantuml = plantuml.as_ref();
+    let plantuml = plantuml.strip_prefix('\u{feff}').unwrap_or(plantuml);
+
+    plantuml
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .split('\n')
+        .map(str::trim_end)
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .trim_end_matches('\n')
+        .to_string()
+}
+
+/// Normalize plantuml as [`normalize_plantuml`] does and apply Unicode NFC
+/// (requires `nfc` feature)
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::normalize_plantuml_nfc;
+///
+/// let normalized = normalize_plantuml_nfc("A -> B: Cafe\u{301}\r\n");
+///
+/// assert_eq!(normalized, "A -> B: Caf\u{e9}");
+/// ```
+#[cfg(feature = "nfc")]
+pub fn normalize_plantuml_nfc<T: AsRef<str>>(plantuml: T) -> String {
+    use unicode_normalization::UnicodeNormalization;
+
+    normalize_plantuml(plantuml).nfc().collect()
+}
+
+/// Normalize plantuml with [`normalize_plantuml`] and encode it with deflate compression
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{encode_plantuml_deflate_normalized, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let encoded_deflate = encode_plantuml_deflate_normalized("@startuml\r\nPUML -> RUST \r\n@enduml\r\n")?;
+///
+///     assert_eq!(encoded_deflate, "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn encode_plantuml_deflate_normalized<T: AsRef<str>>(
+    plantuml: T,
+) -> Result<String, errors::FromPlantumlError> {
+    deflate::encode_plantuml_deflate(normalize_plantuml(plantuml))
+}
+
+/// Normalize plantuml with [`normalize_plantuml_nfc`] and encode it with deflate compression
+/// (requires `nfc` feature)
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{encode_plantuml_deflate_normalized_nfc, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     assert_eq!(
+///         encode_plantuml_deflate_normalized_nfc("A -> B: Cafe\u{301}")?,
+///         encode_plantuml_deflate_normalized_nfc("A -> B: Caf\u{e9}\r\n")?
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "nfc")]
+pub fn encode_plantuml_deflate_normalized_nfc<T: AsRef<str>>(
+    plantuml: T,
+) -> Result<String, errors::FromPlantumlError> {
+    deflate::encode_plantuml_deflate(normalize_plantuml_nfc(plantuml))
+}
+
+/// Content hash (64-bit FNV-1a in hex) of the normalized plantuml,
+/// stable across platforms and crate versions
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::plantuml_hash;
+///
+/// assert_eq!(
+///     plantuml_hash("@startuml\r\nPUML -> RUST\r\n@enduml\r\n"),
+///     plantuml_hash("@startuml\nPUML -> RUST\n@enduml"),
+/// );
+/// ```
+pub fn plantuml_hash<T: AsRef<str>>(plantuml: T) -> String {
+    fnv_hash(&normalize_plantuml(plantuml))
+}
+
+/// Content hash (as [`plantuml_hash`]) of the plantuml normalized with
+/// [`normalize_plantuml_nfc`] (requires `nfc` feature)
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::plantuml_hash_nfc;
+///
+/// assert_eq!(plantuml_hash_nfc("A -> B: Cafe\u{301}"), plantuml_hash_nfc("A -> B: Caf\u{e9}"));
+/// ```
+#[cfg(feature = "nfc")]
+pub fn plantuml_hash_nfc<T: AsRef<str>>(plantuml: T) -> String {
+    fnv_hash(&normalize_plantuml_nfc(plantuml))
+}
+
+fn fnv_hash(text: &str) -> String {
+    let hash = text.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_plantuml_deflate_normalized, normalize_plantuml, plantuml_hash};
+
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_str::PLANTUML_SMALL,
+    };
+
+    #[test]
+    fn it_normalize_plantuml_line_endings_and_bom() {
+        assert_eq!(
+            normalize_plantuml("\u{feff}@startuml\r\nA -> B\t \rB -> A\n\n@enduml\n\n"),
+            "@startuml\nA -> B\nB -> A\n\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_encode_plantuml_deflate_normalized_small() {
+        assert_eq!(
+            encode_plantuml_deflate_normalized(PLANTUML_SMALL.replace('\n', "\r\n")),
+            encode_plantuml_deflate_normalized(PLANTUML_SMALL)
+        );
+        assert_ne!(
+            encode_plantuml_deflate_normalized(PLANTUML_SMALL),
+            Ok(PLANTUML_DEFLATED_SMALL.to_string())
+        );
+    }
+
+    #[test]
+    fn it_plantuml_hash() {
+        assert_eq!(plantuml_hash(""), "cbf29ce484222325");
+        assert_eq!(plantuml_hash("\u{feff}A -> B\r\n"), plantuml_hash("A -> B"));
+        assert_ne!(plantuml_hash("A -> B"), plantuml_hash("B -> A"));
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn it_normalize_plantuml_nfc() {
+        assert_eq!(
+            super::normalize_plantuml_nfc("Cafe\u{301}"),
+            super::normalize_plantuml_nfc("Caf\u{e9}")
+        );
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn it_encode_and_hash_plantuml_nfc() {
+        assert_eq!(
+            super::encode_plantuml_deflate_normalized_nfc("Cafe\u{301}"),
+            super::encode_plantuml_deflate_normalized_nfc("Caf\u{e9}")
+        );
+        assert_eq!(
+            super::plantuml_hash_nfc("Cafe\u{301}"),
+            super::plantuml_hash_nfc("Caf\u{e9}")
+        );
+        assert_ne!(plantuml_hash("Cafe\u{301}"), plantuml_hash("Caf\u{e9}"));
+        assert_eq!(
+            super::plantuml_hash_nfc("A -> B\r\n"),
+            plantuml_hash("A -> B")
+        );
+    }
+}
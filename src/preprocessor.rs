@@ -0,0 +1,1721 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::errors;
+use crate::include::{self, IncludeResolver, SourceLine};
+
+const MAX_CALL_DEPTH: usize = 64;
+const MAX_EXPR_DEPTH: usize = 256;
+const MAX_LOOP_ITERATIONS: usize = 100_000;
+
+/// Local evaluator of the [plantuml preprocessor](https://plantuml.com/preprocessing).
+///
+/// Inlines includes with the help of [`IncludeResolver`] and expands
+/// `!define`, `!definelong`, `!$var = ...`, `!function`, `!procedure`,
+/// `!if`/`!elseif`/`!else`/`!endif`, `!ifdef`/`!ifndef`, `!foreach`, `!while`,
+/// `!assert` and `%` builtin functions. All other lines (`skinparam`, `!pragma`,
+/// `!theme` and so on) are passed to the output as is.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{encode_plantuml_deflate, FromPlantumlError, IncludeResolver, Preprocessor};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let preprocessor = Preprocessor::new(IncludeResolver::new("."));
+///
+///     let preprocessed = preprocessor.process("@startuml\n!$to = \"RUST\"\nPUML -> $to\n@enduml")?;
+///
+///     assert_eq!(preprocessed.source(), "@startuml\nPUML -> RUST\n@enduml");
+///     assert_eq!(preprocessed.origin(2), Some(("<input>", 3)));
+///
+///     let encoded_deflate = encode_plantuml_deflate(preprocessed.source())?;
+///
+///     assert_eq!(encoded_deflate, "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000");
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    resolver: IncludeResolver,
+}
+
+/// Result of the preprocessing with mapping of the output lines back to the original sources
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preprocessed {
+    lines: Vec<SourceLine>,
+}
+
+impl Preprocessor {
+    /// Create preprocessor that inlines includes with `resolver`
+    pub fn new(resolver: IncludeResolver) -> Self {
+        Preprocessor { resolver }
+    }
+
+    /// Preprocess `plantuml`
+    pub fn process<T: AsRef<str>>(
+        &self,
+        plantuml: T,
+    ) -> Result<Preprocessed, errors::FromPlantumlError> {
+        let lines = self.resolver.resolve_lines(plantuml.as_ref(), "<input>")?;
+
+        evaluate(&lines)
+    }
+
+    /// Read file and preprocess it
+    pub fn process_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Preprocessed, errors::FromPlantumlError> {
+        let lines = self.resolver.resolve_file_lines(path.as_ref())?;
+
+        evaluate(&lines)
+    }
+}
+
+impl Preprocessed {
+    /// Preprocessed plantuml
+    pub fn source(&self) -> String {
+        include::join_lines(&self.lines)
+    }
+
+    /// Original file (or `<input>`) and 1-based line for the 1-based `line` of the output
+    pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+        let source_line = self.lines.get(line.checked_sub(1)?)?;
+
+        Some((source_line.origin.as_str(), source_line.line))
+    }
+
+    /// Number of the output lines
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Whether the output is empty
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+/// Preprocess plantuml with local includes relative to the current directory
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{preprocess_plantuml, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let preprocessed = preprocess_plantuml(
+///         "!function $twice($a)\n!return $a + $a\n!endfunction\nA -> B: $twice(21)",
+///     )?;
+///
+///     assert_eq!(preprocessed, "A -> B: 42");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn preprocess_plantuml<T: AsRef<str>>(
+    plantuml: T,
+) -> Result<String, errors::FromPlantumlError> {
+    Ok(Preprocessor::new(IncludeResolver::new("."))
+        .process(plantuml)?
+        .source())
+}
+
+fn evaluate(lines: &[SourceLine]) -> Result<Preprocessed, errors::FromPlantumlError> {
+    let mut position = 0;
+    let (nodes, _) = parse_block(lines, &mut position, &[])?;
+
+    let mut machine = Machine {
+        lines,
+        globals: HashMap::new(),
+        frames: vec![],
+        functions: HashMap::new(),
+        defines: vec![],
+        depth: 0,
+    };
+    let mut output = vec![];
+
+    machine.run(&nodes, &mut output)?;
+
+    Ok(Preprocessed { lines: output })
+}
+
+fn error_at(line: &SourceLine, message: String) -> errors::FromPlantumlError {
+    errors::FromPlantumlError(format!("{} ({}:{})", message, line.origin, line.line))
+}
+
+// --- values ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn is_true(&self) -> bool {
+        match self {
+            Value::Int(i) => *i != 0,
+            Value::Str(s) => !s.is_empty() && s != "false",
+            Value::Array(a) => !a.is_empty(),
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(i) => Some(*i),
+            Value::Str(s) => s.trim().parse().ok(),
+            Value::Array(_) => None,
+        }
+    }
+
+    fn from_bool(b: bool) -> Value {
+        Value::Int(i64::from(b))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Array(a) => {
+                write!(f, "[")?;
+
+                for (index, item) in a.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    match item {
+                        Value::Str(s) => write!(f, "\"{}\"", s)?,
+                        other => write!(f, "{}", other)?,
+                    }
+                }
+
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+// --- parsing of directives ---
+
+#[derive(Debug)]
+struct Node {
+    line: usize,
+    kind: NodeKind,
+}
+
+#[derive(Debug)]
+enum NodeKind {
+    Text,
+    Assign {
+        name: String,
+        expr: String,
+        conditional: bool,
+        global: bool,
+    },
+    Define(Define),
+    Undef(String),
+    If(Vec<Branch>),
+    Foreach {
+        variable: String,
+        expr: String,
+        body: Vec<Node>,
+    },
+    While {
+        expr: String,
+        body: Vec<Node>,
+    },
+    Function(Rc<Function>),
+    Return(String),
+    Assert {
+        expr: String,
+        message: Option<String>,
+    },
+    Ignored,
+}
+
+#[derive(Debug)]
+struct Branch {
+    condition: Condition,
+    body: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Condition {
+    Expr(String),
+    Defined(String, bool),
+    Always,
+}
+
+#[derive(Debug, Clone)]
+struct Define {
+    name: String,
+    params: Option<Vec<String>>,
+    body: String,
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    params: Vec<Param>,
+    body: Vec<Node>,
+    procedure: bool,
+}
+
+#[derive(Debug)]
+struct Param {
+    name: String,
+    default: Option<String>,
+}
+
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim();
+
+    if !trimmed.starts_with('!') {
+        return None;
+    }
+
+    let end = trimmed[1..]
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map(|e| e + 1)
+        .unwrap_or(trimmed.len());
+
+    Some((&trimmed[..end], trimmed[end..].trim()))
+}
+
+type Terminator = Option<(String, usize)>;
+
+fn parse_block(
+    lines: &[SourceLine],
+    position: &mut usize,
+    terminators: &[&str],
+) -> Result<(Vec<Node>, Terminator), errors::FromPlantumlError> {
+    let mut nodes = vec![];
+
+    while *position < lines.len() {
+        let index = *position;
+        let line = &lines[index];
+        *position += 1;
+
+        let (keyword, rest) = match directive(&line.text) {
+            Some(d) => d,
+            None => {
+                nodes.push(Node {
+                    line: index,
+                    kind: NodeKind::Text,
+                });
+                continue;
+            }
+        };
+
+        if terminators.contains(&keyword) {
+            return Ok((nodes, Some((keyword.to_string(), index))));
+        }
+
+        let kind = match keyword {
+            "!" if rest.starts_with('$') => parse_assign(rest, false, line)?,
+            "!local" => parse_assign(rest, false, line)?,
+            "!global" => parse_assign(rest, true, line)?,
+            "!define" => NodeKind::Define(parse_define(rest, String::new(), line)?),
+            "!definelong" => {
+                let mut body = vec![];
+
+                loop {
+                    match lines.get(*position) {
+                        Some(l) if directive(&l.text).map(|d| d.0) == Some("!enddefinelong") => {
+                            *position += 1;
+                            break;
+                        }
+                        Some(l) => {
+                            body.push(l.text.clone());
+                            *position += 1;
+                        }
+                        None => {
+                            return Err(error_at(
+                                line,
+                                "missing `!enddefinelong` for `!definelong`".to_string(),
+                            ));
+                        }
+                    }
+                }
+
+                NodeKind::Define(parse_define(rest, body.join("\n"), line)?)
+            }
+            "!undef" => NodeKind::Undef(rest.to_string()),
+            "!if" | "!ifdef" | "!ifndef" => {
+                let mut branches = vec![];
+                let mut condition = match keyword {
+                    "!if" => Condition::Expr(rest.to_string()),
+                    "!ifdef" => Condition::Defined(rest.to_string(), true),
+                    _ => Condition::Defined(rest.to_string(), false),
+                };
+
+                loop {
+                    let (body, end) =
+                        parse_block(lines, position, &["!elseif", "!else", "!endif"])?;
+
+                    branches.push(Branch { condition, body });
+
+                    match end {
+                        Some((end, end_index)) if end == "!elseif" => {
+                            let (_, expr) = directive(&lines[end_index].text).unwrap_or_default();
+                            condition = Condition::Expr(expr.to_string());
+                        }
+                        Some((end, _)) if end == "!else" => condition = Condition::Always,
+                        Some(_) => break,
+                        None => {
+                            return Err(error_at(
+                                line,
+                                format!("missing `!endif` for `{}`", keyword),
+                            ));
+                        }
+                    }
+                }
+
+                NodeKind::If(branches)
+            }
+            "!foreach" => {
+                let (variable, expr) = rest.split_once(" in ").ok_or_else(|| {
+                    error_at(line, "expected `!foreach $item in <list>`".to_string())
+                })?;
+                let body = parse_body(lines, position, "!endfor", keyword, line)?;
+
+                NodeKind::Foreach {
+                    variable: variable.trim().to_string(),
+                    expr: expr.trim().to_string(),
+                    body,
+                }
+            }
+            "!while" => NodeKind::While {
+                expr: rest.to_string(),
+                body: parse_body(lines, position, "!endwhile", keyword, line)?,
+            },
+            "!function" | "!procedure" | "!unquoted" | "!final" => {
+                let (keyword, rest) = strip_function_modifiers(keyword, rest);
+                let procedure = match keyword {
+                    "!function" => false,
+                    "!procedure" => true,
+                    _ => {
+                        nodes.push(Node {
+                            line: index,
+                            kind: NodeKind::Text,
+                        });
+                        continue;
+                    }
+                };
+
+                NodeKind::Function(Rc::new(parse_function(
+                    rest, procedure, lines, position, line,
+                )?))
+            }
+            "!return" => NodeKind::Return(rest.to_string()),
+            "!assert" => {
+                let (expr, message) = match split_top_level(rest, ':') {
+                    Some((expr, message)) => (expr, Some(message.trim().to_string())),
+                    None => (rest, None),
+                };
+
+                NodeKind::Assert {
+                    expr: expr.trim().to_string(),
+                    message,
+                }
+            }
+            "!log" | "!dump_memory" => NodeKind::Ignored,
+            "!endif" | "!else" | "!elseif" | "!endfor" | "!endwhile" | "!endfunction"
+            | "!endprocedure" | "!enddefinelong" => {
+                return Err(error_at(line, format!("unexpected `{}`", keyword)));
+            }
+            _ => NodeKind::Text,
+        };
+
+        nodes.push(Node { line: index, kind });
+    }
+
+    Ok((nodes, None))
+}
+
+fn parse_body(
+    lines: &[SourceLine],
+    position: &mut usize,
+    terminator: &str,
+    keyword: &str,
+    line: &SourceLine,
+) -> Result<Vec<Node>, errors::FromPlantumlError> {
+    match parse_block(lines, position, &[terminator])? {
+        (body, Some(_)) => Ok(body),
+        (_, None) => Err(error_at(
+            line,
+            format!("missing `{}` for `{}`", terminator, keyword),
+        )),
+    }
+}
+
+fn strip_function_modifiers<'a>(keyword: &'a str, rest: &'a str) -> (&'a str, &'a str) {
+    let mut keyword = keyword;
+    let mut rest = rest;
+
+    while keyword == "!unquoted" || keyword == "!final" {
+        let (next, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+        keyword = match next {
+            "function" => "!function",
+            "procedure" => "!procedure",
+            "unquoted" => "!unquoted",
+            "final" => "!final",
+            _ => return (keyword, rest),
+        };
+        rest = tail.trim();
+    }
+
+    (keyword, rest)
+}
+
+fn parse_assign(
+    rest: &str,
+    global: bool,
+    line: &SourceLine,
+) -> Result<NodeKind, errors::FromPlantumlError> {
+    let (name, conditional, expr) = match rest.split_once('=') {
+        Some((name, expr)) if name.ends_with('?') => {
+            (name.trim_end_matches('?').trim(), true, expr)
+        }
+        Some((name, expr)) => (name.trim(), false, expr),
+        None => return Err(error_at(line, "expected `$variable = <value>`".to_string())),
+    };
+
+    if !is_variable_name(name) {
+        return Err(error_at(line, format!("invalid variable name `{}`", name)));
+    }
+
+    Ok(NodeKind::Assign {
+        name: name.to_string(),
+        expr: expr.trim().to_string(),
+        conditional,
+        global,
+    })
+}
+
+fn parse_define(
+    rest: &str,
+    long_body: String,
+    line: &SourceLine,
+) -> Result<Define, errors::FromPlantumlError> {
+    let name_end = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .unwrap_or(rest.len());
+    let name = &rest[..name_end];
+
+    if name.is_empty() {
+        return Err(error_at(line, "expected name after `!define`".to_string()));
+    }
+
+    let tail = &rest[name_end..];
+
+    let (params, body) = if tail.starts_with('(') {
+        let close = tail
+            .find(')')
+            .ok_or_else(|| error_at(line, format!("unclosed parameters of `{}`", name)))?;
+        let params = tail[1..close]
+            .split(',')
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+
+        (Some(params), tail[close + 1..].trim())
+    } else {
+        (None, tail.trim())
+    };
+
+    Ok(Define {
+        name: name.to_string(),
+        params,
+        body: if long_body.is_empty() {
+            body.to_string()
+        } else {
+            long_body
+        },
+    })
+}
+
+fn parse_function(
+    rest: &str,
+    procedure: bool,
+    lines: &[SourceLine],
+    position: &mut usize,
+    line: &SourceLine,
+) -> Result<Function, errors::FromPlantumlError> {
+    let open = rest
+        .find('(')
+        .ok_or_else(|| error_at(line, "expected parameters of function".to_string()))?;
+    let close = matching_paren(rest, open)
+        .ok_or_else(|| error_at(line, "unclosed parameters of function".to_string()))?;
+    let name = rest[..open].trim().to_string();
+
+    let params = split_arguments(&rest[open + 1..close])
+        .into_iter()
+        .map(|p| match p.split_once('=') {
+            Some((name, default)) => Param {
+                name: name.trim().to_string(),
+                default: Some(default.trim().to_string()),
+            },
+            None => Param {
+                name: p.trim().to_string(),
+                default: None,
+            },
+        })
+        .collect();
+
+    let tail = rest[close + 1..].trim();
+
+    let body = match directive(tail) {
+        Some(("!return", expr)) if !procedure => vec![Node {
+            line: *position - 1,
+            kind: NodeKind::Return(expr.to_string()),
+        }],
+        _ => {
+            let terminator = if procedure {
+                "!endprocedure"
+            } else {
+                "!endfunction"
+            };
+            let keyword = if procedure { "!procedure" } else { "!function" };
+
+            parse_body(lines, position, terminator, keyword, line)?
+        }
+    };
+
+    Ok(Function {
+        name,
+        params,
+        body,
+        procedure,
+    })
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars.next() == Some('$') && chars.all(|c| c.is_alphanumeric() || c == '_') && name.len() > 1
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn matching_paren(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+
+    for (index, c) in text[open..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') | (None, '[') => depth += 1,
+            (None, ')') | (None, ']') => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(open + index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn split_top_level(text: &str, separator: char) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    let mut quote = None;
+
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') | (None, '[') => depth += 1,
+            (None, ')') | (None, ']') => depth -= 1,
+            (None, c) if c == separator && depth == 0 => {
+                return Some((&text[..index], &text[index + c.len_utf8()..]));
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn split_arguments(text: &str) -> Vec<&str> {
+    let mut arguments = vec![];
+    let mut rest = text;
+
+    if text.trim().is_empty() {
+        return arguments;
+    }
+
+    while let Some((argument, tail)) = split_top_level(rest, ',') {
+        arguments.push(argument.trim());
+        rest = tail;
+    }
+
+    arguments.push(rest.trim());
+
+    arguments
+}
+
+// --- expressions ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Int(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 19] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "(", ")", ",", "[",
+    "]",
+];
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = expr.chars().collect();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+
+        if c.is_whitespace() {
+            index += 1;
+        } else if c == '"' || c == '\'' {
+            let start = index + 1;
+            index = start;
+
+            while index < chars.len() && chars[index] != c {
+                index += 1;
+            }
+
+            if index == chars.len() {
+                return Err(format!("unclosed string in `{}`", expr));
+            }
+
+            tokens.push(Token::Str(chars[start..index].iter().collect()));
+            index += 1;
+        } else if c.is_ascii_digit() {
+            let start = index;
+
+            while index < chars.len() && chars[index].is_ascii_digit() {
+                index += 1;
+            }
+
+            let number: String = chars[start..index].iter().collect();
+            tokens.push(Token::Int(
+                number
+                    .parse()
+                    .map_err(|_| format!("invalid number `{}`", number))?,
+            ));
+        } else if c == '$'
+            || c == '%' && chars.get(index + 1).is_some_and(|n| n.is_alphabetic())
+            || is_identifier_char(c)
+        {
+            let start = index;
+            index += 1;
+
+            while index < chars.len() && is_identifier_char(chars[index]) {
+                index += 1;
+            }
+
+            tokens.push(Token::Ident(chars[start..index].iter().collect()));
+        } else {
+            let rest: String = chars[index..].iter().take(2).collect();
+            let operator = OPERATORS
+                .iter()
+                .find(|o| rest.starts_with(*o))
+                .ok_or_else(|| format!("unexpected `{}` in `{}`", c, expr))?;
+
+            tokens.push(Token::Op(operator));
+            index += operator.chars().count();
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Variable(String),
+    Call(String, Vec<Expr>),
+    Array(Vec<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl ExprParser {
+    fn parse(expr: &str) -> Result<Expr, String> {
+        let mut parser = ExprParser {
+            tokens: tokenize(expr)?,
+            position: 0,
+            depth: 0,
+        };
+
+        if parser.tokens.is_empty() {
+            return Ok(Expr::Literal(Value::Str(String::new())));
+        }
+
+        let result = parser.binary(0)?;
+
+        match parser.tokens.get(parser.position) {
+            None => Ok(result),
+            Some(token) => Err(format!("unexpected `{:?}` in `{}`", token, expr)),
+        }
+    }
+
+    fn precedence(operator: &str) -> Option<usize> {
+        match operator {
+            "||" => Some(1),
+            "&&" => Some(2),
+            "==" | "!=" => Some(3),
+            "<" | ">" | "<=" | ">=" => Some(4),
+            "+" | "-" => Some(5),
+            "*" | "/" | "%" => Some(6),
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, min_precedence: usize) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut left = self.unary()?;
+
+        while let Some(Token::Op(operator)) = self.tokens.get(self.position) {
+            let operator = *operator;
+            let precedence = match Self::precedence(operator) {
+                Some(p) if p > min_precedence => p,
+                _ => break,
+            };
+
+            // every operator nests the chain one level deeper (evaluation recurses through it)
+            if self.depth >= MAX_EXPR_DEPTH {
+                return Err(format!("expression nesting exceeds {}", MAX_EXPR_DEPTH));
+            }
+
+            self.depth += 1;
+            self.position += 1;
+            let right = self.binary(precedence)?;
+            left = Expr::Binary(operator, Box::new(left), Box::new(right));
+        }
+
+        self.depth = depth;
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.depth >= MAX_EXPR_DEPTH {
+            return Err(format!("expression nesting exceeds {}", MAX_EXPR_DEPTH));
+        }
+
+        self.depth += 1;
+        let result = self.operand();
+        self.depth -= 1;
+
+        result
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| "unexpected end of expression".to_string())?;
+        self.position += 1;
+
+        match token {
+            Token::Str(s) => Ok(Expr::Literal(Value::Str(s))),
+            Token::Int(i) => Ok(Expr::Literal(Value::Int(i))),
+            Token::Op("!") => Ok(Expr::Not(Box::new(self.unary()?))),
+            Token::Op("-") => Ok(Expr::Negate(Box::new(self.unary()?))),
+            Token::Op("(") => {
+                let inner = self.binary(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Op("[") => Ok(Expr::Array(self.list("]")?)),
+            Token::Ident(name) => {
+                if self.tokens.get(self.position) == Some(&Token::Op("(")) {
+                    self.position += 1;
+                    Ok(Expr::Call(name, self.list(")")?))
+                } else if name.starts_with('$') {
+                    Ok(Expr::Variable(name))
+                } else {
+                    match name.as_str() {
+                        "true" => Ok(Expr::Literal(Value::Int(1))),
+                        "false" => Ok(Expr::Literal(Value::Int(0))),
+                        _ => Ok(Expr::Literal(Value::Str(name))),
+                    }
+                }
+            }
+            Token::Op(operator) => Err(format!("unexpected `{}`", operator)),
+        }
+    }
+
+    fn list(&mut self, close: &'static str) -> Result<Vec<Expr>, String> {
+        let mut items = vec![];
+
+        if self.tokens.get(self.position) == Some(&Token::Op(close)) {
+            self.position += 1;
+            return Ok(items);
+        }
+
+        loop {
+            items.push(self.binary(0)?);
+
+            match self.tokens.get(self.position) {
+                Some(Token::Op(",")) => self.position += 1,
+                Some(Token::Op(o)) if *o == close => {
+                    self.position += 1;
+                    return Ok(items);
+                }
+                _ => return Err(format!("expected `{}`", close)),
+            }
+        }
+    }
+
+    fn expect(&mut self, operator: &'static str) -> Result<(), String> {
+        if self.tokens.get(self.position) == Some(&Token::Op(operator)) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", operator))
+        }
+    }
+}
+
+// --- evaluation ---
+
+enum Flow {
+    Next,
+    Return(Value),
+}
+
+struct Machine<'a> {
+    lines: &'a [SourceLine],
+    globals: HashMap<String, Value>,
+    frames: Vec<HashMap<String, Value>>,
+    functions: HashMap<String, Rc<Function>>,
+    defines: Vec<Define>,
+    depth: usize,
+}
+
+impl<'a> Machine<'a> {
+    fn run(
+        &mut self,
+        nodes: &[Node],
+        output: &mut Vec<SourceLine>,
+    ) -> Result<Flow, errors::FromPlantumlError> {
+        for node in nodes {
+            let line = &self.lines[node.line];
+
+            match &node.kind {
+                NodeKind::Text => self.text(line, output)?,
+                NodeKind::Assign {
+                    name,
+                    expr,
+                    conditional,
+                    global,
+                } => {
+                    if *conditional && self.variable(name).is_some() {
+                        continue;
+                    }
+
+                    let value = self.eval(expr, line)?;
+                    self.assign(name, value, *global);
+                }
+                NodeKind::Define(define) => {
+                    self.defines.retain(|d| d.name != define.name);
+                    self.defines.push(define.clone());
+                }
+                NodeKind::Undef(name) => {
+                    self.defines.retain(|d| &d.name != name);
+                    self.globals.remove(name);
+                }
+                NodeKind::If(branches) => {
+                    for branch in branches {
+                        let matched = match &branch.condition {
+                            Condition::Expr(expr) => self.eval(expr, line)?.is_true(),
+                            Condition::Defined(name, expected) => {
+                                self.is_defined(name) == *expected
+                            }
+                            Condition::Always => true,
+                        };
+
+                        if matched {
+                            if let Flow::Return(value) = self.run(&branch.body, output)? {
+                                return Ok(Flow::Return(value));
+                            }
+
+                            break;
+                        }
+                    }
+                }
+                NodeKind::Foreach {
+                    variable,
+                    expr,
+                    body,
+                } => {
+                    let items = match self.eval(expr, line)? {
+                        Value::Array(items) => items,
+                        other => {
+                            return Err(error_at(
+                                line,
+                                format!("`{}` is not a list: `{}`", expr, other),
+                            ));
+                        }
+                    };
+
+                    for item in items {
+                        self.assign(variable, item, false);
+
+                        if let Flow::Return(value) = self.run(body, output)? {
+                            return Ok(Flow::Return(value));
+                        }
+                    }
+                }
+                NodeKind::While { expr, body } => {
+                    let mut iterations = 0;
+
+                    while self.eval(expr, line)?.is_true() {
+                        iterations += 1;
+
+                        if iterations > MAX_LOOP_ITERATIONS {
+                            return Err(error_at(
+                                line,
+                                format!(
+                                    "`!while {}` exceeds {} iterations",
+                                    expr, MAX_LOOP_ITERATIONS
+                                ),
+                            ));
+                        }
+
+                        if let Flow::Return(value) = self.run(body, output)? {
+                            return Ok(Flow::Return(value));
+                        }
+                    }
+                }
+                NodeKind::Function(function) => {
+                    self.functions
+                        .insert(function.name.clone(), Rc::clone(function));
+                }
+                NodeKind::Return(expr) => {
+                    if self.frames.is_empty() {
+                        return Err(error_at(line, "`!return` outside of function".to_string()));
+                    }
+
+                    return Ok(Flow::Return(self.eval(expr, line)?));
+                }
+                NodeKind::Assert { expr, message } => {
+                    if !self.eval(expr, line)?.is_true() {
+                        let message = match message {
+                            Some(m) => self.eval(m, line)?.to_string(),
+                            None => format!("assertion failed: `{}`", expr),
+                        };
+
+                        return Err(error_at(line, message));
+                    }
+                }
+                NodeKind::Ignored => {}
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn variable(&self, name: &str) -> Option<&Value> {
+        self.frames
+            .last()
+            .and_then(|frame| frame.get(name))
+            .or_else(|| self.globals.get(name))
+    }
+
+    fn assign(&mut self, name: &str, value: Value, global: bool) {
+        match self.frames.last_mut() {
+            Some(frame) if !global => {
+                frame.insert(name.to_string(), value);
+            }
+            _ => {
+                self.globals.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.variable(name).is_some()
+            || self.defines.iter().any(|d| d.name == name)
+            || self.functions.contains_key(name)
+    }
+
+    fn eval(&mut self, expr: &str, line: &SourceLine) -> Result<Value, errors::FromPlantumlError> {
+        let expanded = self.expand_defines(expr);
+        let parsed = ExprParser::parse(&expanded).map_err(|err| error_at(line, err))?;
+
+        self.eval_expr(&parsed, line)
+    }
+
+    fn eval_expr(
+        &mut self,
+        expr: &Expr,
+        line: &SourceLine,
+    ) -> Result<Value, errors::FromPlantumlError> {
+        match expr {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Variable(name) => self
+                .variable(name)
+                .cloned()
+                .ok_or_else(|| error_at(line, format!("undefined variable `{}`", name))),
+            Expr::Array(items) => Ok(Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.eval_expr(item, line))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Expr::Not(inner) => Ok(Value::from_bool(!self.eval_expr(inner, line)?.is_true())),
+            Expr::Negate(inner) => match self.eval_expr(inner, line)?.as_int() {
+                Some(i) => i
+                    .checked_neg()
+                    .map(Value::Int)
+                    .ok_or_else(|| error_at(line, "integer overflow".to_string())),
+                None => Err(error_at(line, "negation of not a number".to_string())),
+            },
+            Expr::Binary("&&", left, right) => Ok(Value::from_bool(
+                self.eval_expr(left, line)?.is_true() && self.eval_expr(right, line)?.is_true(),
+            )),
+            Expr::Binary("||", left, right) => Ok(Value::from_bool(
+                self.eval_expr(left, line)?.is_true() || self.eval_expr(right, line)?.is_true(),
+            )),
+            Expr::Binary(operator, left, right) => {
+                let left = self.eval_expr(left, line)?;
+                let right = self.eval_expr(right, line)?;
+
+                binary(operator, left, right).map_err(|err| error_at(line, err))
+            }
+            Expr::Call(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.eval_expr(argument, line))
+                    .collect::<Result<Vec<Value>, _>>()?;
+
+                self.call(name, arguments, line, None)
+            }
+        }
+    }
+
+    fn call(
+        &mut self,
+        name: &str,
+        arguments: Vec<Value>,
+        line: &SourceLine,
+        output: Option<&mut Vec<SourceLine>>,
+    ) -> Result<Value, errors::FromPlantumlError> {
+        if name.starts_with('%') {
+            return self.builtin(name, arguments, line);
+        }
+
+        let function = self
+            .functions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| error_at(line, format!("undefined function `{}`", name)))?;
+
+        if arguments.len() > function.params.len() {
+            return Err(error_at(
+                line,
+                format!(
+                    "too many arguments for `{}`: expected {}, got {}",
+                    name,
+                    function.params.len(),
+                    arguments.len()
+                ),
+            ));
+        }
+
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(error_at(
+                line,
+                format!("call depth of `{}` exceeds {}", name, MAX_CALL_DEPTH),
+            ));
+        }
+
+        let mut frame = HashMap::new();
+        let mut arguments = arguments.into_iter();
+
+        for param in function.params.iter() {
+            let value = match (arguments.next(), &param.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => self.eval(default, line)?,
+                (None, None) => {
+                    return Err(error_at(
+                        line,
+                        format!("missing argument `{}` for `{}`", param.name, name),
+                    ));
+                }
+            };
+
+            frame.insert(param.name.clone(), value);
+        }
+
+        let mut body_output = vec![];
+
+        self.frames.push(frame);
+        self.depth += 1;
+        let flow = self.run(&function.body, &mut body_output);
+        self.depth -= 1;
+        self.frames.pop();
+
+        let flow = flow?;
+
+        if function.procedure {
+            let text = include::join_lines(&body_output);
+
+            if let Some(output) = output {
+                output.extend(body_output);
+            }
+
+            return Ok(Value::Str(text));
+        }
+
+        match flow {
+            Flow::Return(value) => Ok(value),
+            Flow::Next => Err(error_at(
+                line,
+                format!("function `{}` finished without `!return`", name),
+            )),
+        }
+    }
+
+    fn builtin(
+        &mut self,
+        name: &str,
+        arguments: Vec<Value>,
+        line: &SourceLine,
+    ) -> Result<Value, errors::FromPlantumlError> {
+        let argument = |index: usize| -> Result<&Value, errors::FromPlantumlError> {
+            arguments.get(index).ok_or_else(|| {
+                error_at(
+                    line,
+                    format!("missing argument {} of `{}`", index + 1, name),
+                )
+            })
+        };
+        let int = |index: usize| -> Result<i64, errors::FromPlantumlError> {
+            argument(index)?.as_int().ok_or_else(|| {
+                error_at(
+                    line,
+                    format!("argument {} of `{}` is not a number", index + 1, name),
+                )
+            })
+        };
+
+        let value =
+            match name {
+                "%strlen" => Value::Int(argument(0)?.to_string().chars().count() as i64),
+                "%substr" => {
+                    let s = argument(0)?.to_string();
+                    let start = int(1)?.max(0) as usize;
+                    let chars = s.chars().skip(start);
+
+                    Value::Str(match arguments.get(2) {
+                        Some(_) => chars.take(int(2)?.max(0) as usize).collect(),
+                        None => chars.collect(),
+                    })
+                }
+                "%strpos" => {
+                    let s = argument(0)?.to_string();
+                    let needle = argument(1)?.to_string();
+
+                    Value::Int(match s.find(&needle) {
+                        Some(byte) => s[..byte].chars().count() as i64,
+                        None => -1,
+                    })
+                }
+                "%upper" => Value::Str(argument(0)?.to_string().to_uppercase()),
+                "%lower" => Value::Str(argument(0)?.to_string().to_lowercase()),
+                "%string" => Value::Str(argument(0)?.to_string()),
+                "%intval" => Value::Int(argument(0)?.as_int().ok_or_else(|| {
+                    error_at(line, format!("`{}` is not a number", arguments[0]))
+                })?),
+                "%boolval" => Value::from_bool(argument(0)?.is_true()),
+                "%not" => Value::from_bool(!argument(0)?.is_true()),
+                "%true" => Value::Int(1),
+                "%false" => Value::Int(0),
+                "%newline" => Value::Str("\n".to_string()),
+                "%chr" => Value::Str(
+                    char::from_u32(int(0)? as u32)
+                        .map(String::from)
+                        .unwrap_or_default(),
+                ),
+                "%ord" => Value::Int(
+                    argument(0)?
+                        .to_string()
+                        .chars()
+                        .next()
+                        .map_or(0, |c| c as i64),
+                ),
+                "%dec2hex" => Value::Str(format!("{:x}", int(0)?)),
+                "%hex2dec" => Value::Int(
+                    i64::from_str_radix(argument(0)?.to_string().trim(), 16).map_err(|_| {
+                        error_at(line, format!("`{}` is not a hex number", arguments[0]))
+                    })?,
+                ),
+                "%size" => Value::Int(match argument(0)? {
+                    Value::Array(a) => a.len() as i64,
+                    other => other.to_string().chars().count() as i64,
+                }),
+                "%splitstr" => {
+                    let s = argument(0)?.to_string();
+                    let separator = argument(1)?.to_string();
+
+                    Value::Array(
+                        s.split(separator.as_str())
+                            .map(|part| Value::Str(part.to_string()))
+                            .collect(),
+                    )
+                }
+                "%variable_exists" => {
+                    Value::from_bool(self.variable(&argument(0)?.to_string()).is_some())
+                }
+                "%function_exists" => {
+                    Value::from_bool(self.functions.contains_key(&argument(0)?.to_string()))
+                }
+                "%get_variable_value" => {
+                    let variable = argument(0)?.to_string();
+
+                    self.variable(&variable)
+                        .cloned()
+                        .unwrap_or(Value::Str(String::new()))
+                }
+                "%set_variable_value" => {
+                    let variable = argument(0)?.to_string();
+                    let value = argument(1)?.clone();
+
+                    self.assign(&variable, value, false);
+
+                    Value::Str(String::new())
+                }
+                "%call_user_func" => {
+                    let function = argument(0)?.to_string();
+
+                    self.call(&function, arguments[1..].to_vec(), line, None)?
+                }
+                "%filename" => Value::Str(
+                    Path::new(&line.origin)
+                        .file_name()
+                        .map(|f| f.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                ),
+                "%dirpath" => Value::Str(
+                    Path::new(&line.origin)
+                        .parent()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default(),
+                ),
+                _ => {
+                    return Err(error_at(
+                        line,
+                        format!("unknown builtin function `{}`", name),
+                    ))
+                }
+            };
+
+        Ok(value)
+    }
+
+    fn text(
+        &mut self,
+        line: &SourceLine,
+        output: &mut Vec<SourceLine>,
+    ) -> Result<(), errors::FromPlantumlError> {
+        let expanded = self.expand_defines(&line.text);
+        let trimmed = expanded.trim();
+
+        if let Some(open) = trimmed.find('(') {
+            let name = &trimmed[..open];
+
+            if let (Some(function), Some(close)) = (
+                self.functions.get(name).cloned(),
+                matching_paren(trimmed, open),
+            ) {
+                if function.procedure && close + 1 == trimmed.len() {
+                    let arguments = self.arguments(&trimmed[open + 1..close], line)?;
+                    self.call(name, arguments, line, Some(output))?;
+
+                    return Ok(());
+                }
+            }
+        }
+
+        let substituted = self.substitute(&expanded, line)?;
+
+        for text in substituted.split('\n') {
+            output.push(SourceLine {
+                text: text.to_string(),
+                origin: line.origin.clone(),
+                line: line.line,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn arguments(
+        &mut self,
+        text: &str,
+        line: &SourceLine,
+    ) -> Result<Vec<Value>, errors::FromPlantumlError> {
+        split_arguments(text)
+            .into_iter()
+            .map(|argument| self.eval(argument, line))
+            .collect()
+    }
+
+    fn substitute(
+        &mut self,
+        text: &str,
+        line: &SourceLine,
+    ) -> Result<String, errors::FromPlantumlError> {
+        let mut result = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find(|c: char| c == '$' || c == '%' || is_identifier_char(c)) {
+            let previous = rest[..start].chars().last();
+
+            result += &rest[..start];
+            rest = &rest[start..];
+
+            let length = rest
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| !is_identifier_char(*c))
+                .map(|(index, _)| index)
+                .unwrap_or(rest.len());
+            let name = &rest[..length];
+
+            if previous.is_some_and(is_identifier_char)
+                || length == 1 && !is_identifier_char(name.chars().next().unwrap_or(' '))
+            {
+                result += name;
+                rest = &rest[length..];
+                continue;
+            }
+
+            let is_callable = name.starts_with('%') || self.functions.contains_key(name);
+
+            if is_callable && rest[length..].starts_with('(') {
+                if let Some(close) = matching_paren(rest, length) {
+                    let arguments = self.arguments(&rest[length + 1..close], line)?;
+
+                    result += &self.call(name, arguments, line, None)?.to_string();
+                    rest = &rest[close + 1..];
+                    continue;
+                }
+            }
+
+            match self.variable(name) {
+                Some(value) if name.starts_with('$') => result += &value.to_string(),
+                _ => result += name,
+            }
+
+            rest = &rest[length..];
+        }
+
+        result += rest;
+
+        Ok(result)
+    }
+
+    fn expand_defines(&self, text: &str) -> String {
+        let mut result = text.to_string();
+
+        for define in self.defines.iter() {
+            result = expand_define(&result, define);
+        }
+
+        result
+    }
+}
+
+fn expand_define(text: &str, define: &Define) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(define.name.as_str()) {
+        let end = start + define.name.len();
+        let before = rest[..start].chars().last();
+        let after = rest[end..].chars().next();
+
+        if before.is_some_and(is_identifier_char) || after.is_some_and(is_identifier_char) {
+            result += &rest[..end];
+            rest = &rest[end..];
+            continue;
+        }
+
+        result += &rest[..start];
+
+        match &define.params {
+            None => {
+                result += &define.body;
+                rest = &rest[end..];
+            }
+            Some(params) => match (after, matching_paren(rest, end)) {
+                (Some('('), Some(close)) => {
+                    let arguments = split_arguments(&rest[end + 1..close]);
+                    let mut body = define.body.clone();
+
+                    for (param, argument) in params.iter().zip(arguments.iter()) {
+                        body = replace_word(&body, param, argument);
+                    }
+
+                    result += &body.replace("##", "");
+                    rest = &rest[close + 1..];
+                }
+                _ => {
+                    result += &rest[start..end];
+                    rest = &rest[end..];
+                }
+            },
+        }
+    }
+
+    result += rest;
+
+    result
+}
+
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(word) {
+        let end = start + word.len();
+        let before = rest[..start].chars().last();
+        let after = rest[end..].chars().next();
+
+        result += &rest[..start];
+        result += if before.is_some_and(is_identifier_char) || after.is_some_and(is_identifier_char)
+        {
+            word
+        } else {
+            replacement
+        };
+        rest = &rest[end..];
+    }
+
+    result += rest;
+
+    result
+}
+
+fn binary(operator: &str, left: Value, right: Value) -> Result<Value, String> {
+    let numbers = match (&left, &right) {
+        (Value::Int(l), Value::Int(r)) => Some((*l, *r)),
+        _ => None,
+    };
+
+    let value = match (operator, numbers) {
+        ("+", Some((l, r))) => Value::Int(l.checked_add(r).ok_or("integer overflow")?),
+        ("+", None) => Value::Str(left.to_string() + &right.to_string()),
+        ("==", _) => Value::from_bool(left.to_string() == right.to_string()),
+        ("!=", _) => Value::from_bool(left.to_string() != right.to_string()),
+        ("<", Some((l, r))) => Value::from_bool(l < r),
+        (">", Some((l, r))) => Value::from_bool(l > r),
+        ("<=", Some((l, r))) => Value::from_bool(l <= r),
+        (">=", Some((l, r))) => Value::from_bool(l >= r),
+        ("<", None) => Value::from_bool(left.to_string() < right.to_string()),
+        (">", None) => Value::from_bool(left.to_string() > right.to_string()),
+        ("<=", None) => Value::from_bool(left.to_string() <= right.to_string()),
+        (">=", None) => Value::from_bool(left.to_string() >= right.to_string()),
+        ("/", Some((_, 0))) | ("%", Some((_, 0))) => return Err("division by zero".to_string()),
+        (operator, _) => {
+            let (l, r) = match (left.as_int(), right.as_int()) {
+                (Some(l), Some(r)) => (l, r),
+                _ => {
+                    return Err(format!(
+                        "`{}` expects numbers, got `{}` and `{}`",
+                        operator, left, right
+                    ))
+                }
+            };
+
+            let value = match operator {
+                "-" => l.checked_sub(r),
+                "*" => l.checked_mul(r),
+                "/" if r != 0 => l.checked_div(r),
+                "%" if r != 0 => l.checked_rem(r),
+                _ => return Err("division by zero".to_string()),
+            };
+
+            Value::Int(value.ok_or("integer overflow")?)
+        }
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{preprocess_plantuml, Preprocessor};
+
+    use crate::errors;
+    use crate::include::IncludeResolver;
+    use crate::tests::fs::temp_dir;
+
+    #[test]
+    fn it_preprocess_variables_and_defines() {
+        assert_eq!(
+            preprocess_plantuml(
+                "!define SERVICE(name) participant name <<service>>\n!$user = \"Alice\"\n!$user ?= \"Bob\"\nSERVICE(Api)\n$user -> Api"
+            ),
+            Ok("participant Api <<service>>\nAlice -> Api".to_string())
+        );
+    }
+
+    #[test]
+    fn it_preprocess_conditions() {
+        assert_eq!(
+            preprocess_plantuml(
+                "!$n = 3\n!if $n > 5\nbig\n!elseif $n == 3 && %strlen(\"ab\") == 2\nthree\n!else\nsmall\n!endif\n!ifndef UNKNOWN\nundefined\n!endif"
+            ),
+            Ok("three\nundefined".to_string())
+        );
+    }
+
+    #[test]
+    fn it_preprocess_functions_and_procedures() {
+        assert_eq!(
+            preprocess_plantuml(
+                "!function $arrow($from, $to, $label = \"calls\")\n!return $from + \" -> \" + $to + \": \" + $label\n!endfunction\n!procedure $pair($a, $b)\n$arrow($a, $b)\n$arrow($b, $a, \"replies\")\n!endprocedure\n$pair(\"A\", \"B\")"
+            ),
+            Ok("A -> B: calls\nB -> A: replies".to_string())
+        );
+    }
+
+    #[test]
+    fn it_preprocess_foreach_and_builtins() {
+        assert_eq!(
+            preprocess_plantuml(
+                "!foreach $name in %splitstr(\"api,db\", \",\")\nparticipant %upper($name)\n!endfor\nnote: %substr(\"plantuml\", 5)%newline()end"
+            ),
+            Ok("participant API\nparticipant DB\nnote: uml\nend".to_string())
+        );
+    }
+
+    #[test]
+    fn it_preprocess_line_mapping() {
+        let dir = temp_dir("preprocessor_mapping");
+        fs::write(dir.join("common.iuml"), "!$x = 1\nCOMMON").unwrap();
+
+        let preprocessed = Preprocessor::new(IncludeResolver::new(&dir))
+            .process("@startuml\n!include common.iuml\nA -> B\n@enduml")
+            .unwrap();
+
+        let common = dir.join("common.iuml").display().to_string();
+
+        assert_eq!(preprocessed.source(), "@startuml\nCOMMON\nA -> B\n@enduml");
+        assert_eq!(preprocessed.origin(2), Some((common.as_str(), 2)));
+        assert_eq!(preprocessed.origin(3), Some(("<input>", 3)));
+        assert_eq!(preprocessed.origin(5), None);
+    }
+
+    #[test]
+    fn it_preprocess_undefined_variable_error() {
+        assert_eq!(
+            preprocess_plantuml("A -> B\n!$a = $b + 1"),
+            Err(errors::FromPlantumlError(
+                "undefined variable `$b` (<input>:2)".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_preprocess_missing_endif_error() {
+        assert_eq!(
+            preprocess_plantuml("!if 1\nA -> B"),
+            Err(errors::FromPlantumlError(
+                "missing `!endif` for `!if` (<input>:1)".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_preprocess_integer_overflow_error() {
+        for expr in [
+            "9223372036854775807 + 1",
+            "-9223372036854775807 - 2",
+            "9223372036854775807 * 2",
+            "(-9223372036854775807 - 1) / -1",
+            "-(-9223372036854775807 - 1)",
+        ] {
+            assert_eq!(
+                preprocess_plantuml(format!("!$x = {}\nA -> B: $x", expr)),
+                Err(errors::FromPlantumlError(
+                    "integer overflow (<input>:1)".to_string()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn it_preprocess_expression_nesting_error() {
+        let nested = format!("!$x = {}1{}", "(".repeat(100_000), ")".repeat(100_000));
+
+        assert_eq!(
+            preprocess_plantuml(nested),
+            Err(errors::FromPlantumlError(
+                "expression nesting exceeds 256 (<input>:1)".to_string()
+            ))
+        );
+        assert_eq!(
+            preprocess_plantuml(format!("!$x = 1{}", "+1".repeat(200_000))),
+            Err(errors::FromPlantumlError(
+                "expression nesting exceeds 256 (<input>:1)".to_string()
+            ))
+        );
+        assert_eq!(
+            preprocess_plantuml(format!("!$x = 1{}\nA -> B: $x", "+1".repeat(100))),
+            Ok("A -> B: 101".to_string())
+        );
+        assert_eq!(
+            preprocess_plantuml(format!("!$x = {}1\nA -> B: $x", "-".repeat(200))),
+            Ok("A -> B: 1".to_string())
+        );
+    }
+
+    #[test]
+    fn it_preprocess_assert_error() {
+        assert_eq!(
+            preprocess_plantuml("!$v = 2\n!assert $v == 1 : \"unexpected version \" + $v"),
+            Err(errors::FromPlantumlError(
+                "unexpected version 2 (<input>:2)".to_string()
+            ))
+        );
+    }
+}
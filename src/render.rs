@@ -0,0 +1,45 @@
+use crate::errors;
+use crate::url;
+
+/// Diagram rendered by a [`Renderer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendered {
+    /// Format of the output
+    pub format: url::OutputFormat,
+    /// Media type of the output (`image/svg+xml` and so on)
+    pub content_type: String,
+    /// Output bytes
+    pub bytes: Vec<u8>,
+}
+
+/// Something that renders plantuml sources (plantuml server, local plantuml.jar, cache...)
+pub trait Renderer {
+    /// Render the plantuml source to the format
+    fn render(
+        &self,
+        plantuml: &str,
+        format: url::OutputFormat,
+    ) -> Result<Rendered, errors::FromPlantumlError>;
+
+    /// Identity of the renderer (server, command...), renderers with the same identity
+    /// render sources to the same output
+    fn identity(&self) -> String;
+}
+
+/// Media type of the format
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{content_type, OutputFormat};
+///
+/// assert_eq!(content_type(OutputFormat::Svg), "image/svg+xml");
+/// ```
+pub fn content_type(format: url::OutputFormat) -> &'static str {
+    match format {
+        url::OutputFormat::Png => "image/png",
+        url::OutputFormat::Svg => "image/svg+xml",
+        url::OutputFormat::Txt => "text/plain",
+        url::OutputFormat::Uml => "text/html",
+    }
+}
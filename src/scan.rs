@@ -0,0 +1,365 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::errors;
+use crate::kind;
+use crate::url;
+use crate::utils;
+
+const MIN_DEFLATE_LENGTH: usize = 16;
+
+/// How the encoded plantuml was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoundKind {
+    /// Plantuml server URL
+    Url,
+    /// Bare hex payload with `~h` prefix
+    Hex,
+    /// Bare deflate payload
+    Deflate,
+}
+
+impl FoundKind {
+    /// Name of the kind (`url`, `hex` or `deflate`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FoundKind::Url => "url",
+            FoundKind::Hex => "hex",
+            FoundKind::Deflate => "deflate",
+        }
+    }
+}
+
+/// Encoded plantuml found in a text
+#[derive(Debug, PartialEq)]
+pub struct Found {
+    /// How the plantuml was found
+    pub kind: FoundKind,
+    /// Byte span of the URL or the payload in the text
+    pub span: Range<usize>,
+    /// Line of the span start (1-based)
+    pub line: usize,
+    /// Column of the span start in characters (1-based)
+    pub column: usize,
+    /// Parsed URL (for [`FoundKind::Url`])
+    pub url: Option<url::PlantumlUrl>,
+    /// Encoded plantuml
+    pub encoded: String,
+    /// Decoded plantuml or decoding error
+    pub decoded: Result<String, errors::FromPlantumlError>,
+}
+
+impl Found {
+    /// Whether the plantuml can not be decoded
+    pub fn is_broken(&self) -> bool {
+        self.decoded.is_err()
+    }
+}
+
+/// Find plantuml server URLs and bare `~h` / deflate payloads in any text
+/// (markdown, asciidoc, reStructuredText, HTML, source comments...) and decode them.
+///
+/// Bare deflate payloads can not be told apart from other long tokens,
+/// so they are reported only if they decode to a wrapped plantuml.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{scan_plantuml, FoundKind};
+///
+/// let text = "<img src=\"https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000\">\n\
+///             bare: ~h4073";
+///
+/// let found = scan_plantuml(text);
+///
+/// assert_eq!(found.len(), 2);
+/// assert_eq!(found[0].kind, FoundKind::Url);
+/// assert_eq!(found[0].decoded.as_deref(), Ok("@startuml\nPUML -> RUST\n@enduml"));
+/// assert_eq!((found[1].kind, found[1].line, found[1].column), (FoundKind::Hex, 2, 7));
+/// assert!(!found[1].is_broken());
+/// ```
+pub fn scan_plantuml<T: AsRef<str>>(text: T) -> Vec<Found> {
+    let text = text.as_ref();
+    let mut found: Vec<Found> = vec![];
+
+    for span in url_spans(text) {
+        if let Some(plantuml_url) = url::PlantumlUrl::parse(&text[span.clone()]) {
+            found.push(new_found(
+                text,
+                FoundKind::Url,
+                span,
+                decode(&plantuml_url.encoded),
+                plantuml_url.encoded.clone(),
+                Some(plantuml_url),
+            ));
+        }
+    }
+
+    let urls: Vec<Range<usize>> = found.iter().map(|f| f.span.clone()).collect();
+
+    for span in tokens(text) {
+        if urls
+            .iter()
+            .any(|u| u.start <= span.start && span.end <= u.end)
+        {
+            continue;
+        }
+
+        let token = &text[span.clone()];
+
+        if let Some(hex) = token.strip_prefix("~h") {
+            if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                let decoded = decode(token);
+
+                found.push(new_found(
+                    text,
+                    FoundKind::Hex,
+                    span,
+                    decoded,
+                    token.to_string(),
+                    None,
+                ));
+            }
+        } else if token.starts_with("~1") || token.len() >= MIN_DEFLATE_LENGTH {
+            let decoded = decode(token);
+            let explicit = token.starts_with("~1");
+
+            if explicit || decoded.as_ref().is_ok_and(kind::is_wrapped_plantuml) {
+                found.push(new_found(
+                    text,
+                    FoundKind::Deflate,
+                    span,
+                    decoded,
+                    token.to_string(),
+                    None,
+                ));
+            }
+        }
+    }
+
+    found.sort_by_key(|f| f.span.start);
+
+    found
+}
+
+/// Encoded plantuml found in a file
+#[derive(Debug, PartialEq)]
+pub struct ScannedFile {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Found plantuml
+    pub found: Vec<Found>,
+}
+
+/// Scan files and directories (recursively, skipping hidden ones) with [`scan_plantuml`].
+///
+/// Files that are not UTF-8 text are skipped, files without plantuml are not returned.
+pub fn scan_plantuml_files<I, P>(paths: I) -> Result<Vec<ScannedFile>, errors::FromPlantumlError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut scanned = vec![];
+
+    for path in paths {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            utils::walk_files(path, true)?
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        for file in files {
+            let bytes = fs::read(&file).map_err(|err| {
+                errors::FromPlantumlError(format!(
+                    "there is a problem during reading `{}`: `{}`",
+                    file.display(),
+                    err
+                ))
+            })?;
+
+            let text = match String::from_utf8(bytes) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+
+            let found = scan_plantuml(text);
+
+            if !found.is_empty() {
+                scanned.push(ScannedFile { path: file, found });
+            }
+        }
+    }
+
+    Ok(scanned)
+}
+
+/// Strictly decoded payload, so that truncated or garbage streams are broken
+fn decode(encoded: &str) -> Result<String, errors::FromPlantumlError> {
+    url::decode_plantuml_strict(encoded).map_err(|(_, message)| {
+        errors::FromPlantumlError(format!("there is a problem during decoding: `{}`", message))
+    })
+}
+
+fn new_found(
+    text: &str,
+    kind: FoundKind,
+    span: Range<usize>,
+    decoded: Result<String, errors::FromPlantumlError>,
+    encoded: String,
+    url: Option<url::PlantumlUrl>,
+) -> Found {
+    let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+
+    Found {
+        kind,
+        line: text[..span.start].matches('\n').count() + 1,
+        column: text[line_start..span.start].chars().count() + 1,
+        span,
+        url,
+        encoded,
+        decoded,
+    }
+}
+
+/// Spans of `http(s)://` URLs of the text (without trailing punctuation)
+pub(crate) fn url_spans(text: &str) -> Vec<Range<usize>> {
+    let mut starts: Vec<usize> = ["http://", "https://"]
+        .iter()
+        .flat_map(|scheme| text.match_indices(scheme).map(|(i, _)| i))
+        .collect();
+
+    starts.sort();
+
+    starts
+        .into_iter()
+        .map(|start| {
+            let end = text[start..]
+                .find(|c: char| c.is_whitespace() || "\"'<>()[]{}|\\^`".contains(c))
+                .map_or(text.len(), |i| start + i);
+
+            start
+                ..start
+                    + text[start..end]
+                        .trim_end_matches(['.', ',', ';', ':', '!', '?'])
+                        .len()
+        })
+        .collect()
+}
+
+fn tokens(text: &str) -> Vec<Range<usize>> {
+    let is_token = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '~';
+    let mut tokens = vec![];
+    let mut start = None;
+
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        match (start, is_token(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                tokens.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{scan_plantuml, scan_plantuml_files, FoundKind};
+
+    use crate::errors;
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_hex_str::PLANTUML_HEX_SMALL,
+        plantuml_str::PLANTUML_SMALL,
+    };
+    use crate::tests::fs::temp_dir;
+
+    #[test]
+    fn it_scan_plantuml_urls() {
+        let text = format!(
+            ".. image:: http://localhost:8080/png/{deflate}.\n\nimage::https://www.plantuml.com/plantuml/svg/{hex}[]\n<a href='https://example.com/svg/'>x</a>",
+            deflate = PLANTUML_DEFLATED_SMALL,
+            hex = PLANTUML_HEX_SMALL
+        );
+
+        let found = scan_plantuml(&text);
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].kind, FoundKind::Url);
+        assert_eq!((found[0].line, found[0].column), (1, 12));
+        assert_eq!(
+            &text[found[0].span.clone()],
+            format!("http://localhost:8080/png/{}", PLANTUML_DEFLATED_SMALL)
+        );
+        assert_eq!(found[0].decoded, Ok(PLANTUML_SMALL.to_string()));
+        assert_eq!(found[1].encoded, PLANTUML_HEX_SMALL);
+        assert_eq!((found[1].line, found[1].column), (3, 8));
+    }
+
+    #[test]
+    fn it_scan_plantuml_skips_other_urls() {
+        let found = scan_plantuml(
+            "![logo](https://cdn.example.com/assets/svg/logo)\n![broken](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8)",
+        );
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].line, 2);
+        assert!(found[0].is_broken());
+    }
+
+    #[test]
+    fn it_scan_plantuml_bare_payloads() {
+        let text = format!(
+            "// diagram: {}\n# {} and not_a_diagram_but_a_long_identifier ~1SoWk ~h1",
+            PLANTUML_DEFLATED_SMALL, PLANTUML_HEX_SMALL
+        );
+
+        let found = scan_plantuml(text);
+
+        assert_eq!(
+            found.iter().map(|f| f.kind).collect::<Vec<_>>(),
+            vec![
+                FoundKind::Deflate,
+                FoundKind::Hex,
+                FoundKind::Deflate,
+                FoundKind::Hex
+            ]
+        );
+        assert_eq!(found[0].decoded, Ok(PLANTUML_SMALL.to_string()));
+        assert!(found[2].is_broken());
+        assert!(scan_plantuml("~1SoWkIImgAStDuGe8")[0].is_broken());
+        assert_eq!(
+            found[3].decoded,
+            Err(errors::FromPlantumlError(
+                "there is a problem during decoding: `Odd number of digits`".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_scan_plantuml_files() {
+        let dir = temp_dir("scan_files");
+
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join("docs/a.adoc"), PLANTUML_HEX_SMALL).unwrap();
+        fs::write(dir.join(".git/b.txt"), PLANTUML_HEX_SMALL).unwrap();
+        fs::write(dir.join("c.bin"), [0xff, 0xfe, 0x00]).unwrap();
+        fs::write(dir.join("d.txt"), "nothing").unwrap();
+
+        let scanned = scan_plantuml_files([&dir]).unwrap();
+
+        assert_eq!(scanned.len(), 1);
+        assert_eq!(scanned[0].path, dir.join("docs/a.adoc"));
+        assert!(scan_plantuml_files([dir.join("missing")]).is_err());
+    }
+}
@@ -0,0 +1,975 @@
+use crate::errors;
+use crate::kind;
+use crate::minify;
+use crate::url;
+
+/// Keywords declaring sequence participants (usecase and component diagrams use some of them too)
+const PARTICIPANT_KEYWORDS: [&str; 8] = [
+    "participant",
+    "actor",
+    "boundary",
+    "control",
+    "entity",
+    "database",
+    "collections",
+    "queue",
+];
+
+/// Keywords declaring elements of class-like diagrams
+const ELEMENT_KEYWORDS: [&str; 18] = [
+    "abstract class",
+    "abstract",
+    "class",
+    "interface",
+    "enum",
+    "annotation",
+    "struct",
+    "exception",
+    "protocol",
+    "component",
+    "node",
+    "usecase",
+    "object",
+    "artifact",
+    "cloud",
+    "folder",
+    "storage",
+    "rectangle",
+];
+
+const DIRECTIONS: [&str; 8] = ["up", "down", "left", "right", "u", "d", "l", "r"];
+
+/// What was changed in the diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ItemKind {
+    /// Participant of a sequence diagram
+    Participant,
+    /// Message (arrow) of a sequence diagram
+    Message,
+    /// Class, interface, enum, component and other elements of class-like diagrams
+    Element,
+    /// Relation (arrow) between elements
+    Relation,
+    /// Field or method of an element (`Owner: member`)
+    Member,
+    /// Any other line (notes, titles, skinparams, lines of other diagram types)
+    Statement,
+}
+
+impl ItemKind {
+    /// Name of the item kind (`participant`, `message` and so on)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::Participant => "participant",
+            ItemKind::Message => "message",
+            ItemKind::Element => "element",
+            ItemKind::Member => "member",
+            ItemKind::Relation => "relation",
+            ItemKind::Statement => "statement",
+        }
+    }
+}
+
+/// How the item was changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKind {
+    /// Item exists only in the new diagram
+    Added,
+    /// Item exists only in the old diagram
+    Removed,
+    /// Participant or element got another name (or label)
+    Renamed,
+    /// Participant or element got another keyword (`participant` to `actor` and so on)
+    Changed,
+}
+
+/// Single change of [`DiagramDiff`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// What was changed
+    pub item: ItemKind,
+    /// How it was changed
+    pub kind: ChangeKind,
+    /// Item in the old diagram (none for added items)
+    pub old: Option<String>,
+    /// Item in the new diagram (none for removed items)
+    pub new: Option<String>,
+}
+
+/// Structured difference of two diagrams made by [`diff_plantuml`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagramDiff {
+    /// Kind of the old diagram
+    pub old_kind: kind::DiagramKind,
+    /// Kind of the new diagram
+    pub new_kind: kind::DiagramKind,
+    /// Changes (participants and elements first, then arrows, members and other lines)
+    pub changes: Vec<Change>,
+}
+
+impl DiagramDiff {
+    /// Whether the diagrams are the same (ignoring whitespace and comments)
+    pub fn is_empty(&self) -> bool {
+        self.old_kind == self.new_kind && self.changes.is_empty()
+    }
+
+    /// Number of changes of the item kind and the change kind
+    pub fn count(&self, item: ItemKind, kind: ChangeKind) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| change.item == item && change.kind == kind)
+            .count()
+    }
+
+    /// Human readable summary like `3 new messages, 1 participant renamed`
+    pub fn summary(&self) -> String {
+        let mut counts: Vec<((ItemKind, ChangeKind), usize)> = vec![];
+
+        for change in self.changes.iter() {
+            let key = (change.item, change.kind);
+
+            match counts.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((key, 1)),
+            }
+        }
+
+        counts.sort_by_key(|(key, _)| *key);
+
+        let mut parts: Vec<String> = counts
+            .into_iter()
+            .map(|((item, kind), count)| {
+                let name = if count == 1 {
+                    item.as_str().to_string()
+                } else {
+                    format!("{}s", item.as_str())
+                };
+
+                match kind {
+                    ChangeKind::Added => format!("{} new {}", count, name),
+                    ChangeKind::Removed => format!("{} {} removed", count, name),
+                    ChangeKind::Renamed => format!("{} {} renamed", count, name),
+                    ChangeKind::Changed => format!("{} {} changed", count, name),
+                }
+            })
+            .collect();
+
+        if self.old_kind != self.new_kind {
+            parts.insert(
+                0,
+                format!(
+                    "diagram type changed from {} to {}",
+                    self.old_kind.tag(),
+                    self.new_kind.tag()
+                ),
+            );
+        }
+
+        if parts.is_empty() {
+            "no changes".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Decode two encoded plantuml (plantuml server URLs, `~h` hex or deflate payloads)
+/// and compare them with [`diff_plantuml_sources`]
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{diff_plantuml, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let diff = diff_plantuml(
+///         "https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000",
+///         "~h407374617274756d6c0a50554d4c202d3e20525553540a52555354202d3e2050554d4c0a40656e64756d6c",
+///     )?;
+///
+///     assert_eq!(diff.summary(), "1 new message");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn diff_plantuml<O: AsRef<str>, N: AsRef<str>>(
+    old: O,
+    new: N,
+) -> Result<DiagramDiff, errors::FromPlantumlError> {
+    Ok(diff_plantuml_sources(
+        decode(old.as_ref())?,
+        decode(new.as_ref())?,
+    ))
+}
+
+/// Compare two plantuml sources ignoring whitespace and comments.
+///
+/// Sequence diagrams are compared by participants and messages, class-like diagrams
+/// (class, component, usecase...) by elements, members and relations, other lines
+/// and other diagram types line by line. A participant or an element is renamed
+/// if its label changed or if all its arrows match arrows of a new one.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{diff_plantuml_sources, ChangeKind, ItemKind};
+///
+/// let diff = diff_plantuml_sources(
+///     "@startuml\nAlice -> Bob: hello\n@enduml",
+///     "@startuml\n' greeting\nAlice -> Carol : hello\nCarol --> Alice: hi\n@enduml",
+/// );
+///
+/// assert_eq!(diff.count(ItemKind::Participant, ChangeKind::Renamed), 1);
+/// assert_eq!(diff.summary(), "1 participant renamed, 1 new message");
+/// ```
+pub fn diff_plantuml_sources<O: AsRef<str>, N: AsRef<str>>(old: O, new: N) -> DiagramDiff {
+    let old = Model::parse(old.as_ref());
+    let new = Model::parse(new.as_ref());
+
+    let mut changes = vec![];
+    let renames = diff_nodes(&old, &new, &mut changes);
+
+    let old_links: Vec<Link> = old.links.iter().map(|l| l.renamed(&renames)).collect();
+    let (removed, added) = multiset_diff(&old_links, &new.links);
+
+    changes.extend(removed.into_iter().map(|i| Change {
+        item: old.link_kind(),
+        kind: ChangeKind::Removed,
+        old: Some(old.links[i].to_string()),
+        new: None,
+    }));
+    changes.extend(added.into_iter().map(|i| Change {
+        item: new.link_kind(),
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(new.links[i].to_string()),
+    }));
+
+    let old_members: Vec<(String, String)> = old
+        .members
+        .iter()
+        .map(|(owner, member)| (rename(owner, &renames), member.clone()))
+        .collect();
+    let (removed, added) = multiset_diff(&old_members, &new.members);
+
+    changes.extend(removed.into_iter().map(|i| Change {
+        item: ItemKind::Member,
+        kind: ChangeKind::Removed,
+        old: Some(format!("{}: {}", old.members[i].0, old.members[i].1)),
+        new: None,
+    }));
+    changes.extend(added.into_iter().map(|i| Change {
+        item: ItemKind::Member,
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(format!("{}: {}", new.members[i].0, new.members[i].1)),
+    }));
+
+    let (removed, added) = multiset_diff(&old.statements, &new.statements);
+
+    changes.extend(removed.into_iter().map(|i| Change {
+        item: ItemKind::Statement,
+        kind: ChangeKind::Removed,
+        old: Some(old.statements[i].clone()),
+        new: None,
+    }));
+    changes.extend(added.into_iter().map(|i| Change {
+        item: ItemKind::Statement,
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(new.statements[i].clone()),
+    }));
+
+    DiagramDiff {
+        old_kind: old.kind,
+        new_kind: new.kind,
+        changes,
+    }
+}
+
+fn decode(encoded: &str) -> Result<String, errors::FromPlantumlError> {
+    let encoded = encoded.trim();
+
+    match url::PlantumlUrl::parse(encoded) {
+        Some(plantuml_url) => plantuml_url.decode(),
+        None => url::decode_plantuml(encoded),
+    }
+}
+
+/// Compare participants / elements, push their changes and return renames (old id, new id)
+fn diff_nodes(old: &Model, new: &Model, changes: &mut Vec<Change>) -> Vec<(String, String)> {
+    let mut renames: Vec<(String, String)> = vec![];
+    let mut removed: Vec<&Node> = vec![];
+
+    for node in old.nodes.iter() {
+        match new.node(&node.id) {
+            Some(other) if other.label != node.label => changes.push(Change {
+                item: new.node_kind(),
+                kind: ChangeKind::Renamed,
+                old: Some(node.label.clone()),
+                new: Some(other.label.clone()),
+            }),
+            Some(other) if other.keyword != node.keyword => changes.push(Change {
+                item: new.node_kind(),
+                kind: ChangeKind::Changed,
+                old: Some(format!("{} {}", node.keyword, node.label)),
+                new: Some(format!("{} {}", other.keyword, other.label)),
+            }),
+            Some(_) => {}
+            None => removed.push(node),
+        }
+    }
+
+    let mut added: Vec<&Node> = new
+        .nodes
+        .iter()
+        .filter(|node| old.node(&node.id).is_none())
+        .collect();
+
+    for node in removed {
+        let links: Vec<&Link> = old
+            .links
+            .iter()
+            .filter(|link| link.from == node.id || link.to == node.id)
+            .collect();
+
+        let candidate = added.iter().position(|other| {
+            let mut renames = renames.clone();
+
+            renames.push((node.id.clone(), other.id.clone()));
+
+            other.keyword == node.keyword
+                && !links.is_empty()
+                && links
+                    .iter()
+                    .all(|link| new.links.contains(&link.renamed(&renames)))
+        });
+
+        match candidate {
+            Some(index) => {
+                let other = added.remove(index);
+
+                changes.push(Change {
+                    item: new.node_kind(),
+                    kind: ChangeKind::Renamed,
+                    old: Some(node.label.clone()),
+                    new: Some(other.label.clone()),
+                });
+                renames.push((node.id.clone(), other.id.clone()));
+            }
+            None => changes.push(Change {
+                item: old.node_kind(),
+                kind: ChangeKind::Removed,
+                old: Some(node.label.clone()),
+                new: None,
+            }),
+        }
+    }
+
+    changes.extend(added.into_iter().map(|node| Change {
+        item: new.node_kind(),
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(node.label.clone()),
+    }));
+
+    renames
+}
+
+/// Indexes of old items without equal new items and new items without equal old items
+fn multiset_diff<T: PartialEq>(old: &[T], new: &[T]) -> (Vec<usize>, Vec<usize>) {
+    let mut matched = vec![false; new.len()];
+    let mut removed = vec![];
+
+    for (i, item) in old.iter().enumerate() {
+        match (0..new.len()).find(|&j| !matched[j] && new[j] == *item) {
+            Some(j) => matched[j] = true,
+            None => removed.push(i),
+        }
+    }
+
+    let added = (0..new.len()).filter(|&j| !matched[j]).collect();
+
+    (removed, added)
+}
+
+fn rename(id: &str, renames: &[(String, String)]) -> String {
+    renames
+        .iter()
+        .find(|(old, _)| old == id)
+        .map_or(id, |(_, new)| new.as_str())
+        .to_string()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    keyword: String,
+    id: String,
+    label: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Link {
+    from: String,
+    from_multiplicity: Option<String>,
+    arrow: String,
+    to_multiplicity: Option<String>,
+    to: String,
+    text: String,
+}
+
+impl Link {
+    fn renamed(&self, renames: &[(String, String)]) -> Link {
+        Link {
+            from: rename(&self.from, renames),
+            to: rename(&self.to, renames),
+            ..self.clone()
+        }
+    }
+}
+
+impl std::fmt::Display for Link {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.from)?;
+
+        if let Some(multiplicity) = &self.from_multiplicity {
+            write!(f, " \"{}\"", multiplicity)?;
+        }
+
+        write!(f, " {} ", self.arrow)?;
+
+        if let Some(multiplicity) = &self.to_multiplicity {
+            write!(f, "\"{}\" ", multiplicity)?;
+        }
+
+        write!(f, "{}", self.to)?;
+
+        if !self.text.is_empty() {
+            write!(f, ": {}", self.text)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parsed line of a diagram
+enum Line {
+    Declaration(Node, bool),
+    Link(Link),
+    Colon(String, String),
+    Statement(String),
+}
+
+/// Participants / elements, arrows, members and other lines of a diagram
+struct Model {
+    kind: kind::DiagramKind,
+    sequence: bool,
+    nodes: Vec<Node>,
+    links: Vec<Link>,
+    members: Vec<(String, String)>,
+    statements: Vec<String>,
+}
+
+impl Model {
+    fn parse(plantuml: &str) -> Model {
+        let kind = kind::DiagramKind::detect(plantuml);
+        let minified = minify::minify_plantuml(plantuml);
+        let lines: Vec<&str> = minified
+            .lines()
+            .filter(|line| {
+                let lowercase = line.to_lowercase();
+
+                !lowercase.starts_with("@start") && !lowercase.starts_with("@end")
+            })
+            .collect();
+
+        let mut model = Model {
+            kind,
+            sequence: true,
+            nodes: vec![],
+            links: vec![],
+            members: vec![],
+            statements: vec![],
+        };
+
+        if kind != kind::DiagramKind::Uml {
+            model.statements = lines.iter().map(|line| line.to_string()).collect();
+
+            return model;
+        }
+
+        let mut parsed = vec![];
+        let mut body: Option<String> = None;
+
+        for line in lines {
+            if let Some(owner) = &body {
+                if line == "}" {
+                    body = None;
+                } else {
+                    parsed.push(Line::Colon(owner.clone(), line.to_string()));
+                }
+
+                continue;
+            }
+
+            let line = match parse_declaration(line) {
+                Some((node, opens_body)) => {
+                    if opens_body {
+                        body = Some(node.id.clone());
+                    }
+
+                    Line::Declaration(node, opens_body)
+                }
+                None => match parse_link(line) {
+                    Some(link) => Line::Link(link),
+                    None => match parse_colon(line) {
+                        Some((owner, member)) => Line::Colon(owner, member),
+                        None => Line::Statement(line.to_string()),
+                    },
+                },
+            };
+
+            parsed.push(line);
+        }
+
+        model.sequence = !parsed.iter().any(|line| match line {
+            Line::Declaration(node, opens_body) => {
+                *opens_body || ELEMENT_KEYWORDS.contains(&node.keyword.as_str())
+            }
+            Line::Link(link) => is_element_link(link),
+            _ => false,
+        });
+
+        for line in parsed {
+            match line {
+                Line::Declaration(node, _) => {
+                    match model.nodes.iter_mut().find(|n| n.id == node.id) {
+                        Some(existing) => *existing = node,
+                        None => model.nodes.push(node),
+                    }
+                }
+                Line::Link(link) => {
+                    for id in [&link.from, &link.to] {
+                        if model.node(id).is_none() {
+                            model.nodes.push(Node {
+                                keyword: "participant".to_string(),
+                                id: id.clone(),
+                                label: id.clone(),
+                            });
+                        }
+                    }
+
+                    model.links.push(link);
+                }
+                Line::Colon(owner, member) if !model.sequence => {
+                    model.members.push((owner, member));
+                }
+                Line::Colon(owner, member) => {
+                    model.statements.push(format!("{} : {}", owner, member));
+                }
+                Line::Statement(statement) => model.statements.push(statement),
+            }
+        }
+
+        if !model.sequence {
+            for node in model.nodes.iter_mut() {
+                if node.keyword == "participant" {
+                    node.keyword = "class".to_string();
+                }
+            }
+        }
+
+        model
+    }
+
+    fn node(&self, id: &str) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    fn node_kind(&self) -> ItemKind {
+        if self.sequence {
+            ItemKind::Participant
+        } else {
+            ItemKind::Element
+        }
+    }
+
+    fn link_kind(&self) -> ItemKind {
+        if self.sequence {
+            ItemKind::Message
+        } else {
+            ItemKind::Relation
+        }
+    }
+}
+
+/// Class-like arrows (`<|--`, `*--`, `o--`, `..>`) or `[component]` / `(usecase)` operands
+fn is_element_link(link: &Link) -> bool {
+    let arrow = link.arrow.as_str();
+
+    arrow.contains('|')
+        || arrow.contains('*')
+        || arrow.contains("..")
+        || arrow.starts_with('o')
+        || arrow.ends_with('o')
+        || link.from_multiplicity.is_some()
+        || link.to_multiplicity.is_some()
+        || [&link.from, &link.to]
+            .iter()
+            .any(|id| id.len() > 1 && (id.starts_with('[') || id.starts_with('(')))
+}
+
+/// `keyword name [as alias] [<<stereotype>>] [#color] [{]`
+fn parse_declaration(line: &str) -> Option<(Node, bool)> {
+    let lowercase = line.to_lowercase();
+    let keyword = ELEMENT_KEYWORDS
+        .iter()
+        .chain(PARTICIPANT_KEYWORDS.iter())
+        .find(|keyword| {
+            lowercase.starts_with(*keyword)
+                && line[keyword.len()..].starts_with(|c: char| c.is_whitespace())
+        })?;
+
+    let rest = line[keyword.len()..].trim_start();
+    let (name, name_quoted, rest) = name_token(rest)?;
+    let rest = rest.trim_start();
+
+    let (id, label) = match rest
+        .strip_prefix("as ")
+        .and_then(|rest| name_token(rest.trim_start()))
+    {
+        Some((alias, true, _)) => (name, alias),
+        Some((alias, false, _)) if name_quoted => (alias, name),
+        Some((alias, false, _)) => (alias, name),
+        None => (name.clone(), name),
+    };
+
+    Some((
+        Node {
+            keyword: keyword.to_string(),
+            id,
+            label,
+        },
+        line.ends_with('{'),
+    ))
+}
+
+fn name_token(text: &str) -> Option<(String, bool, &str)> {
+    if let Some(quoted) = text.strip_prefix('"') {
+        let end = quoted.find('"')?;
+
+        return Some((quoted[..end].to_string(), true, &quoted[end + 1..]));
+    }
+
+    let end = text
+        .find(|c: char| c.is_whitespace() || "<#{".contains(c))
+        .unwrap_or(text.len());
+
+    Some((text[..end].to_string(), false, &text[end..])).filter(|(name, _, _)| !name.is_empty())
+}
+
+/// `from ["multiplicity"] arrow ["multiplicity"] to [: text]`
+fn parse_link(line: &str) -> Option<Link> {
+    let (from, rest) = operand(line)?;
+    let (from_multiplicity, rest) = multiplicity(rest.trim_start());
+    let rest = rest.trim_start();
+
+    let run_end = rest
+        .find(|c: char| c.is_whitespace() || c == '"' || c == ':')
+        .unwrap_or(rest.len());
+    let after = rest[run_end..].trim_start();
+
+    let arrow_end = if after.is_empty() || after.starts_with(':') {
+        rest[..run_end].rfind(|c: char| "-.>|*\\/".contains(c))? + 1
+    } else {
+        run_end
+    };
+
+    let arrow = normalize_arrow(&rest[..arrow_end])?;
+    let (to_multiplicity, rest) = multiplicity(rest[arrow_end..].trim_start());
+    let (to, rest) = operand(rest.trim_start())?;
+
+    let text = match rest.find(':') {
+        Some(colon) => rest[colon + 1..].trim().to_string(),
+        None if rest
+            .split_whitespace()
+            .all(|t| ["++", "--", "**", "!!"].contains(&t)) =>
+        {
+            String::new()
+        }
+        None => return None,
+    };
+
+    Some(Link {
+        from,
+        from_multiplicity,
+        arrow,
+        to_multiplicity,
+        to,
+        text,
+    })
+}
+
+/// `owner : member`
+fn parse_colon(line: &str) -> Option<(String, String)> {
+    let (owner, rest) = operand(line)?;
+    let member = rest.trim_start().strip_prefix(':')?.trim();
+
+    Some((owner, member.to_string())).filter(|(_, member)| !member.is_empty())
+}
+
+/// Name of a participant or an element (`Alice`, `"Long name"`, `[Component]`, `(Use case)`,
+/// `[` / `]` for arrows from / to the outside of sequence diagrams)
+pub(crate) fn operand(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.chars();
+
+    match chars.next()? {
+        '"' => {
+            let end = text[1..].find('"')? + 1;
+
+            Some((text[1..end].to_string(), &text[end + 1..]))
+        }
+        '[' if chars.next().is_some_and(|c| "-.<>ox/\\".contains(c)) => {
+            Some(("[".to_string(), &text[1..]))
+        }
+        ']' => Some(("]".to_string(), &text[1..])),
+        open @ ('[' | '(') => {
+            let close = if open == '[' { ']' } else { ')' };
+            let end = text.find(close)? + 1;
+
+            Some((text[..end].to_string(), &text[end..]))
+        }
+        _ => {
+            let mut end = 0;
+
+            for (i, c) in text.char_indices() {
+                let is_name = c.is_alphanumeric()
+                    || c == '_'
+                    || c == '$'
+                    || (c == '.'
+                        && i > 0
+                        && text[i + 1..].starts_with(|n: char| n.is_alphanumeric()));
+
+                if !is_name {
+                    break;
+                }
+
+                end = i + c.len_utf8();
+            }
+
+            Some((text[..end].to_string(), &text[end..])).filter(|(name, _)| !name.is_empty())
+        }
+    }
+}
+
+pub(crate) fn multiplicity(text: &str) -> (Option<String>, &str) {
+    match text
+        .strip_prefix('"')
+        .and_then(|quoted| quoted.find('"').map(|end| (quoted, end)))
+    {
+        Some((quoted, end)) => (Some(quoted[..end].to_string()), &quoted[end + 1..]),
+        None => (None, text),
+    }
+}
+
+/// Arrow without colors, styles (`[#red,dashed]`) and directions (`-up->`)
+pub(crate) fn normalize_arrow(arrow: &str) -> Option<String> {
+    let mut normalized = String::new();
+    let mut rest = arrow;
+
+    while let Some(open) = rest.find('[') {
+        let close = rest[open..].find(']')? + open;
+
+        normalized.push_str(&rest[..open]);
+        rest = &rest[close + 1..];
+    }
+
+    normalized.push_str(rest);
+
+    let mut result = String::new();
+    let mut word = String::new();
+
+    for c in normalized.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_alphabetic() {
+            word.push(c);
+            continue;
+        }
+
+        if !word.is_empty() {
+            if !DIRECTIONS.contains(&word.to_lowercase().as_str()) {
+                if !matches!(word.as_str(), "o" | "x") {
+                    return None;
+                }
+
+                result.push_str(&word);
+            }
+
+            word.clear();
+        }
+
+        if c != ' ' {
+            result.push(c);
+        }
+    }
+
+    let valid = result.contains(['-', '.']) && result.chars().all(|c| "-.<>|*ox#/\\+^".contains(c));
+
+    Some(result).filter(|_| valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_plantuml, diff_plantuml_sources, Change, ChangeKind, ItemKind};
+
+    use crate::errors;
+    use crate::kind::DiagramKind;
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_hex_str::PLANTUML_HEX_SMALL,
+    };
+
+    fn change(item: ItemKind, kind: ChangeKind, old: Option<&str>, new: Option<&str>) -> Change {
+        Change {
+            item,
+            kind,
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn it_diff_plantuml_same_diagram() {
+        let diff = diff_plantuml(PLANTUML_DEFLATED_SMALL, PLANTUML_HEX_SMALL).unwrap();
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.summary(), "no changes");
+        assert!(diff_plantuml_sources(
+            "@startuml\n' comment\nA -> B : hi\n@enduml",
+            "@startuml\n  A->B: hi  \n\n@enduml\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn it_diff_plantuml_sequence() {
+        let old = "@startuml\nparticipant \"Web shop\" as Shop\nactor User\ndatabase DB\nUser -> Shop: order\nShop -> DB: save\nShop -[#red]-> User: done\n@enduml";
+        let new = "@startuml\nparticipant \"Online shop\" as Shop\nparticipant User\nqueue Bus\nUser -> Shop: order\nShop -> Bus: save\nShop --> User: done\nShop -> Bus ++ : notify\n@enduml";
+
+        let diff = diff_plantuml_sources(old, new);
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                change(
+                    ItemKind::Participant,
+                    ChangeKind::Renamed,
+                    Some("Web shop"),
+                    Some("Online shop")
+                ),
+                change(
+                    ItemKind::Participant,
+                    ChangeKind::Changed,
+                    Some("actor User"),
+                    Some("participant User")
+                ),
+                change(ItemKind::Participant, ChangeKind::Removed, Some("DB"), None),
+                change(ItemKind::Participant, ChangeKind::Added, None, Some("Bus")),
+                change(
+                    ItemKind::Message,
+                    ChangeKind::Removed,
+                    Some("Shop -> DB: save"),
+                    None
+                ),
+                change(
+                    ItemKind::Message,
+                    ChangeKind::Added,
+                    None,
+                    Some("Shop -> Bus: save")
+                ),
+                change(
+                    ItemKind::Message,
+                    ChangeKind::Added,
+                    None,
+                    Some("Shop -> Bus: notify")
+                ),
+            ]
+        );
+        assert_eq!(
+            diff.summary(),
+            "1 new participant, 1 participant removed, 1 participant renamed, 1 participant changed, 2 new messages, 1 message removed"
+        );
+    }
+
+    #[test]
+    fn it_diff_plantuml_class() {
+        let old = "@startuml\nclass Order {\n+id : u64\n}\nclass Customer\nCustomer \"1\" *-- \"many\" Order : places\nOrder --|> Entity\n@enduml";
+        let new = "@startuml\nclass Order {\n+id : u64\n+total : u64\n}\nclass Client\nClient \"1\" *-- \"many\" Order : places\nOrder ..|> Entity\nOrder : +pay()\n@enduml";
+
+        let diff = diff_plantuml_sources(old, new);
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                change(
+                    ItemKind::Element,
+                    ChangeKind::Renamed,
+                    Some("Customer"),
+                    Some("Client")
+                ),
+                change(
+                    ItemKind::Relation,
+                    ChangeKind::Removed,
+                    Some("Order --|> Entity"),
+                    None
+                ),
+                change(
+                    ItemKind::Relation,
+                    ChangeKind::Added,
+                    None,
+                    Some("Order ..|> Entity")
+                ),
+                change(
+                    ItemKind::Member,
+                    ChangeKind::Added,
+                    None,
+                    Some("Order: +total : u64")
+                ),
+                change(
+                    ItemKind::Member,
+                    ChangeKind::Added,
+                    None,
+                    Some("Order: +pay()")
+                ),
+            ]
+        );
+        assert_eq!(
+            diff.summary(),
+            "1 element renamed, 1 new relation, 1 relation removed, 2 new members"
+        );
+    }
+
+    #[test]
+    fn it_diff_plantuml_other_diagrams() {
+        let diff = diff_plantuml_sources(
+            "@startmindmap\n* root\n** a\n@endmindmap",
+            "@startmindmap\n* root\n** b\n@endmindmap",
+        );
+
+        assert_eq!(diff.old_kind, DiagramKind::Mindmap);
+        assert_eq!(diff.summary(), "1 new statement, 1 statement removed");
+        assert_eq!(
+            diff_plantuml_sources("@startuml\nA -> B\n@enduml", "@startjson\n{}\n@endjson")
+                .summary(),
+            "diagram type changed from uml to json, 2 participants removed, 1 message removed, 1 new statement"
+        );
+    }
+
+    #[test]
+    fn it_diff_plantuml_errors() {
+        assert_eq!(
+            diff_plantuml("~h1", PLANTUML_HEX_SMALL),
+            Err(errors::FromPlantumlError(
+                "there is a problem during hex decoding: `Odd number of digits`".to_string()
+            ))
+        );
+    }
+}
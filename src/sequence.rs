@@ -0,0 +1,672 @@
+use crate::deflate;
+use crate::errors;
+use crate::url;
+use crate::utils;
+
+/// Kind of the participant of the sequence diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticipantKind {
+    /// `participant`
+    Participant,
+    /// `actor`
+    Actor,
+    /// `boundary`
+    Boundary,
+    /// `control`
+    Control,
+    /// `entity`
+    Entity,
+    /// `database`
+    Database,
+    /// `collections`
+    Collections,
+    /// `queue`
+    Queue,
+}
+
+impl ParticipantKind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            ParticipantKind::Participant => "participant",
+            ParticipantKind::Actor => "actor",
+            ParticipantKind::Boundary => "boundary",
+            ParticipantKind::Control => "control",
+            ParticipantKind::Entity => "entity",
+            ParticipantKind::Database => "database",
+            ParticipantKind::Collections => "collections",
+            ParticipantKind::Queue => "queue",
+        }
+    }
+}
+
+/// Participant of the sequence diagram
+#[derive(Debug, Clone, PartialEq)]
+pub struct Participant {
+    name: String,
+    kind: ParticipantKind,
+    alias: Option<String>,
+}
+
+impl Participant {
+    /// Participant of the given kind
+    pub fn new<T: AsRef<str>>(kind: ParticipantKind, name: T) -> Self {
+        Participant {
+            name: name.as_ref().to_string(),
+            kind,
+            alias: None,
+        }
+    }
+
+    /// `actor`
+    pub fn actor<T: AsRef<str>>(name: T) -> Self {
+        Participant::new(ParticipantKind::Actor, name)
+    }
+
+    /// `database`
+    pub fn database<T: AsRef<str>>(name: T) -> Self {
+        Participant::new(ParticipantKind::Database, name)
+    }
+
+    /// `queue`
+    pub fn queue<T: AsRef<str>>(name: T) -> Self {
+        Participant::new(ParticipantKind::Queue, name)
+    }
+
+    /// Short alias used to reference the participant in messages
+    pub fn alias<T: AsRef<str>>(mut self, alias: T) -> Self {
+        self.alias = Some(alias.as_ref().to_string());
+        self
+    }
+
+    fn matches(&self, reference: &str) -> bool {
+        self.alias.as_deref() == Some(reference) || self.name == reference
+    }
+
+    fn reference(&self) -> String {
+        match &self.alias {
+            Some(alias) => identifier(alias),
+            None => identifier(&self.name),
+        }
+    }
+
+    fn declaration(&self) -> String {
+        match &self.alias {
+            Some(alias) => format!(
+                "{} {} as {}",
+                self.kind.keyword(),
+                quoted(&self.name),
+                identifier(alias)
+            ),
+            None => format!("{} {}", self.kind.keyword(), identifier(&self.name)),
+        }
+    }
+}
+
+/// Arrow style of the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrow {
+    /// `->`
+    Sync,
+    /// `->>`
+    Async,
+    /// `-->`
+    Reply,
+    /// `-->>`
+    AsyncReply,
+    /// `->x`
+    Lost,
+    /// `<->`
+    Bidirectional,
+}
+
+impl Arrow {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Arrow::Sync => "->",
+            Arrow::Async => "->>",
+            Arrow::Reply => "-->",
+            Arrow::AsyncReply => "-->>",
+            Arrow::Lost => "->x",
+            Arrow::Bidirectional => "<->",
+        }
+    }
+}
+
+/// Message between participants
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    from: String,
+    to: String,
+    text: Option<String>,
+    arrow: Arrow,
+    activate: bool,
+    deactivate: bool,
+}
+
+impl Message {
+    /// Synchronous message from one participant (name or alias) to another
+    pub fn new<F: AsRef<str>, T: AsRef<str>>(from: F, to: T) -> Self {
+        Message {
+            from: from.as_ref().to_string(),
+            to: to.as_ref().to_string(),
+            text: None,
+            arrow: Arrow::Sync,
+            activate: false,
+            deactivate: false,
+        }
+    }
+
+    /// Label of the message
+    pub fn text<T: AsRef<str>>(mut self, text: T) -> Self {
+        self.text = Some(text.as_ref().to_string());
+        self
+    }
+
+    /// Arrow style of the message
+    pub fn arrow(mut self, arrow: Arrow) -> Self {
+        self.arrow = arrow;
+        self
+    }
+
+    /// Activate the target (`++`)
+    pub fn activate(mut self) -> Self {
+        self.activate = true;
+        self
+    }
+
+    /// Deactivate the source (`--`)
+    pub fn deactivate(mut self) -> Self {
+        self.deactivate = true;
+        self
+    }
+}
+
+/// Position of the note
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotePosition {
+    /// `note left of`
+    Left,
+    /// `note right of`
+    Right,
+    /// `note over`
+    Over,
+}
+
+/// Kind of the grouping fragment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupKind {
+    /// `alt`
+    Alt,
+    /// `opt`
+    Opt,
+    /// `loop`
+    Loop,
+    /// `par`
+    Par,
+    /// `break`
+    Break,
+    /// `critical`
+    Critical,
+    /// `group`
+    Group,
+}
+
+impl GroupKind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            GroupKind::Alt => "alt",
+            GroupKind::Opt => "opt",
+            GroupKind::Loop => "loop",
+            GroupKind::Par => "par",
+            GroupKind::Break => "break",
+            GroupKind::Critical => "critical",
+            GroupKind::Group => "group",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Message(Message),
+    Activate(String),
+    Deactivate(String),
+    Note {
+        position: NotePosition,
+        participants: Vec<String>,
+        text: String,
+    },
+    Group {
+        kind: GroupKind,
+        label: String,
+        items: Vec<Item>,
+        branches: Vec<(String, Vec<Item>)>,
+    },
+    Divider(String),
+    Delay(Option<String>),
+    Spacer,
+}
+
+/// Builder of the [sequence diagram](https://plantuml.com/sequence-diagram)
+/// that renders correctly quoted and escaped plantuml
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{FromPlantumlError, Message, Participant, SequenceDiagram};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let diagram = SequenceDiagram::new()
+///         .participant(Participant::actor("PUML"))
+///         .message(Message::new("PUML", "RUST"));
+///
+///     assert_eq!(diagram.plantuml(), "@startuml\nactor PUML\nPUML -> RUST\n@enduml");
+///
+///     let encoded_deflate = diagram.encode_deflate()?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SequenceDiagram {
+    title: Option<String>,
+    autonumber: bool,
+    participants: Vec<Participant>,
+    items: Vec<Item>,
+}
+
+impl SequenceDiagram {
+    /// Empty sequence diagram
+    pub fn new() -> Self {
+        SequenceDiagram::default()
+    }
+
+    /// Title of the diagram
+    pub fn title<T: AsRef<str>>(mut self, title: T) -> Self {
+        self.title = Some(title.as_ref().to_string());
+        self
+    }
+
+    /// Number messages automatically
+    pub fn autonumber(mut self) -> Self {
+        self.autonumber = true;
+        self
+    }
+
+    /// Declare participant (participants are rendered in the order of declaration)
+    pub fn participant(mut self, participant: Participant) -> Self {
+        self.participants.push(participant);
+        self
+    }
+
+    /// Add message
+    pub fn message(mut self, message: Message) -> Self {
+        self.items.push(Item::Message(message));
+        self
+    }
+
+    /// Activate participant
+    pub fn activate<T: AsRef<str>>(mut self, participant: T) -> Self {
+        self.items
+            .push(Item::Activate(participant.as_ref().to_string()));
+        self
+    }
+
+    /// Deactivate participant
+    pub fn deactivate<T: AsRef<str>>(mut self, participant: T) -> Self {
+        self.items
+            .push(Item::Deactivate(participant.as_ref().to_string()));
+        self
+    }
+
+    /// Add note relative to participants (`Over` spans all of them)
+    pub fn note<I, P, T>(mut self, position: NotePosition, participants: I, text: T) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+        T: AsRef<str>,
+    {
+        self.items.push(Item::Note {
+            position,
+            participants: participants
+                .into_iter()
+                .map(|p| p.as_ref().to_string())
+                .collect(),
+            text: text.as_ref().to_string(),
+        });
+        self
+    }
+
+    /// Add grouping fragment (`alt`, `loop` and so on) with items built by `build`
+    pub fn group<T, F>(mut self, kind: GroupKind, label: T, build: F) -> Self
+    where
+        T: AsRef<str>,
+        F: FnOnce(SequenceDiagram) -> SequenceDiagram,
+    {
+        let inner = build(SequenceDiagram::new());
+
+        self.participants.extend(inner.participants);
+        self.items.push(Item::Group {
+            kind,
+            label: label.as_ref().to_string(),
+            items: inner.items,
+            branches: vec![],
+        });
+        self
+    }
+
+    /// Add `else` branch to the last grouping fragment
+    /// (no-op if the last item is not a grouping fragment, `build` is not called)
+    pub fn otherwise<T, F>(mut self, label: T, build: F) -> Self
+    where
+        T: AsRef<str>,
+        F: FnOnce(SequenceDiagram) -> SequenceDiagram,
+    {
+        if let Some(Item::Group { branches, .. }) = self.items.last_mut() {
+            let inner = build(SequenceDiagram::new());
+
+            branches.push((label.as_ref().to_string(), inner.items));
+            self.participants.extend(inner.participants);
+        }
+
+        self
+    }
+
+    /// Add divider (`== text ==`)
+    pub fn divider<T: AsRef<str>>(mut self, text: T) -> Self {
+        self.items.push(Item::Divider(text.as_ref().to_string()));
+        self
+    }
+
+    /// Add delay (`...` or `...text...`)
+    pub fn delay(mut self, text: Option<&str>) -> Self {
+        self.items.push(Item::Delay(text.map(String::from)));
+        self
+    }
+
+    /// Add spacer (`|||`)
+    pub fn spacer(mut self) -> Self {
+        self.items.push(Item::Spacer);
+        self
+    }
+
+    /// Plantuml of the diagram
+    pub fn plantuml(&self) -> String {
+        let mut lines = vec!["@startuml".to_string()];
+
+        if let Some(title) = &self.title {
+            lines.push(format!("title {}", label(title)));
+        }
+
+        if self.autonumber {
+            lines.push("autonumber".to_string());
+        }
+
+        for participant in self.participants.iter() {
+            lines.push(participant.declaration());
+        }
+
+        self.render_items(&self.items, 0, &mut lines);
+
+        lines.push("@enduml".to_string());
+
+        lines.join("\n")
+    }
+
+    /// Encode the diagram with deflate compression
+    pub fn encode_deflate(&self) -> Result<String, errors::FromPlantumlError> {
+        deflate::encode_plantuml_deflate(self.plantuml())
+    }
+
+    /// Server URL of the diagram
+    pub fn url<S: AsRef<str>>(
+        &self,
+        server: S,
+        format: url::OutputFormat,
+    ) -> Result<String, errors::FromPlantumlError> {
+        Ok(url::plantuml_url(server, format, self.encode_deflate()?))
+    }
+
+    fn reference(&self, participant: &str) -> String {
+        self.participants
+            .iter()
+            .find(|p| p.matches(participant))
+            .map(Participant::reference)
+            .unwrap_or_else(|| identifier(participant))
+    }
+
+    fn render_items(&self, items: &[Item], depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+
+        for item in items {
+            match item {
+                Item::Message(message) => {
+                    let mut line = format!(
+                        "{}{} {} {}",
+                        indent,
+                        self.reference(&message.from),
+                        message.arrow.as_str(),
+                        self.reference(&message.to)
+                    );
+
+                    if message.activate {
+                        line += " ++";
+                    }
+
+                    if message.deactivate {
+                        line += " --";
+                    }
+
+                    if let Some(text) = &message.text {
+                        line += &format!(" : {}", label(text));
+                    }
+
+                    lines.push(line);
+                }
+                Item::Activate(participant) => lines.push(format!(
+                    "{}activate {}",
+                    indent,
+                    self.reference(participant)
+                )),
+                Item::Deactivate(participant) => lines.push(format!(
+                    "{}deactivate {}",
+                    indent,
+                    self.reference(participant)
+                )),
+                Item::Note {
+                    position,
+                    participants,
+                    text,
+                } => {
+                    let participants = participants
+                        .iter()
+                        .map(|p| self.reference(p))
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    let position = match position {
+                        NotePosition::Left => "left of",
+                        NotePosition::Right => "right of",
+                        NotePosition::Over => "over",
+                    };
+
+                    if text.contains('\n') {
+                        lines.push(format!("{}note {} {}", indent, position, participants));
+                        lines.extend(text.lines().map(|l| format!("{}{}", indent, note_line(l))));
+                        lines.push(format!("{}end note", indent));
+                    } else {
+                        lines.push(format!(
+                            "{}note {} {} : {}",
+                            indent,
+                            position,
+                            participants,
+                            label(text)
+                        ));
+                    }
+                }
+                Item::Group {
+                    kind,
+                    label: group_label,
+                    items,
+                    branches,
+                } => {
+                    lines.push(
+                        format!("{}{} {}", indent, kind.keyword(), label(group_label))
+                            .trim_end()
+                            .to_string(),
+                    );
+                    self.render_items(items, depth + 1, lines);
+
+                    for (branch_label, branch_items) in branches {
+                        lines.push(
+                            format!("{}else {}", indent, label(branch_label))
+                                .trim_end()
+                                .to_string(),
+                        );
+                        self.render_items(branch_items, depth + 1, lines);
+                    }
+
+                    lines.push(format!("{}end", indent));
+                }
+                Item::Divider(text) => lines.push(format!("{}== {} ==", indent, label(text))),
+                Item::Delay(None) => lines.push(format!("{}...", indent)),
+                Item::Delay(Some(text)) => lines.push(format!("{}...{}...", indent, label(text))),
+                Item::Spacer => lines.push(format!("{}|||", indent)),
+            }
+        }
+    }
+}
+
+const KEYWORDS: [&str; 8] = [
+    "as",
+    "participant",
+    "actor",
+    "note",
+    "end",
+    "else",
+    "activate",
+    "deactivate",
+];
+
+fn label(text: &str) -> String {
+    utils::escape_label(text)
+}
+
+/// Line of a multi-line note, `end note` and `@end...` lines (which would end the note
+/// or the diagram) are escaped with creole `~`
+fn note_line(line: &str) -> String {
+    let lowercase = line
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase();
+
+    if ["end note", "endnote", "@end"]
+        .iter()
+        .any(|closer| lowercase.starts_with(closer))
+    {
+        format!("~{}", line.trim_start())
+    } else {
+        line.to_string()
+    }
+}
+
+fn identifier(name: &str) -> String {
+    utils::name_or_quoted(name, &KEYWORDS)
+}
+
+fn quoted(name: &str) -> String {
+    utils::quote_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arrow, GroupKind, Message, NotePosition, Participant, SequenceDiagram};
+
+    use crate::deflate::decode_plantuml_deflate;
+
+    #[test]
+    fn it_sequence_encode_deflate() {
+        let diagram = SequenceDiagram::new().message(Message::new("PUML", "RUST").text("HELLO "));
+
+        assert_eq!(
+            diagram.plantuml(),
+            "@startuml\nPUML -> RUST : HELLO \n@enduml"
+        );
+        assert_eq!(
+            decode_plantuml_deflate(diagram.encode_deflate().unwrap()),
+            Ok(diagram.plantuml())
+        );
+    }
+
+    #[test]
+    fn it_sequence_quotes_names() {
+        let diagram = SequenceDiagram::new()
+            .participant(Participant::actor("End User"))
+            .participant(Participant::database("Main \"DB\" -> replica").alias("db"))
+            .message(Message::new("End User", "db").text("select\n*").activate())
+            .message(
+                Message::new("db", "End User")
+                    .arrow(Arrow::Reply)
+                    .deactivate(),
+            )
+            .message(Message::new("a->b", "End User").arrow(Arrow::Async));
+
+        assert_eq!(
+            diagram.plantuml(),
+            "@startuml\nactor \"End User\"\ndatabase \"Main &#34;DB&#34; -> replica\" as db\n\"End User\" -> db ++ : select\\n*\ndb --> \"End User\" --\n\"a->b\" ->> \"End User\"\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_sequence_groups_notes_and_dividers() {
+        let diagram = SequenceDiagram::new()
+            .title("Login flow")
+            .autonumber()
+            .divider("Init")
+            .group(GroupKind::Alt, "valid", |s| {
+                s.message(Message::new("A", "B"))
+                    .note(NotePosition::Right, ["B"], "checked")
+            })
+            .otherwise("invalid", |s| {
+                s.group(GroupKind::Loop, "retry", |s| {
+                    s.message(Message::new("A", "A"))
+                })
+            })
+            .note(NotePosition::Over, ["A", "B"], "multi\nline")
+            .delay(Some("5 minutes"))
+            .spacer()
+            .activate("A")
+            .deactivate("A");
+
+        assert_eq!(
+            diagram.plantuml(),
+            "@startuml\ntitle Login flow\nautonumber\n== Init ==\nalt valid\n  A -> B\n  note right of B : checked\nelse invalid\n  loop retry\n    A -> A\n  end\nend\nnote over A, B\nmulti\nline\nend note\n...5 minutes...\n|||\nactivate A\ndeactivate A\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_sequence_escapes_note_closers_and_ignores_lonely_else() {
+        let diagram = SequenceDiagram::new()
+            .message(Message::new("A", "B"))
+            .otherwise("ignored", |s| s.message(Message::new("B", "A")))
+            .note(
+                NotePosition::Left,
+                ["A"],
+                "first\n  End  Note\nendnote\n@enduml\nlast",
+            );
+
+        assert_eq!(
+            diagram.plantuml(),
+            "@startuml\nA -> B\nnote left of A\nfirst\n~End  Note\n~endnote\n~@enduml\nlast\nend note\n@enduml"
+        );
+    }
+
+    #[test]
+    fn it_sequence_url() {
+        let diagram = SequenceDiagram::new().message(Message::new("PUML", "RUST"));
+
+        assert_eq!(
+            diagram.url("http://localhost", crate::url::OutputFormat::Svg),
+            Ok("http://localhost/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000".to_string())
+        );
+    }
+}
@@ -0,0 +1,463 @@
+use crate::deflate;
+use crate::errors;
+use crate::kind;
+use crate::url;
+
+/// Single diagram of the plantuml file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagram {
+    /// Name from `@startuml name` or `@startuml(id=name)`
+    pub name: Option<String>,
+    /// Diagram type from the start tag (`uml`, `mindmap`, `gantt` and so on)
+    pub kind: String,
+    /// 1-based page number (pages are separated by `newpage` or `newpage <title>`)
+    pub page: usize,
+    /// 1-based first line of the diagram in the file
+    pub start_line: usize,
+    /// 1-based last line of the diagram in the file
+    pub end_line: usize,
+    /// Plantuml of the diagram with `@start` and `@end` tags
+    pub source: String,
+}
+
+impl Diagram {
+    /// Known [`kind::DiagramKind`] of the diagram
+    pub fn diagram_kind(&self) -> Option<kind::DiagramKind> {
+        kind::DiagramKind::from_tag(&self.kind)
+    }
+
+    /// Encode the diagram with deflate compression
+    pub fn encode_deflate(&self) -> Result<String, errors::FromPlantumlError> {
+        deflate::encode_plantuml_deflate(&self.source)
+    }
+
+    /// Server URL of the diagram
+    pub fn url<S: AsRef<str>>(
+        &self,
+        server: S,
+        format: url::OutputFormat,
+    ) -> Result<String, errors::FromPlantumlError> {
+        Ok(url::plantuml_url(server, format, self.encode_deflate()?))
+    }
+}
+
+/// Split plantuml file into diagrams (`@startuml ... @enduml`, `@startmindmap ... @endmindmap`
+/// and so on, with `newpage` separated pages as separate diagrams,
+/// the title of `newpage <title>` is kept as the `title` of the next page).
+/// Pages repeat the setup lines of the previous pages (directives, skinparams, participants...),
+/// `newpage` inside notes, legends and comments is not a page break.
+/// Text without any start tag is returned as a single `uml` diagram,
+/// unclosed diagrams are closed.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::split_plantuml;
+///
+/// let diagrams = split_plantuml("@startuml first\nA -> B\n@enduml\n\n@startmindmap\n* root\n@endmindmap");
+///
+/// assert_eq!(diagrams.len(), 2);
+/// assert_eq!(diagrams[0].name, Some("first".to_string()));
+/// assert_eq!(diagrams[1].kind, "mindmap");
+/// assert_eq!((diagrams[1].start_line, diagrams[1].end_line), (5, 7));
+/// assert_eq!(diagrams[1].source, "@startmindmap\n* root\n@endmindmap");
+/// ```
+pub fn split_plantuml<T: AsRef<str>>(plantuml: T) -> Vec<Diagram> {
+    let plantuml = plantuml.as_ref();
+    let lines: Vec<&str> = plantuml.lines().collect();
+    let mut diagrams = vec![];
+    let mut index = 0;
+
+    while index < lines.len() {
+        let (kind, name) = match parse_start(lines[index]) {
+            Some(start) => start,
+            None => {
+                index += 1;
+                continue;
+            }
+        };
+
+        let start = index;
+        let closed = (start + 1..lines.len()).find(|i| lines[*i].trim_start().starts_with("@end"));
+        let end = closed.unwrap_or(lines.len());
+
+        let start_tag = lines[start].trim();
+        let end_tag = match closed {
+            Some(closed) => lines[closed].trim().to_string(),
+            None => format!("@end{}", kind),
+        };
+
+        // page (lines `first..body_end`, the first one includes the start tag) with the setup
+        // lines of the previous pages and the title of its `newpage`
+        let page = |number: usize,
+                    first: usize,
+                    body_end: usize,
+                    end_line: usize,
+                    prelude: &[String],
+                    title: Option<String>| {
+            let mut source = vec![start_tag.to_string()];
+            source.extend(prelude.iter().cloned());
+            source.extend(title);
+            source.extend(lines[first..body_end].iter().map(|l| l.to_string()));
+            source.push(end_tag.clone());
+
+            Diagram {
+                name: name.clone(),
+                kind: kind.clone(),
+                page: number,
+                start_line: if number == 1 { start + 1 } else { first + 1 },
+                end_line,
+                source: source.join("\n"),
+            }
+        };
+
+        let mut page_start = start + 1;
+        let mut number = 1;
+        let mut title = None;
+        // setup lines of the previous pages and of the current one
+        let mut inherited: Vec<String> = vec![];
+        let mut prelude: Vec<String> = vec![];
+        let mut block: Option<Block> = None;
+
+        for (i, line) in lines.iter().enumerate().take(end).skip(start + 1) {
+            if let Some(open) = block {
+                if open == Block::Skinparam {
+                    prelude.push(line.to_string());
+                }
+
+                if open.is_closed_by(line) {
+                    block = None;
+                }
+
+                continue;
+            }
+
+            block = Block::opened_by(line);
+
+            if block == Some(Block::Skinparam) || is_prelude(line) {
+                prelude.push(line.to_string());
+            }
+
+            let newpage = match parse_newpage(line) {
+                Some(newpage) => newpage,
+                None => continue,
+            };
+
+            diagrams.push(page(number, page_start, i, i + 1, &inherited, title.take()));
+
+            number += 1;
+            page_start = i + 1;
+            inherited = prelude.clone();
+            title = newpage.map(|newpage_title| format!("title {}", newpage_title));
+        }
+
+        diagrams.push(page(
+            number,
+            page_start,
+            end,
+            closed.map_or(lines.len(), |closed| closed + 1),
+            &inherited,
+            title,
+        ));
+
+        index = end + 1;
+    }
+
+    if diagrams.is_empty() && !plantuml.trim().is_empty() {
+        diagrams.push(Diagram {
+            name: None,
+            kind: "uml".to_string(),
+            page: 1,
+            start_line: 1,
+            end_line: lines.len(),
+            source: plantuml.to_string(),
+        });
+    }
+
+    diagrams
+}
+
+/// Multi-line block that can not contain page breaks (text blocks) or belongs to the prelude
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Note,
+    Legend,
+    Comment,
+    Skinparam,
+}
+
+impl Block {
+    fn opened_by(line: &str) -> Option<Block> {
+        let trimmed = line.trim();
+        let lowercase = trimmed.to_lowercase();
+        let first = lowercase.split_whitespace().next().unwrap_or_default();
+
+        match first {
+            "note" | "hnote" | "rnote" if !trimmed.contains(':') => Some(Block::Note),
+            "legend" => Some(Block::Legend),
+            "skinparam" if trimmed.ends_with('{') => Some(Block::Skinparam),
+            _ if trimmed.starts_with("/'") && !trimmed.ends_with("'/") => Some(Block::Comment),
+            _ => None,
+        }
+    }
+
+    fn is_closed_by(self, line: &str) -> bool {
+        let lowercase = line.trim().to_lowercase();
+        let words: Vec<&str> = lowercase.split_whitespace().collect();
+
+        match self {
+            Block::Note => matches!(
+                words.as_slice(),
+                ["endnote" | "endhnote" | "endrnote", ..] | ["end", "note" | "hnote" | "rnote", ..]
+            ),
+            Block::Legend => matches!(words.as_slice(), ["endlegend", ..] | ["end", "legend", ..]),
+            Block::Comment => lowercase.ends_with("'/"),
+            Block::Skinparam => lowercase.starts_with('}'),
+        }
+    }
+}
+
+/// Setup line repeated on the following pages (directives, skinparams, participants...)
+fn is_prelude(line: &str) -> bool {
+    let lowercase = line.trim().to_lowercase();
+    let first = lowercase.split_whitespace().next().unwrap_or_default();
+
+    matches!(
+        first,
+        "!theme"
+            | "!include"
+            | "!include_many"
+            | "!include_once"
+            | "!includeurl"
+            | "!pragma"
+            | "skinparam"
+            | "skinparamlocked"
+            | "hide"
+            | "show"
+            | "participant"
+            | "actor"
+            | "boundary"
+            | "control"
+            | "entity"
+            | "database"
+            | "collections"
+            | "queue"
+    )
+}
+
+/// Title of the `newpage` line (`Some(None)` for `newpage` without title)
+fn parse_newpage(line: &str) -> Option<Option<&str>> {
+    let trimmed = line.trim();
+    let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+
+    if !trimmed[..end].eq_ignore_ascii_case("newpage") {
+        return None;
+    }
+
+    Some(Some(trimmed[end..].trim()).filter(|title| !title.is_empty()))
+}
+
+/// Split plantuml file into diagrams and encode each one with deflate compression
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{encode_plantuml_deflate_split, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let encoded = encode_plantuml_deflate_split(
+///         "@startuml\nPUML -> RUST\n@enduml\n@startuml\nPUML -> RUST\n@enduml",
+///     )?;
+///
+///     assert_eq!(encoded, vec![
+///         "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000",
+///         "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000",
+///     ]);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn encode_plantuml_deflate_split<T: AsRef<str>>(
+    plantuml: T,
+) -> Result<Vec<String>, errors::FromPlantumlError> {
+    split_plantuml(plantuml)
+        .iter()
+        .map(Diagram::encode_deflate)
+        .collect()
+}
+
+/// Split plantuml file into diagrams and build server URL for each one
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{plantuml_urls_split, FromPlantumlError, OutputFormat, PLANTUML_SERVER};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let urls = plantuml_urls_split("@startuml\nPUML -> RUST\n@enduml", PLANTUML_SERVER, OutputFormat::Png)?;
+///
+///     assert_eq!(urls, vec![
+///         "https://www.plantuml.com/plantuml/png/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000",
+///     ]);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn plantuml_urls_split<T: AsRef<str>, S: AsRef<str>>(
+    plantuml: T,
+    server: S,
+    format: url::OutputFormat,
+) -> Result<Vec<String>, errors::FromPlantumlError> {
+    split_plantuml(plantuml)
+        .iter()
+        .map(|diagram| diagram.url(server.as_ref(), format))
+        .collect()
+}
+
+fn parse_start(line: &str) -> Option<(String, Option<String>)> {
+    let tag = line.trim().strip_prefix("@start")?;
+    let kind_end = tag
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(tag.len());
+    let kind = &tag[..kind_end];
+
+    if kind.is_empty() {
+        return None;
+    }
+
+    let rest = tag[kind_end..].trim();
+
+    let name = if let Some(id) = rest.strip_prefix("(id=") {
+        Some(id.trim_end_matches(')').trim().to_string())
+    } else if rest.is_empty() {
+        None
+    } else {
+        Some(rest.to_string())
+    };
+
+    Some((kind.to_lowercase(), name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_plantuml_deflate_split, split_plantuml, Diagram};
+
+    use crate::kind::DiagramKind;
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_str::PLANTUML_SMALL,
+    };
+
+    #[test]
+    fn it_split_plantuml_multiple_diagrams() {
+        let diagrams = split_plantuml(
+            "' header\n@startuml(id=first)\nA -> B\n@enduml\n@startgantt\n[Task] lasts 2 days\n@endgantt",
+        );
+
+        assert_eq!(
+            diagrams,
+            vec![
+                Diagram {
+                    name: Some("first".to_string()),
+                    kind: "uml".to_string(),
+                    page: 1,
+                    start_line: 2,
+                    end_line: 4,
+                    source: "@startuml(id=first)\nA -> B\n@enduml".to_string(),
+                },
+                Diagram {
+                    name: None,
+                    kind: "gantt".to_string(),
+                    page: 1,
+                    start_line: 5,
+                    end_line: 7,
+                    source: "@startgantt\n[Task] lasts 2 days\n@endgantt".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_split_plantuml_newpage() {
+        let diagrams = split_plantuml("@startuml pages\nA -> B\nnewpage\nB -> C\n@enduml");
+
+        assert_eq!(diagrams.len(), 2);
+        assert_eq!(diagrams[0].diagram_kind(), Some(DiagramKind::Uml));
+        assert_eq!(diagrams[0].source, "@startuml pages\nA -> B\n@enduml");
+        assert_eq!((diagrams[0].start_line, diagrams[0].end_line), (1, 3));
+        assert_eq!(diagrams[1].source, "@startuml pages\nB -> C\n@enduml");
+        assert_eq!((diagrams[1].page, diagrams[1].start_line), (2, 4));
+        assert_eq!(diagrams[1].end_line, 5);
+    }
+
+    #[test]
+    fn it_split_plantuml_newpage_title() {
+        let diagrams = split_plantuml(
+            "@startuml\nA -> B\nnewpage Second page\nB -> C\nNEWPAGE\nC -> D\n@enduml",
+        );
+
+        assert_eq!(diagrams.len(), 3);
+        assert_eq!(diagrams[0].source, "@startuml\nA -> B\n@enduml");
+        assert_eq!(
+            diagrams[1].source,
+            "@startuml\ntitle Second page\nB -> C\n@enduml"
+        );
+        assert_eq!((diagrams[1].start_line, diagrams[1].end_line), (4, 5));
+        assert_eq!(diagrams[2].source, "@startuml\nC -> D\n@enduml");
+    }
+
+    #[test]
+    fn it_split_plantuml_newpage_prelude() {
+        let diagrams = split_plantuml(
+            "@startuml\n!theme plain\nskinparam monochrome {\n  Reverse true\n}\nparticipant A\nA -> B\nnote over A\nnewpage\nend note\nnewpage\nactor C\nB -> C\nnewpage\nC -> A\n@enduml",
+        );
+
+        assert_eq!(diagrams.len(), 3);
+        assert_eq!(
+            diagrams[0].source,
+            "@startuml\n!theme plain\nskinparam monochrome {\n  Reverse true\n}\nparticipant A\nA -> B\nnote over A\nnewpage\nend note\n@enduml"
+        );
+        assert_eq!(
+            diagrams[1].source,
+            "@startuml\n!theme plain\nskinparam monochrome {\n  Reverse true\n}\nparticipant A\nactor C\nB -> C\n@enduml"
+        );
+        assert_eq!(
+            diagrams[2].source,
+            "@startuml\n!theme plain\nskinparam monochrome {\n  Reverse true\n}\nparticipant A\nactor C\nC -> A\n@enduml"
+        );
+        assert_eq!((diagrams[2].start_line, diagrams[2].end_line), (15, 16));
+    }
+
+    #[test]
+    fn it_split_plantuml_without_tags() {
+        let diagrams = split_plantuml("A -> B");
+
+        assert_eq!(diagrams.len(), 1);
+        assert_eq!(diagrams[0].source, "A -> B");
+        assert!(split_plantuml("\n").is_empty());
+    }
+
+    #[test]
+    fn it_split_plantuml_unclosed() {
+        let diagrams = split_plantuml("@startuml\nA -> B");
+
+        assert_eq!(diagrams[0].source, "@startuml\nA -> B\n@enduml");
+        assert_eq!(diagrams[0].end_line, 2);
+
+        let diagrams = split_plantuml("@startuml\nA -> B\n@enduml\n@startmindmap");
+
+        assert_eq!(diagrams.len(), 2);
+        assert_eq!(diagrams[1].source, "@startmindmap\n@endmindmap");
+        assert_eq!((diagrams[1].start_line, diagrams[1].end_line), (4, 4));
+    }
+
+    #[test]
+    fn it_encode_plantuml_deflate_split_small() {
+        assert_eq!(
+            encode_plantuml_deflate_split(format!("{}\n{}", PLANTUML_SMALL, PLANTUML_SMALL)),
+            Ok(vec![
+                PLANTUML_DEFLATED_SMALL.to_string(),
+                PLANTUML_DEFLATED_SMALL.to_string()
+            ])
+        );
+    }
+}
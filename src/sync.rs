@@ -0,0 +1,378 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::deflate;
+use crate::errors;
+use crate::scan;
+use crate::url;
+use crate::utils;
+
+/// Extensions of the files looked up in directories by [`sync_plantuml_files`]
+pub const SYNC_EXTENSIONS: [&str; 4] = ["md", "markdown", "mdx", "html"];
+
+const MARKER: &str = "plantuml:";
+
+/// Link that does not match its source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleLink {
+    /// Line of the link (1-based)
+    pub line: usize,
+    /// Source file from the marker (as written in the marker)
+    pub source: String,
+    /// Current URL
+    pub old_url: String,
+    /// URL of the re-encoded source file
+    pub new_url: String,
+}
+
+/// Result of the synchronization of a text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Synced {
+    /// Text with rewritten links
+    pub text: String,
+    /// Links that were stale
+    pub stale: Vec<StaleLink>,
+}
+
+impl Synced {
+    /// Whether all links match their source files
+    pub fn is_up_to_date(&self) -> bool {
+        self.stale.is_empty()
+    }
+
+    /// Line diff of the stale links (`-` old line, `+` new line)
+    pub fn diff<P: AsRef<Path>>(&self, path: P, original: &str) -> String {
+        let new_lines: Vec<&str> = self.text.lines().collect();
+        let mut diff = vec![];
+
+        for stale in self.stale.iter() {
+            diff.push(format!(
+                "{}:{}: stale link of `{}`",
+                path.as_ref().display(),
+                stale.line,
+                stale.source
+            ));
+
+            if let (Some(old), Some(new)) = (
+                original.lines().nth(stale.line - 1),
+                new_lines.get(stale.line - 1),
+            ) {
+                diff.push(format!("-{}", old));
+                diff.push(format!("+{}", new));
+            }
+        }
+
+        diff.join("\n")
+    }
+}
+
+/// Re-encode source files referenced by `<!-- plantuml: path/to/file.puml -->`
+/// markers (relative to `base_dir`) with [`crate::encode_plantuml_deflate`]
+/// and rewrite plantuml server links that follow the markers
+/// (on the same line or on the next non-empty line).
+/// Server and format of the links are kept, the link after a marker may have any
+/// payload (even a placeholder) and any server.
+/// Server and format of the links are kept.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{sync_plantuml_links, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     let dir = std::env::temp_dir();
+///     std::fs::write(dir.join("sync_example.puml"), "@startuml\nPUML -> RUST\n@enduml")?;
+///
+///     let readme = "<!-- plantuml: sync_example.puml -->\n![arch](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuKhEIImkLd2jICmjo4dbSaZDIm6g0000)\n";
+///     let synced = sync_plantuml_links(readme, &dir)?;
+///
+///     assert!(!synced.is_up_to_date());
+///     assert_eq!(
+///         synced.text,
+///         "<!-- plantuml: sync_example.puml -->\n![arch](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)\n"
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+pub fn sync_plantuml_links<T: AsRef<str>, P: AsRef<Path>>(
+    text: T,
+    base_dir: P,
+) -> Result<Synced, errors::FromPlantumlError> {
+    let text = text.as_ref();
+    let mut output = String::with_capacity(text.len());
+    let mut stale = vec![];
+    let mut position = 0;
+
+    for (marker_start, marker_end, source) in markers(text) {
+        let marker_line = text[..marker_start].matches('\n').count() + 1;
+
+        let link = link_areas(text, marker_start, marker_end)
+            .into_iter()
+            .filter(|(start, _)| *start >= position)
+            .find_map(|(start, end)| {
+                scan::url_spans(&text[start..end])
+                    .into_iter()
+                    .find_map(|span| {
+                        let plantuml_url =
+                            url::PlantumlUrl::parse_any(&text[start..end][span.clone()])?;
+
+                        Some((start + span.start..start + span.end, plantuml_url))
+                    })
+            });
+
+        let (found, plantuml_url) = link.ok_or_else(|| {
+            errors::FromPlantumlError(format!(
+                "marker of `{}` is not followed by a plantuml link (line {})",
+                source, marker_line
+            ))
+        })?;
+
+        let path = base_dir.as_ref().join(&source);
+        let plantuml = fs::read_to_string(&path).map_err(|err| {
+            errors::FromPlantumlError(format!(
+                "there is a problem during reading `{}`: `{}` (line {})",
+                path.display(),
+                err,
+                marker_line
+            ))
+        })?;
+
+        let new_url = url::PlantumlUrl {
+            encoded: deflate::encode_plantuml_deflate(plantuml)?,
+            ..plantuml_url
+        }
+        .url();
+        let old_url = &text[found.clone()];
+
+        output.push_str(&text[position..found.start]);
+        output.push_str(&new_url);
+        position = found.end;
+
+        if old_url != new_url {
+            stale.push(StaleLink {
+                line: text[..found.start].matches('\n').count() + 1,
+                source,
+                old_url: old_url.to_string(),
+                new_url,
+            });
+        }
+    }
+
+    output.push_str(&text[position..]);
+
+    Ok(Synced {
+        text: output,
+        stale,
+    })
+}
+
+/// File synchronized by [`sync_plantuml_files`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncedFile {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Original content of the file
+    pub original: String,
+    /// Result of the synchronization
+    pub synced: Synced,
+}
+
+/// Synchronize links of files and directories (recursively, [`SYNC_EXTENSIONS`] only)
+/// with [`sync_plantuml_links`] relative to the directory of every file,
+/// rewriting files with stale links if `fix` is set
+pub fn sync_plantuml_files<I, P>(
+    paths: I,
+    fix: bool,
+) -> Result<Vec<SyncedFile>, errors::FromPlantumlError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut synced_files = vec![];
+
+    for path in paths {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            utils::walk_files(path, true)?
+                .into_iter()
+                .filter(|file| {
+                    file.extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| SYNC_EXTENSIONS.contains(&e))
+                })
+                .collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        for file in files {
+            let io_error = |action: &str, err: std::io::Error| {
+                errors::FromPlantumlError(format!(
+                    "there is a problem during {} `{}`: `{}`",
+                    action,
+                    file.display(),
+                    err
+                ))
+            };
+
+            let original = fs::read_to_string(&file).map_err(|err| io_error("reading", err))?;
+            let synced = sync_plantuml_links(&original, file.parent().unwrap_or(Path::new(".")))
+                .map_err(|err| {
+                    errors::FromPlantumlError(format!("{}: {}", file.display(), err.0))
+                })?;
+
+            if fix && !synced.is_up_to_date() {
+                fs::write(&file, &synced.text).map_err(|err| io_error("writing", err))?;
+            }
+
+            synced_files.push(SyncedFile {
+                path: file,
+                original,
+                synced,
+            });
+        }
+    }
+
+    Ok(synced_files)
+}
+
+fn markers(text: &str) -> Vec<(usize, usize, String)> {
+    let mut markers = vec![];
+    let mut rest = 0;
+
+    while let Some(start) = text[rest..].find("<!--").map(|i| rest + i) {
+        let end = match text[start..].find("-->") {
+            Some(end) => start + end + 3,
+            None => break,
+        };
+
+        let comment = text[start + 4..end - 3].trim();
+
+        if let Some(source) = comment.strip_prefix(MARKER) {
+            let source = source.trim();
+
+            if !source.is_empty() {
+                markers.push((start, end, source.to_string()));
+            }
+        }
+
+        rest = end;
+    }
+
+    markers
+}
+
+/// Where to look for the link of the marker: after the marker on its line,
+/// before the marker on its line and the next non-empty line
+fn link_areas(text: &str, marker_start: usize, marker_end: usize) -> Vec<(usize, usize)> {
+    let line_start = text[..marker_start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[marker_end..]
+        .find('\n')
+        .map_or(text.len(), |i| marker_end + i);
+
+    let mut areas = vec![(marker_end, line_end), (line_start, marker_start)];
+    let mut start = (line_end + 1).min(text.len());
+
+    for line in text[start..].split_inclusive('\n') {
+        if !line.trim().is_empty() {
+            areas.push((start, start + line.trim_end_matches(['\r', '\n']).len()));
+            break;
+        }
+
+        start += line.len();
+    }
+
+    areas
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{sync_plantuml_files, sync_plantuml_links, StaleLink};
+
+    use crate::errors;
+    use crate::tests::fs::temp_dir;
+
+    const SMALL: &str = "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000";
+
+    #[test]
+    fn it_sync_plantuml_links() {
+        let dir = temp_dir("sync_links");
+
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(
+            dir.join("docs/arch.puml"),
+            "@startuml\nPUML -> RUST\n@enduml",
+        )
+        .unwrap();
+
+        let text = format!(
+            "# Readme\n\n<!-- plantuml: docs/arch.puml -->\n\n![arch](http://localhost:8080/png/stale)\n\n<img src=\"https://www.plantuml.com/plantuml/svg/{}\"> <!-- plantuml: docs/arch.puml -->\n",
+            SMALL
+        );
+
+        let synced = sync_plantuml_links(&text, &dir).unwrap();
+
+        assert_eq!(
+            synced.stale,
+            vec![StaleLink {
+                line: 5,
+                source: "docs/arch.puml".to_string(),
+                old_url: "http://localhost:8080/png/stale".to_string(),
+                new_url: format!("http://localhost:8080/png/{}", SMALL),
+            }]
+        );
+        assert_eq!(
+            synced.text,
+            text.replace("png/stale", &format!("png/{}", SMALL))
+        );
+        assert_eq!(
+            synced.diff("README.md", &text),
+            format!(
+                "README.md:5: stale link of `docs/arch.puml`\n-![arch](http://localhost:8080/png/stale)\n+![arch](http://localhost:8080/png/{})",
+                SMALL
+            )
+        );
+    }
+
+    #[test]
+    fn it_sync_plantuml_links_errors() {
+        let dir = temp_dir("sync_errors");
+
+        assert_eq!(
+            sync_plantuml_links("<!-- plantuml: a.puml -->\n\nno link", &dir),
+            Err(errors::FromPlantumlError(
+                "marker of `a.puml` is not followed by a plantuml link (line 1)".to_string()
+            ))
+        );
+        assert!(sync_plantuml_links(
+            "<!-- plantuml: missing.puml --> https://www.plantuml.com/plantuml/svg/~h40",
+            &dir
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_sync_plantuml_files_fix() {
+        let dir = temp_dir("sync_files");
+        let readme = "<!-- plantuml: arch.puml -->\nhttps://www.plantuml.com/plantuml/svg/~h40\n";
+
+        fs::write(dir.join("arch.puml"), "@startuml\nPUML -> RUST\n@enduml").unwrap();
+        fs::write(dir.join("README.md"), readme).unwrap();
+        fs::write(dir.join("notes.txt"), "<!-- plantuml: missing.puml -->").unwrap();
+
+        let checked = sync_plantuml_files([&dir], false).unwrap();
+
+        assert_eq!(checked.len(), 1);
+        assert_eq!(checked[0].synced.stale.len(), 1);
+        assert_eq!(fs::read_to_string(dir.join("README.md")).unwrap(), readme);
+
+        sync_plantuml_files([&dir], true).unwrap();
+
+        assert!(sync_plantuml_files([&dir], false).unwrap()[0]
+            .synced
+            .is_up_to_date());
+    }
+}
@@ -49,3 +49,19 @@ pub mod constants {
             "ZP4zRy8m48Pt_ueJdHawLMMe53iWLK9LLLHrFk8hjG9sE3kbzDSJ0u8a1CAG8SdpVja-DxP0nXNCCSiNx4ghbLiwXeVnU2nJ9Vo9-46hDsn280k1InArVvxM9TcC4cQtEHYaHCKityfbHNQ0MN0i7MlYU_2f-QuUItN5l0Tj3qgUKvLEEXJloC5afa0RxczK5xMIKgSNnFswYRFX3LS4hwi2iz0Fpym_mUDJFzYt-73whTguBl4QUpQzurflLpqX--a95lGd1CQFyEc9HN6U0eE9OHz0BT7x0Q27siwaISueSrPtexA9Jl6Qfn8YTe3XIoDfL5tfb-CyrZI_6AZPGzJVMbev0iKK9Hznlm-T7hBX8LiUwcQ-gaiL32_oep9rbeIkujbjMuERmHVBZxMFAtHbqZyWsVl4aupDzycT9xS3";
     }
 }
+
+#[cfg(test)]
+pub mod fs {
+    use std::path::PathBuf;
+
+    pub fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("plantuml_encoding_{}", std::process::id()))
+            .join(name);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+}
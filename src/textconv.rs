@@ -0,0 +1,350 @@
+use crate::scan;
+
+const CONTEXT: usize = 3;
+
+/// Replace every plantuml link and bare payload found by [`crate::scan_plantuml`]
+/// with its decoded source on separate lines, so line diffs of the text show
+/// changes of the diagrams (git `textconv`).
+///
+/// Broken payloads are kept with the decoding error.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::textconv_plantuml;
+///
+/// assert_eq!(
+///     textconv_plantuml("![a](https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000)"),
+///     "![a](\n--- plantuml svg https://www.plantuml.com/plantuml ---\n@startuml\nPUML -> RUST\n@enduml\n--- end plantuml ---\n)"
+/// );
+/// ```
+pub fn textconv_plantuml<T: AsRef<str>>(text: T) -> String {
+    let text = text.as_ref();
+    let mut output = String::with_capacity(text.len());
+    let mut position = 0;
+
+    for found in scan::scan_plantuml(text) {
+        let header = match &found.url {
+            Some(url) => format!("{} {}", url.format.as_str(), url.server),
+            None => found.kind.as_str().to_string(),
+        };
+
+        let body = match &found.decoded {
+            Ok(plantuml) => plantuml.trim_end_matches('\n').to_string(),
+            Err(err) => format!("{}\n! {}", &text[found.span.clone()], err.0),
+        };
+
+        output.push_str(&text[position..found.span.start]);
+        output.push_str(&format!(
+            "\n--- plantuml {} ---\n{}\n--- end plantuml ---\n",
+            header, body
+        ));
+        position = found.span.end;
+    }
+
+    output.push_str(&text[position..]);
+
+    output
+}
+
+/// Unified diff (3 lines of context) of the texts converted with [`textconv_plantuml`]
+/// (git external diff driver), empty if they are the same
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::textconv_diff_plantuml;
+///
+/// let old = "https://www.plantuml.com/plantuml/svg/~h407374617274756d6c0a41202d3e20420a40656e64756d6c";
+/// let new = "https://www.plantuml.com/plantuml/svg/~h407374617274756d6c0a41202d3e20430a40656e64756d6c";
+///
+/// assert_eq!(
+///     textconv_diff_plantuml(old, new, "a/README.md", "b/README.md"),
+///     "--- a/README.md\n+++ b/README.md\n@@ -1,6 +1,6 @@\n \n --- plantuml svg https://www.plantuml.com/plantuml ---\n @startuml\n-A -> B\n+A -> C\n @enduml\n --- end plantuml ---\n"
+/// );
+/// ```
+pub fn textconv_diff_plantuml<O, N, A, B>(old: O, new: N, old_label: A, new_label: B) -> String
+where
+    O: AsRef<str>,
+    N: AsRef<str>,
+    A: AsRef<str>,
+    B: AsRef<str>,
+{
+    unified_diff(
+        &textconv_plantuml(old),
+        &textconv_plantuml(new),
+        old_label.as_ref(),
+        new_label.as_ref(),
+    )
+}
+
+/// Unified diff of the lines with `---` / `+++` labels (empty if they are equal)
+pub(crate) fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let hunks = unified_hunks(
+        &old.lines().collect::<Vec<_>>(),
+        &new.lines().collect::<Vec<_>>(),
+    );
+
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    format!("--- {}\n+++ {}\n{}", old_label, new_label, hunks)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Line operations turning `old` into `new` (shortest edit script of
+/// the linear space variant of Myers' algorithm)
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+
+    diff_range(old, new, 0, 0, &mut ops);
+
+    ops
+}
+
+/// Operations of `old` and `new` slices starting at the lines `old_start` and `new_start`
+/// (split at the middle snake until the rest is only deleted or only inserted)
+fn diff_range(old: &[&str], new: &[&str], old_start: usize, new_start: usize, ops: &mut Vec<Op>) {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    let (a_start, b_start) = (old_start + prefix, new_start + prefix);
+
+    ops.extend((0..prefix).map(|k| Op::Equal(old_start + k, new_start + k)));
+
+    if a.is_empty() || b.is_empty() {
+        ops.extend((0..a.len()).map(|i| Op::Delete(a_start + i)));
+        ops.extend((0..b.len()).map(|j| Op::Insert(b_start + j)));
+    } else {
+        let (x0, y0, x1, y1) = middle_snake(a, b);
+
+        diff_range(&a[..x0], &b[..y0], a_start, b_start, ops);
+        ops.extend((0..x1 - x0).map(|k| Op::Equal(a_start + x0 + k, b_start + y0 + k)));
+        diff_range(&a[x1..], &b[y1..], a_start + x1, b_start + y1, ops);
+    }
+
+    ops.extend((0..suffix).map(|k| Op::Equal(a_start + a.len() + k, b_start + b.len() + k)));
+}
+
+/// Middle snake `(x0, y0, x1, y1)` of the shortest edit script: equal lines
+/// `a[x0..x1]` and `b[y0..y1]` found by searching from both ends at once
+fn middle_snake(a: &[&str], b: &[&str]) -> (usize, usize, usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2 + 1;
+    let at = |k: isize| (k + max) as usize;
+
+    // furthest `x` on each diagonal `k = x - y`, backwards `x` counts from the end
+    let mut forward = vec![0isize; 2 * max as usize + 1];
+    let mut backward = vec![0isize; 2 * max as usize + 1];
+
+    for d in 0..max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            forward[at(k)] = x;
+
+            if delta % 2 != 0 && (delta - k).abs() < d && x + backward[at(delta - k)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            backward[at(k)] = x;
+
+            if delta % 2 == 0 && (delta - k).abs() <= d && forward[at(delta - k)] + x >= n {
+                return (
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                );
+            }
+        }
+    }
+
+    (0, 0, 0, 0)
+}
+
+fn unified_hunks(old: &[&str], new: &[&str]) -> String {
+    let ops = diff_ops(old, new);
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut output = String::new();
+    let mut index = 0;
+
+    while index < changed.len() {
+        let start = changed[index].saturating_sub(CONTEXT);
+        let mut end = changed[index];
+
+        while index < changed.len() && changed[index] <= end + 2 * CONTEXT + 1 {
+            end = changed[index];
+            index += 1;
+        }
+
+        let end = (end + CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+
+        let (old_start, new_start) = ops[..start].iter().fold((0, 0), |(o, n), op| match op {
+            Op::Equal(..) => (o + 1, n + 1),
+            Op::Delete(_) => (o + 1, n),
+            Op::Insert(_) => (o, n + 1),
+        });
+        let old_count = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Insert(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Delete(_)))
+            .count();
+
+        output.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_count),
+            range(new_start, new_count)
+        ));
+
+        for op in hunk {
+            let line = match op {
+                Op::Equal(i, _) => format!(" {}", old[*i]),
+                Op::Delete(i) => format!("-{}", old[*i]),
+                Op::Insert(j) => format!("+{}", new[*j]),
+            };
+
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_ops, textconv_diff_plantuml, textconv_plantuml, unified_hunks, Op};
+
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_str::PLANTUML_SMALL,
+    };
+
+    #[test]
+    fn it_textconv_plantuml() {
+        assert_eq!(
+            textconv_plantuml(format!("payload: {}\nbroken ~h1\n", PLANTUML_DEFLATED_SMALL)),
+            format!(
+                "payload: \n--- plantuml deflate ---\n{}\n--- end plantuml ---\n\nbroken \n--- plantuml hex ---\n~h1\n! there is a problem during decoding: `Odd number of digits`\n--- end plantuml ---\n\n",
+                PLANTUML_SMALL
+            )
+        );
+        assert_eq!(textconv_plantuml("no diagrams"), "no diagrams");
+    }
+
+    #[test]
+    fn it_unified_hunks() {
+        let old: Vec<String> = (1..=12).map(|i| i.to_string()).collect();
+        let mut new = old.clone();
+
+        new[0] = "one".to_string();
+        new.remove(10);
+        new.push("13".to_string());
+
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+
+        assert_eq!(
+            unified_hunks(&old, &new),
+            "@@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n@@ -8,5 +8,5 @@\n 8\n 9\n 10\n-11\n 12\n+13\n"
+        );
+        assert_eq!(unified_hunks(&old, &old), "");
+        assert_eq!(unified_hunks(&[], &["a"]), "@@ -0,0 +1 @@\n+a\n");
+    }
+
+    #[test]
+    fn it_diff_ops_far_apart_edits() {
+        let old: Vec<String> = (0..20_000).map(|i| format!("A -> B: {}", i)).collect();
+        let mut new = old.clone();
+
+        new[10] = "A -> C".to_string();
+        new.insert(19_990, "C -> B".to_string());
+
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+
+        assert_eq!(
+            diff_ops(&old, &new)
+                .into_iter()
+                .filter(|op| !matches!(op, Op::Equal(..)))
+                .collect::<Vec<_>>(),
+            vec![Op::Delete(10), Op::Insert(10), Op::Insert(19_990)]
+        );
+        assert_eq!(
+            diff_ops(
+                &["a", "b", "c", "a", "b", "b", "a"],
+                &["c", "b", "a", "b", "a", "c"]
+            )
+            .len(),
+            9
+        );
+    }
+
+    #[test]
+    fn it_textconv_diff_plantuml_same() {
+        assert_eq!(textconv_diff_plantuml("a", "a", "a", "b"), "");
+    }
+}
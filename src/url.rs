@@ -0,0 +1,363 @@
+use crate::deflate;
+use crate::errors;
+use crate::hex;
+
+/// Public plantuml server
+pub const PLANTUML_SERVER: &str = "https://www.plantuml.com/plantuml";
+
+/// Default length limit of plantuml server URLs, longer ones are not reliably served
+pub const DEFAULT_MAX_URL_LENGTH: usize = 4000;
+
+/// Output format (path segment) of the plantuml server URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    /// Web editor (`/uml/`)
+    Uml,
+    /// PNG image (`/png/`)
+    Png,
+    /// SVG image (`/svg/`)
+    Svg,
+    /// ASCII art (`/txt/`)
+    Txt,
+}
+
+impl OutputFormat {
+    /// Path segment of the format
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Uml => "uml",
+            OutputFormat::Png => "png",
+            OutputFormat::Svg => "svg",
+            OutputFormat::Txt => "txt",
+        }
+    }
+
+    /// Format by the path segment (`uml`, `png`, `svg` or `txt`, case insensitive)
+    pub fn from_name<T: AsRef<str>>(name: T) -> Option<OutputFormat> {
+        let name = name.as_ref().to_lowercase();
+
+        [
+            OutputFormat::Uml,
+            OutputFormat::Png,
+            OutputFormat::Svg,
+            OutputFormat::Txt,
+        ]
+        .into_iter()
+        .find(|format| format.as_str() == name)
+    }
+}
+
+/// Build plantuml server URL for the encoded plantuml
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{plantuml_url, OutputFormat, PLANTUML_SERVER};
+///
+/// let url = plantuml_url(PLANTUML_SERVER, OutputFormat::Svg, "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000");
+///
+/// assert_eq!(url, "https://www.plantuml.com/plantuml/svg/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000");
+/// ```
+pub fn plantuml_url<S: AsRef<str>, T: AsRef<str>>(
+    server: S,
+    format: OutputFormat,
+    encoded: T,
+) -> String {
+    format!(
+        "{}/{}/{}",
+        server.as_ref().trim_end_matches('/'),
+        format.as_str(),
+        encoded.as_ref()
+    )
+}
+
+/// Plantuml server URL split into the server, the format and the encoded plantuml
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlantumlUrl {
+    /// Server (everything before the format segment)
+    pub server: String,
+    /// Format of the URL
+    pub format: OutputFormat,
+    /// Encoded plantuml (deflate or hex with `~h` prefix)
+    pub encoded: String,
+}
+
+impl PlantumlUrl {
+    /// Parse `http(s)://<server>/<format>/<encoded>` URL (query and fragment are ignored)
+    /// of [`PLANTUML_SERVER`], see [`PlantumlUrl::parse_with_servers`]
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use plantuml_encoding::{FromPlantumlError, OutputFormat, PlantumlUrl};
+    ///
+    /// fn main() -> Result<(), FromPlantumlError> {
+    ///     let url = PlantumlUrl::parse(
+    ///         "https://www.plantuml.com/plantuml/png/SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000",
+    ///     )
+    ///     .unwrap();
+    ///
+    ///     assert_eq!(url.server, "https://www.plantuml.com/plantuml");
+    ///     assert_eq!(url.format, OutputFormat::Png);
+    ///     assert_eq!(url.decode()?, "@startuml\nPUML -> RUST\n@enduml");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn parse<T: AsRef<str>>(url: T) -> Option<PlantumlUrl> {
+        PlantumlUrl::parse_with_servers(url, &[PLANTUML_SERVER])
+    }
+
+    /// Parse `http(s)://<server>/<format>/<encoded>` URL (query and fragment are ignored).
+    ///
+    /// Any image URL may end with `/svg/<name>`, so the URL is a plantuml one only if
+    /// the server is one of `servers`, the payload has `~h` / `~1` prefix or it decodes.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use plantuml_encoding::PlantumlUrl;
+    ///
+    /// assert!(PlantumlUrl::parse_with_servers("http://localhost:8080/svg/broken", &["http://localhost:8080/"]).is_some());
+    /// assert!(PlantumlUrl::parse_with_servers("https://cdn.example.com/assets/svg/logo", &["http://localhost:8080"]).is_none());
+    /// ```
+    pub fn parse_with_servers<T: AsRef<str>, S: AsRef<str>>(
+        url: T,
+        servers: &[S],
+    ) -> Option<PlantumlUrl> {
+        let plantuml_url = PlantumlUrl::parse_any(url.as_ref())?;
+        let encoded = plantuml_url.encoded.as_str();
+        let known = servers
+            .iter()
+            .any(|known| same_server(known.as_ref(), &plantuml_url.server));
+
+        if known
+            || encoded.starts_with("~h")
+            || encoded.starts_with("~1")
+            || decode_plantuml_strict(encoded).is_ok()
+        {
+            Some(plantuml_url)
+        } else {
+            None
+        }
+    }
+
+    /// Parse the URL by its shape only, without checking that it is a plantuml one
+    pub(crate) fn parse_any(url: &str) -> Option<PlantumlUrl> {
+        let url = url.trim();
+
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return None;
+        }
+
+        let url = url.split(['?', '#']).next().unwrap_or_default();
+        let (rest, encoded) = url.rsplit_once('/')?;
+        let (server, format) = rest.rsplit_once('/')?;
+        let host = server.split_once("://").map_or("", |(_, host)| host);
+
+        if encoded.is_empty()
+            || !encoded
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '~'))
+            || host.is_empty()
+        {
+            return None;
+        }
+
+        Some(PlantumlUrl {
+            server: server.to_string(),
+            format: OutputFormat::from_name(format)?,
+            encoded: encoded.to_string(),
+        })
+    }
+
+    /// Decode the plantuml with [`decode_plantuml`]
+    pub fn decode(&self) -> Result<String, errors::FromPlantumlError> {
+        decode_plantuml(&self.encoded)
+    }
+
+    /// Build the URL back
+    pub fn url(&self) -> String {
+        plantuml_url(&self.server, self.format, &self.encoded)
+    }
+}
+
+/// Decode plantuml encoded with deflate or hex (detected by `~h` prefix,
+/// `~1` prefix of deflate is skipped)
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{decode_plantuml, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     assert_eq!(decode_plantuml("~h407374617274756d6c0a40656e64756d6c")?, "@startuml\n@enduml");
+///     assert_eq!(
+///         decode_plantuml("SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000")?,
+///         "@startuml\nPUML -> RUST\n@enduml"
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+pub fn decode_plantuml<T: AsRef<str>>(encoded: T) -> Result<String, errors::FromPlantumlError> {
+    let encoded = encoded.as_ref();
+
+    if encoded.starts_with("~h") {
+        hex::decode_plantuml_hex(encoded)
+    } else {
+        deflate::decode_plantuml_deflate(encoded.strip_prefix("~1").unwrap_or(encoded))
+    }
+}
+
+/// Whether the servers are the same regardless of the scheme, the case and trailing slashes
+fn same_server(a: &str, b: &str) -> bool {
+    let host = |server: &str| {
+        let server = server.trim().trim_end_matches('/');
+
+        server
+            .split_once("://")
+            .map_or(server, |(_, rest)| rest)
+            .to_lowercase()
+    };
+
+    host(a) == host(b)
+}
+
+/// Decode plantuml like [`decode_plantuml`], but truncated or garbage payloads are errors
+pub(crate) fn decode_plantuml_strict(
+    encoded: &str,
+) -> Result<String, (deflate::DecodeStep, String)> {
+    let hex = match encoded.strip_prefix("~h") {
+        Some(hex) => hex,
+        None => {
+            return deflate::decode_plantuml_deflate_strict(
+                encoded.strip_prefix("~1").unwrap_or(encoded),
+            )
+        }
+    };
+
+    if let Some((i, c)) = hex.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err((
+            deflate::DecodeStep::Alphabet,
+            format!("invalid hex character `{}` at {}", c, i + 1),
+        ));
+    }
+
+    let bytes =
+        ::hex::decode(hex).map_err(|err| (deflate::DecodeStep::Alphabet, err.to_string()))?;
+
+    String::from_utf8(bytes).map_err(|err| (deflate::DecodeStep::Utf8, err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_plantuml, decode_plantuml_strict, plantuml_url, OutputFormat, PlantumlUrl,
+        PLANTUML_SERVER,
+    };
+
+    use crate::deflate::DecodeStep;
+    use crate::tests::constants::{
+        plantuml_deflated_str::PLANTUML_DEFLATED_SMALL, plantuml_hex_str::PLANTUML_HEX_SMALL,
+        plantuml_str::PLANTUML_SMALL,
+    };
+
+    #[test]
+    fn it_plantuml_url() {
+        assert_eq!(
+            plantuml_url(PLANTUML_SERVER, OutputFormat::Uml, PLANTUML_DEFLATED_SMALL),
+            format!(
+                "https://www.plantuml.com/plantuml/uml/{}",
+                PLANTUML_DEFLATED_SMALL
+            )
+        );
+    }
+
+    #[test]
+    fn it_plantuml_url_trailing_slash() {
+        assert_eq!(
+            plantuml_url("http://localhost:8080/", OutputFormat::Txt, "~h40"),
+            "http://localhost:8080/txt/~h40"
+        );
+    }
+
+    #[test]
+    fn it_output_format_from_name() {
+        assert_eq!(OutputFormat::from_name("PNG"), Some(OutputFormat::Png));
+        assert_eq!(OutputFormat::from_name("pdf"), None);
+    }
+
+    #[test]
+    fn it_plantuml_url_parse() {
+        let url = PlantumlUrl::parse(format!(
+            "http://localhost:8080/svg/{}?x=1#top",
+            PLANTUML_DEFLATED_SMALL
+        ))
+        .unwrap();
+
+        assert_eq!(
+            url,
+            PlantumlUrl {
+                server: "http://localhost:8080".to_string(),
+                format: OutputFormat::Svg,
+                encoded: PLANTUML_DEFLATED_SMALL.to_string(),
+            }
+        );
+        assert_eq!(
+            url.url(),
+            format!("http://localhost:8080/svg/{}", PLANTUML_DEFLATED_SMALL)
+        );
+        assert_eq!(PlantumlUrl::parse("https://example.com/png/"), None);
+        assert_eq!(PlantumlUrl::parse("https://example.com/pdf/SoWk"), None);
+        assert_eq!(PlantumlUrl::parse("ftp://example.com/png/SoWk"), None);
+        assert_eq!(PlantumlUrl::parse("https://png/SoWk"), None);
+    }
+
+    #[test]
+    fn it_plantuml_url_parse_not_plantuml() {
+        assert_eq!(
+            PlantumlUrl::parse("https://cdn.example.com/assets/svg/logo"),
+            None
+        );
+        assert_eq!(PlantumlUrl::parse("https://example.com/png/SoWk"), None);
+        assert!(PlantumlUrl::parse("https://example.com/png/~1SoWk").is_some());
+        assert!(PlantumlUrl::parse("https://example.com/png/~h1").is_some());
+        assert!(PlantumlUrl::parse("http://WWW.plantuml.com/plantuml/svg/logo").is_some());
+        assert!(PlantumlUrl::parse_with_servers(
+            "https://cdn.example.com/assets/svg/logo",
+            &["https://cdn.example.com/assets"]
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn it_decode_plantuml() {
+        assert_eq!(
+            decode_plantuml(PLANTUML_HEX_SMALL),
+            Ok(PLANTUML_SMALL.to_string())
+        );
+        assert_eq!(
+            decode_plantuml(format!("~1{}", PLANTUML_DEFLATED_SMALL)),
+            Ok(PLANTUML_SMALL.to_string())
+        );
+    }
+
+    #[test]
+    fn it_decode_plantuml_strict() {
+        let step = |encoded: &str| decode_plantuml_strict(encoded).err().map(|e| e.0);
+
+        assert_eq!(
+            decode_plantuml_strict(PLANTUML_HEX_SMALL),
+            Ok(PLANTUML_SMALL.to_string())
+        );
+        assert_eq!(
+            decode_plantuml_strict(&format!("~1{}", PLANTUML_DEFLATED_SMALL)),
+            Ok(PLANTUML_SMALL.to_string())
+        );
+        assert_eq!(step("~h40zz"), Some(DecodeStep::Alphabet));
+        assert_eq!(step("~h407"), Some(DecodeStep::Alphabet));
+        assert_eq!(step("~hff"), Some(DecodeStep::Utf8));
+        assert_eq!(step("~1SoWkIImgAStDuGe8"), Some(DecodeStep::Inflate));
+    }
+}
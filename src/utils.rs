@@ -1,3 +1,8 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors;
+
 fn encode_6_bit(mut b: u8) -> String {
     if b < 10 {
         return String::from((48 + b) as char);
@@ -111,9 +116,94 @@ pub fn decode_plantuml_for_deflate(decoded_string: &str) -> Option<Vec<u8>> {
     Some(result)
 }
 
+/// Single line label with escaped line breaks
+pub fn escape_label(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\n', "\\n")
+}
+
+/// Quoted name with escaped line breaks and quotes
+pub fn quote_name(name: &str) -> String {
+    format!("\"{}\"", escape_label(name).replace('"', "&#34;"))
+}
+
+/// Name as is if it is a simple identifier (and not a keyword), quoted otherwise
+pub fn name_or_quoted(name: &str, keywords: &[&str]) -> String {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        && !keywords.contains(&name)
+    {
+        name.to_string()
+    } else {
+        quote_name(name)
+    }
+}
+
+/// Files of the directory and its subdirectories sorted by path
+/// (optionally skipping files and directories starting with `.`)
+pub fn walk_files(
+    dir: &Path,
+    skip_hidden: bool,
+) -> Result<Vec<PathBuf>, errors::FromPlantumlError> {
+    let mut files = vec![];
+
+    walk(dir, skip_hidden, &mut files)?;
+    files.sort();
+
+    Ok(files)
+}
+
+/// Content of the text file with the path in the error
+pub fn read_file(path: &Path) -> Result<String, errors::FromPlantumlError> {
+    fs::read_to_string(path).map_err(|err| {
+        errors::FromPlantumlError(format!(
+            "there is a problem during reading `{}`: `{}`",
+            path.display(),
+            err
+        ))
+    })
+}
+
+fn walk(
+    dir: &Path,
+    skip_hidden: bool,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), errors::FromPlantumlError> {
+    let read_error = |err: std::io::Error| {
+        errors::FromPlantumlError(format!(
+            "there is a problem during reading `{}`: `{}`",
+            dir.display(),
+            err
+        ))
+    };
+
+    for entry in fs::read_dir(dir).map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+
+        if skip_hidden
+            && path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            walk(&path, skip_hidden, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{decode_plantuml_for_deflate, encode_plantuml_for_deflate};
+    use super::{
+        decode_plantuml_for_deflate, encode_plantuml_for_deflate, escape_label, name_or_quoted,
+    };
 
     use crate::tests::constants::{
         plantuml_for_deflate_str::{
@@ -158,4 +248,16 @@ mod tests {
     fn it_decode_plantuml_for_deflate_out_of_bounds_error() {
         assert_eq!(decode_plantuml_for_deflate("some strange string"), None);
     }
+
+    #[test]
+    fn it_escape_label() {
+        assert_eq!(escape_label("a\r\nb\nc"), "a\\nb\\nc");
+    }
+
+    #[test]
+    fn it_name_or_quoted() {
+        assert_eq!(name_or_quoted("Api_v2", &["end"]), "Api_v2");
+        assert_eq!(name_or_quoted("end", &["end"]), "\"end\"");
+        assert_eq!(name_or_quoted("My \"API\"", &[]), "\"My &#34;API&#34;\"");
+    }
 }
@@ -0,0 +1,837 @@
+use std::fmt;
+
+use crate::deflate;
+use crate::errors;
+use crate::minify;
+use crate::semantic;
+
+const NOTE_CLOSERS: &[&str] = &[
+    "end note",
+    "endnote",
+    "end rnote",
+    "endrnote",
+    "end hnote",
+    "endhnote",
+];
+const LEGEND_CLOSERS: &[&str] = &["endlegend", "end legend"];
+const TITLE_CLOSERS: &[&str] = &["endtitle", "end title"];
+const HEADER_CLOSERS: &[&str] = &["endheader", "end header"];
+const FOOTER_CLOSERS: &[&str] = &["endfooter", "end footer"];
+const GROUP_CLOSERS: &[&str] = &["end", "end group"];
+const BOX_CLOSERS: &[&str] = &["end box", "endbox"];
+const IF_CLOSERS: &[&str] = &["endif", "end if"];
+const WHILE_CLOSERS: &[&str] = &["endwhile", "end while"];
+const REPEAT_CLOSERS: &[&str] = &["repeat while", "repeatwhile"];
+const FORK_CLOSERS: &[&str] = &["end fork", "endfork", "end merge", "endmerge"];
+const SPLIT_CLOSERS: &[&str] = &["end split", "endsplit"];
+const SWITCH_CLOSERS: &[&str] = &["endswitch", "end switch"];
+pub(crate) const BRACE_CLOSERS: &[&str] = &["}"];
+
+const ALL_CLOSERS: [&[&str]; 14] = [
+    NOTE_CLOSERS,
+    LEGEND_CLOSERS,
+    TITLE_CLOSERS,
+    HEADER_CLOSERS,
+    FOOTER_CLOSERS,
+    GROUP_CLOSERS,
+    BOX_CLOSERS,
+    IF_CLOSERS,
+    WHILE_CLOSERS,
+    REPEAT_CLOSERS,
+    FORK_CLOSERS,
+    SPLIT_CLOSERS,
+    SWITCH_CLOSERS,
+    BRACE_CLOSERS,
+];
+
+const GROUPS: [&str; 8] = [
+    "alt", "opt", "loop", "par", "par2", "break", "critical", "group",
+];
+
+/// Elements whose `{ ... }` bodies are members, not diagram lines
+pub(crate) const MEMBER_BODIES: [&str; 17] = [
+    "class",
+    "abstract",
+    "interface",
+    "enum",
+    "annotation",
+    "entity",
+    "struct",
+    "object",
+    "map",
+    "json",
+    "protocol",
+    "exception",
+    "metaclass",
+    "stereotype",
+    "dataclass",
+    "record",
+    "circle",
+];
+
+/// Words of skinparam names (`sequence` + `arrow` + `color`...), matched case insensitively
+const SKINPARAM_WORDS: &str = "\
+    abstract action activity actor agent align alignment annotation archimate arrow artifact \
+    ascii attribute background bar below between biddable body border boundary box card case \
+    character circle circled class closed cloud collections color component condition \
+    control corner database day default delay designed diagonal diagram diamond dimension \
+    display divider domain dpi end entity enum exception external file fix folder font \
+    footer frame gantt generic group guillemet handwritten head header hexagon hover \
+    hyperlink icon ie inheritance interface json label labels legend length lexical life \
+    lifeline line linetype link lollipop machine mandatory map margin max message milestone \
+    min mindmap monochrome monospaced name newpage node nodesep note object overlapping \
+    package padding page participant partition path person port position private protected \
+    public queue radius ranksep rectangle reference requirement response round same \
+    separator sequence shadowing size spot stack start state stereotype stereotypea \
+    stereotypec stereotypee stereotypei stereotypen storage strategy struct style svg \
+    swimlane tab target task text thickness timeline timing title top underline url use \
+    usecase wbs width wrap yaml";
+
+/// Severity of a [`Diagnostic`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The diagram can not be rendered as intended
+    Error,
+    /// Probably a mistake (e.g. unknown skinparam, ignored by plantuml)
+    Warning,
+}
+
+impl Severity {
+    /// Name of the severity (`error` or `warning`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// Problem found by [`validate_plantuml`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Severity of the problem
+    pub severity: Severity,
+    /// Line of the problem (1-based)
+    pub line: usize,
+    /// Column of the problem in characters (1-based)
+    pub column: usize,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line,
+            self.column,
+            self.severity.as_str(),
+            self.message
+        )
+    }
+}
+
+/// Block opened by a line and closed by one of the closers
+struct Open {
+    keyword: String,
+    closers: &'static [&'static str],
+    /// Text lines (notes, legends...) are not validated
+    verbatim: bool,
+    /// Prefix of names in `skinparam name { ... }` blocks
+    skinparam: Option<String>,
+    /// Members of classes, objects... are not diagram lines
+    members: bool,
+    line: usize,
+    column: usize,
+}
+
+/// Validate plantuml source (or decoded plantuml) without rendering it:
+/// unbalanced `@start*` / `@end*` tags, unclosed `note`, `group` / `alt` / `loop`...,
+/// `box`, activity and `{ ... }` blocks, bad arrow syntax and unknown skinparam names.
+///
+/// Only `@startuml` diagrams (or untagged sources) are validated beyond their tags.
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{validate_plantuml, Severity};
+///
+/// let diagnostics = validate_plantuml("@startuml\nalt ok\n  A -> B\nelse\n  A => B\n@enduml");
+///
+/// assert_eq!(diagnostics.len(), 2);
+/// assert_eq!(diagnostics[0].to_string(), "5:5: error: bad arrow syntax `=>`");
+/// assert_eq!(diagnostics[1].to_string(), "2:1: error: `alt` is not closed (expected `end`)");
+/// assert!(validate_plantuml("@startuml\nA -> B\n@enduml").is_empty());
+/// ```
+pub fn validate_plantuml<T: AsRef<str>>(plantuml: T) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut tag: Option<(String, usize, usize)> = None;
+    let mut blocks: Vec<Open> = vec![];
+    let mut in_comment = false;
+
+    for (index, raw) in plantuml.as_ref().lines().enumerate() {
+        let number = index + 1;
+        let verbatim = blocks.last().is_some_and(|open| open.verbatim);
+        let line = if verbatim {
+            raw.to_string()
+        } else {
+            minify::strip_block_comments(raw, &mut in_comment)
+        };
+        let trimmed = line.trim();
+        let indent = line[..line.len() - line.trim_start().len()].chars().count();
+        let column = indent + 1;
+        let error = |column: usize, message: String| Diagnostic {
+            severity: Severity::Error,
+            line: number,
+            column,
+            message,
+        };
+
+        if trimmed.is_empty() || trimmed.starts_with('\'') {
+            continue;
+        }
+
+        if let Some(name) = tag_name(trimmed, "@start") {
+            if let Some((open, line, _)) = &tag {
+                diagnostics.push(error(
+                    column,
+                    format!("`@start{}` inside `@start{}` of line {}", name, open, line),
+                ));
+            }
+
+            close_all(&mut blocks, &mut diagnostics);
+            tag = Some((name.to_string(), number, column));
+            continue;
+        }
+
+        if let Some(name) = tag_name(trimmed, "@end") {
+            match tag.take() {
+                Some((open, _, _)) if open == name => {}
+                Some((open, line, _)) => diagnostics.push(error(
+                    column,
+                    format!(
+                        "`@start{}` of line {} is closed by `@end{}`",
+                        open, line, name
+                    ),
+                )),
+                None => diagnostics.push(error(
+                    column,
+                    format!("`@end{}` without `@start{}`", name, name),
+                )),
+            }
+
+            close_all(&mut blocks, &mut diagnostics);
+            continue;
+        }
+
+        // other diagrams (mindmap, json, gantt...) have their own syntax
+        if tag.as_ref().is_some_and(|(name, _, _)| name != "uml") {
+            continue;
+        }
+
+        let parts = block_line(trimmed);
+        let lowercase = parts.lowercase.as_str();
+
+        if verbatim {
+            if blocks
+                .last()
+                .is_some_and(|open| open.closers.iter().any(|c| is_closer(lowercase, c)))
+            {
+                blocks.pop();
+            }
+
+            continue;
+        }
+
+        if trimmed.starts_with('!') {
+            continue;
+        }
+
+        if let Some(closer) = parts.closer {
+            match blocks
+                .iter()
+                .rposition(|open| open.closers.contains(&closer))
+            {
+                Some(position) => {
+                    while blocks.len() > position + 1 {
+                        diagnostics.push(unclosed(&blocks.pop().unwrap()));
+                    }
+
+                    blocks.pop();
+                }
+                // a bare `end` without an open group is the stop node of activity diagrams
+                None if lowercase == "end" => {}
+                None => {
+                    diagnostics.push(error(column, format!("`{}` without an open block", closer)))
+                }
+            }
+
+            continue;
+        }
+
+        if let Some(prefix) = blocks.last().and_then(|open| open.skinparam.clone()) {
+            let name = trimmed.split_whitespace().next().unwrap_or_default();
+
+            diagnostics.extend(unknown_skinparam(
+                &format!("{}{}", prefix, name),
+                name,
+                number,
+                column,
+            ));
+            continue;
+        }
+
+        let first = parts.first.as_str();
+        let rest = parts.rest;
+        let open = |keyword: &str, closers: &'static [&'static str], verbatim: bool| Open {
+            keyword: keyword.to_string(),
+            closers,
+            verbatim,
+            skinparam: None,
+            members: false,
+            line: number,
+            column,
+        };
+
+        if first == "else" || first == "elseif" {
+            let in_branch = blocks
+                .last()
+                .is_some_and(|open| is_branch(first, lowercase, open.closers));
+
+            if !in_branch {
+                diagnostics.push(error(
+                    column,
+                    format!("`{}` outside of a group or `if`", first),
+                ));
+            }
+
+            continue;
+        }
+
+        if let Some((closers, verbatim)) = parts.opener {
+            blocks.push(open(first, closers, verbatim));
+            continue;
+        }
+
+        match first {
+            "skinparam" | "skinparamlocked" => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == '{')
+                    .unwrap_or(rest.len());
+                let name = without_stereotype(&rest[..end]);
+                let name_column = column + trimmed[..trimmed.len() - rest.len()].chars().count();
+
+                if rest[end..].trim_start().starts_with('{') {
+                    blocks.push(Open {
+                        skinparam: Some(name),
+                        ..open(first, BRACE_CLOSERS, false)
+                    });
+                } else {
+                    diagnostics.extend(unknown_skinparam(&name, &name, number, name_column));
+                }
+
+                continue;
+            }
+            _ => {}
+        }
+
+        let in_members = blocks.last().is_some_and(|open| open.members);
+
+        if !in_members {
+            if let Some((offset, arrow)) = bad_arrow(trimmed) {
+                diagnostics.push(error(
+                    column + trimmed[..offset].chars().count(),
+                    format!("bad arrow syntax `{}`", arrow),
+                ));
+                continue;
+            }
+
+            if is_link(trimmed) {
+                continue;
+            }
+        }
+
+        if trimmed.ends_with('{') {
+            blocks.push(Open {
+                members: MEMBER_BODIES.contains(&first),
+                ..open(first, BRACE_CLOSERS, false)
+            });
+        }
+    }
+
+    if let Some((name, line, column)) = tag {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            line,
+            column,
+            message: format!("`@start{}` is not closed by `@end{}`", name, name),
+        });
+    }
+
+    close_all(&mut blocks, &mut diagnostics);
+
+    diagnostics
+}
+
+/// Encode plantuml with deflate compression (as [`crate::encode_plantuml_deflate`])
+/// if [`validate_plantuml`] finds no errors (warnings are allowed)
+///
+/// ## Example
+///
+/// ```rust
+/// use plantuml_encoding::{encode_plantuml_deflate_checked, FromPlantumlError};
+///
+/// fn main() -> Result<(), FromPlantumlError> {
+///     assert_eq!(
+///         encode_plantuml_deflate_checked("@startuml\nPUML -> RUST\n@enduml")?,
+///         "SoWkIImgAStDuGe8zVLHqBLJ20eD3k5oICrB0Ge20000"
+///     );
+///     assert_eq!(
+///         encode_plantuml_deflate_checked("@startuml\nPUML -> RUST"),
+///         Err(FromPlantumlError(
+///             "plantuml is not valid: 1:1: error: `@startuml` is not closed by `@enduml`".to_string()
+///         ))
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+pub fn encode_plantuml_deflate_checked<T: AsRef<str>>(
+    plantuml: T,
+) -> Result<String, errors::FromPlantumlError> {
+    let errors: Vec<String> = validate_plantuml(plantuml.as_ref())
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+
+    if !errors.is_empty() {
+        return Err(errors::FromPlantumlError(format!(
+            "plantuml is not valid: {}",
+            errors.join("; ")
+        )));
+    }
+
+    deflate::encode_plantuml_deflate(plantuml)
+}
+
+/// Name of the `@start*` / `@end*` tag (`uml` for `@startuml(id=x)`)
+pub(crate) fn tag_name<'a>(trimmed: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = trimmed.strip_prefix(prefix)?;
+    let end = rest
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or(rest.len());
+
+    Some(&rest[..end]).filter(|name| !name.is_empty())
+}
+
+pub(crate) fn is_closer(lowercase: &str, closer: &str) -> bool {
+    if lowercase == closer || closer == "}" && lowercase.starts_with('}') {
+        return true;
+    }
+
+    // `end` alone closes groups, `end note`... are other closers
+    closer != "end"
+        && lowercase.starts_with(closer)
+        && !lowercase[closer.len()..].starts_with(|c: char| c.is_alphanumeric())
+}
+
+/// Closer of any block starting the line (`lowercase` with single spaces)
+/// Line of a `@startuml` diagram split for tracking blocks,
+/// shared by [`validate_plantuml`] and [`crate::format_plantuml`]
+pub(crate) struct BlockLine<'a> {
+    /// Lowercase line with single spaces between words
+    pub(crate) lowercase: String,
+    /// First word of the line in lowercase
+    pub(crate) first: String,
+    /// Rest of the line after the first word (as written)
+    pub(crate) rest: &'a str,
+    /// Closer the line is (`end`, `endif`, `}`...)
+    pub(crate) closer: Option<&'static str>,
+    /// Closers of the block the line opens and whether its content is text
+    pub(crate) opener: Option<(&'static [&'static str], bool)>,
+}
+
+/// Split the trimmed line into its first word, the rest, the closer and the opened block
+pub(crate) fn block_line(trimmed: &str) -> BlockLine<'_> {
+    let lowercase = trimmed
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase();
+
+    // the token is taken from the line itself, lowercase may change lengths of characters
+    let token = trimmed
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .unwrap_or_default();
+    let first = token.to_lowercase();
+    let rest = trimmed[token.len()..].trim_start();
+
+    BlockLine {
+        closer: closer_of(&lowercase),
+        opener: keyword_block(&first, rest, &lowercase),
+        lowercase,
+        first,
+        rest,
+    }
+}
+
+fn closer_of(lowercase: &str) -> Option<&'static str> {
+    ALL_CLOSERS
+        .iter()
+        .flat_map(|closers| closers.iter())
+        .find(|closer| is_closer(lowercase, closer))
+        .copied()
+}
+
+/// Closers of the block opened by a keyword line (`alt`, `if`, `note`...)
+/// and whether its content is text, not diagram lines
+fn keyword_block(
+    first: &str,
+    rest: &str,
+    lowercase: &str,
+) -> Option<(&'static [&'static str], bool)> {
+    match first {
+        "note" | "hnote" | "rnote" if !rest.contains(':') && !rest.starts_with('"') => {
+            Some((NOTE_CLOSERS, true))
+        }
+        "legend" if is_text_block(lowercase) => Some((LEGEND_CLOSERS, true)),
+        "title" if is_text_block(lowercase) => Some((TITLE_CLOSERS, true)),
+        "header" if is_text_block(lowercase) => Some((HEADER_CLOSERS, true)),
+        "footer" if is_text_block(lowercase) => Some((FOOTER_CLOSERS, true)),
+        "break" if rest.is_empty() => None,
+        group if GROUPS.contains(&group) => Some((GROUP_CLOSERS, false)),
+        "box" => Some((BOX_CLOSERS, false)),
+        "if" => Some((IF_CLOSERS, false)),
+        "while" => Some((WHILE_CLOSERS, false)),
+        "switch" => Some((SWITCH_CLOSERS, false)),
+        "repeat" => Some((REPEAT_CLOSERS, false)),
+        "fork" if !lowercase.ends_with(" again") => Some((FORK_CLOSERS, false)),
+        "split" if !lowercase.ends_with(" again") => Some((SPLIT_CLOSERS, false)),
+        _ => None,
+    }
+}
+
+/// Line starting another branch of the block (`else`, `fork again`, `case`...)
+pub(crate) fn is_branch(first: &str, lowercase: &str, closers: &[&str]) -> bool {
+    match first {
+        "else" | "elseif" => closers == GROUP_CLOSERS || closers == IF_CLOSERS,
+        "fork" => closers == FORK_CLOSERS && lowercase == "fork again",
+        "split" => closers == SPLIT_CLOSERS && lowercase == "split again",
+        "case" => closers == SWITCH_CLOSERS,
+        _ => false,
+    }
+}
+
+/// `legend`, `title`... without text on the same line (with alignments)
+fn is_text_block(lowercase: &str) -> bool {
+    lowercase
+        .split_whitespace()
+        .skip(1)
+        .all(|word| matches!(word, "left" | "right" | "center" | "top" | "bottom"))
+}
+
+fn unclosed(open: &Open) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        line: open.line,
+        column: open.column,
+        message: format!(
+            "`{}` is not closed (expected `{}`)",
+            open.keyword, open.closers[0]
+        ),
+    }
+}
+
+fn close_all(blocks: &mut Vec<Open>, diagnostics: &mut Vec<Diagnostic>) {
+    diagnostics.extend(blocks.drain(..).rev().map(|open| unclosed(&open)));
+}
+
+/// Byte offset and text of a malformed arrow of a link line (`A -> B`)
+pub(crate) fn bad_arrow(line: &str) -> Option<(usize, String)> {
+    let (arrow, rest, offset) = arrow_of(line)?;
+
+    // `{` / `}` are crow's feet of entity relationships, `(` / `)` lollipops
+    if arrow.contains(['{', '}', '(', ')']) {
+        return None;
+    }
+
+    // `A - > B`
+    if rest.starts_with(['<', '>', '|']) {
+        let next = rest.split_whitespace().next().unwrap_or_default();
+
+        return Some((offset, format!("{} {}", arrow, next)));
+    }
+
+    let valid =
+        semantic::normalize_arrow(arrow).is_some_and(|normalized| is_well_formed(&normalized));
+
+    Some((offset, arrow.to_string())).filter(|_| !valid)
+}
+
+pub(crate) fn is_link(line: &str) -> bool {
+    arrow_of(line).is_some()
+}
+
+/// Arrow after the first operand of the line, the rest and the byte offset of the arrow
+pub(crate) fn arrow_of(line: &str) -> Option<(&str, &str, usize)> {
+    let (_, rest) = semantic::operand(line)?;
+    let (_, rest) = semantic::multiplicity(rest.trim_start());
+    let rest = rest.trim_start();
+    let offset = line.len() - rest.len();
+
+    let first = rest.chars().next()?;
+    let starts = "-.=<>*|#/\\+^}".contains(first)
+        || ("ox".contains(first) && rest[1..].starts_with(['-', '.']));
+
+    if !starts {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut run_end = rest.len();
+
+    for (i, c) in rest.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' if depth > 0 => depth -= 1,
+            c if depth == 0 && (c.is_whitespace() || c == '"' || c == ':') => {
+                run_end = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let run = &rest[..run_end];
+    let is_arrow = run.contains(['-', '.']) || (run.contains('=') && run.contains(['<', '>']));
+
+    if !is_arrow {
+        return None;
+    }
+
+    let after = rest[run_end..].trim_start();
+
+    // operand glued to the arrow (`A->B`, `A ->]`)
+    let arrow_end = if after.is_empty() || after.starts_with(':') {
+        run.rfind(|c: char| "-.=>|*\\/".contains(c))
+            .map_or(run.len(), |i| i + 1)
+    } else {
+        run_end
+    };
+
+    Some((&rest[..arrow_end], rest[arrow_end..].trim_start(), offset))
+}
+
+/// Heads and a body of `-` or `.` (`<|--`, `-->`, `..>`, `->>`, `o--*`...)
+fn is_well_formed(arrow: &str) -> bool {
+    const LEFT: [&str; 13] = [
+        "<<", "<|", "//", "\\\\", "<", "*", "o", "+", "#", "x", "/", "\\", "^",
+    ];
+    const RIGHT: [&str; 13] = [
+        ">>", "|>", "//", "\\\\", ">", "*", "o", "+", "#", "x", "/", "\\", "^",
+    ];
+
+    // `o` / `x` outside of heads (`->x`, `o<-`)
+    let body = match arrow.strip_suffix(['o', 'x']) {
+        Some(body) if body.ends_with(['>', '/', '\\']) => body,
+        _ => arrow,
+    };
+    let body = match body.strip_prefix(['o', 'x']) {
+        Some(body) if body.starts_with(['<', '/', '\\']) => body,
+        _ => body,
+    };
+    let body = LEFT
+        .iter()
+        .find_map(|head| body.strip_prefix(head))
+        .unwrap_or(body);
+    let body = RIGHT
+        .iter()
+        .find_map(|head| body.strip_suffix(head))
+        .unwrap_or(body);
+
+    !body.is_empty() && (body.chars().all(|c| c == '-') || body.chars().all(|c| c == '.'))
+}
+
+/// `class<<Entity>>` -> `class`
+fn without_stereotype(name: &str) -> String {
+    match (name.find("<<"), name.rfind(">>")) {
+        (Some(start), Some(end)) if start < end => {
+            format!("{}{}", &name[..start], &name[end + 2..])
+        }
+        _ => name.to_string(),
+    }
+}
+
+fn unknown_skinparam(name: &str, shown: &str, line: usize, column: usize) -> Option<Diagnostic> {
+    let name = without_stereotype(name).to_lowercase();
+
+    if name.is_empty() || is_skinparam(&name) {
+        return None;
+    }
+
+    Some(Diagnostic {
+        severity: Severity::Warning,
+        line,
+        column,
+        message: format!("unknown skinparam `{}`", shown),
+    })
+}
+
+/// Whether the lowercase name is a concatenation of [`SKINPARAM_WORDS`]
+fn is_skinparam(name: &str) -> bool {
+    let mut reachable = vec![false; name.len() + 1];
+
+    reachable[0] = true;
+
+    for start in 0..name.len() {
+        if !reachable[start] {
+            continue;
+        }
+
+        for word in SKINPARAM_WORDS.split_whitespace() {
+            if name[start..].starts_with(word) {
+                reachable[start + word.len()] = true;
+            }
+        }
+    }
+
+    reachable[name.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        block_line, encode_plantuml_deflate_checked, is_well_formed, validate_plantuml, Severity,
+        NOTE_CLOSERS,
+    };
+
+    use crate::tests::constants::plantuml_str::{PLANTUML_LARGE, PLANTUML_SMALL};
+
+    fn messages(plantuml: &str) -> Vec<String> {
+        validate_plantuml(plantuml)
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn it_block_line() {
+        let parts = block_line("Note  LEFT of \u{212A}");
+
+        assert_eq!(
+            (parts.lowercase.as_str(), parts.first.as_str(), parts.rest),
+            ("note left of k", "note", "LEFT of \u{212A}")
+        );
+        assert_eq!(parts.opener, Some((NOTE_CLOSERS, true)));
+        assert_eq!(block_line("\u{212A}(x) -> B").rest, "(x) -> B");
+        assert_eq!(block_line("End   Note").closer, Some("end note"));
+    }
+
+    #[test]
+    fn it_validate_plantuml_valid() {
+        assert!(validate_plantuml(PLANTUML_SMALL).is_empty());
+        assert!(validate_plantuml(PLANTUML_LARGE).is_empty());
+        assert!(validate_plantuml(
+            "@startuml\nskinparam class {\n  BackgroundColor<<Entity>> red\n  ArrowColor blue\n}\n\
+             package a.b {\n  class A {\n    +x -> y\n  }\n  A <|-- B\n  A \"1\" *-- \"many\" C : has\n}\n\
+             note left of A\n  end\n  x => y\nend note\nE ||--o{ F\n[*] --> S\nA -[#red,dashed]up-> B\n@enduml"
+        )
+        .is_empty());
+        assert!(validate_plantuml(
+            "start\nif (a?) then (yes)\n  :x;\nelseif (b?) then\n  :y;\nelse\n  fork\n    :z;\n  fork again\n    :w;\n  end fork\nendif\nwhile (c?)\nendwhile\nstop"
+        )
+        .is_empty());
+        assert!(validate_plantuml("@startuml\nstart\n:a;\nend\n@enduml").is_empty());
+        assert!(validate_plantuml(
+            "@startuml\nstart\nif (a?) then\n  :b;\n  end\nendif\n:c;\nstop\n@enduml"
+        )
+        .is_empty());
+        assert!(validate_plantuml("@startmindmap\n* a\n** b -> {\n@endmindmap").is_empty());
+    }
+
+    #[test]
+    fn it_validate_plantuml_tags() {
+        assert_eq!(
+            messages("@startuml\nA -> B\n@startuml\n@endmindmap\n@enduml"),
+            vec![
+                "3:1: error: `@startuml` inside `@startuml` of line 1",
+                "4:1: error: `@startuml` of line 3 is closed by `@endmindmap`",
+                "5:1: error: `@enduml` without `@startuml`",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_validate_plantuml_blocks() {
+        assert_eq!(
+            messages("@startuml\nalt x\n  loop 10\n    A -> B\nend\npackage P {\n  note over A\n  A -> B\n@enduml"),
+            vec![
+                "7:3: error: `note` is not closed (expected `end note`)",
+                "6:1: error: `package` is not closed (expected `}`)",
+                "2:1: error: `alt` is not closed (expected `end`)",
+            ]
+        );
+        assert_eq!(
+            messages("A -> B\nend group\nelse\n}"),
+            vec![
+                "2:1: error: `end group` without an open block",
+                "3:1: error: `else` outside of a group or `if`",
+                "4:1: error: `}` without an open block",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_validate_plantuml_arrows() {
+        assert_eq!(
+            messages(
+                "A -> B\nA - > B\nA -.-> B : x\nA->>>B\nA -lft-> B\nA <|-- B\nA ->x B\nA ->] : out"
+            ),
+            vec![
+                "2:3: error: bad arrow syntax `- >`",
+                "3:3: error: bad arrow syntax `-.->`",
+                "4:2: error: bad arrow syntax `->>>`",
+                "5:3: error: bad arrow syntax `-lft->`",
+            ]
+        );
+        assert!(is_well_formed("<-->>"));
+        assert!(is_well_formed("o..*"));
+        assert!(is_well_formed("o<->x"));
+        assert!(!is_well_formed("<>"));
+    }
+
+    #[test]
+    fn it_validate_plantuml_skinparams() {
+        let diagnostics = validate_plantuml(
+            "skinparam sequenceArowColor red\nskinparam sequence {\n  ArrowColour red\n}",
+        );
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "1:11: warning: unknown skinparam `sequenceArowColor`",
+                "3:3: warning: unknown skinparam `ArrowColour`",
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn it_validate_plantuml_non_ascii_first_token() {
+        assert!(validate_plantuml("@startuml\n\u{2126} -> B\n@enduml").is_empty());
+        assert!(validate_plantuml("@startuml\n\u{212A}(x) -> B\n@enduml").is_empty());
+        assert!(encode_plantuml_deflate_checked("\u{2126} -> B").is_ok());
+    }
+
+    #[test]
+    fn it_encode_plantuml_deflate_checked() {
+        assert!(encode_plantuml_deflate_checked("skinparam foo bar\nA -> B").is_ok());
+        assert!(encode_plantuml_deflate_checked("A => B").is_err());
+    }
+}